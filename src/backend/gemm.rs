@@ -0,0 +1,256 @@
+use crate::tensor::Float;
+
+///
+/// The depth of the blocks of A and B that are packed at once, so that a packed panel of B stays in the L1 cache while
+/// the panels of A stream past it
+///
+const KC: usize = 256;
+
+///
+/// The rows of A packed at once, which stay in the L2 cache
+///
+const MC: usize = 96;
+
+///
+/// The columns of B packed at once, which stay in the L3 cache
+///
+const NC: usize = 4096;
+
+///
+/// Calculates an `MR x NR` tile of C from a packed panel of A, `kc` columns of `MR` rows, and a packed panel of B,
+/// `kc` rows of `NR` columns. The tile starts at zero
+///
+/// # Safety
+///
+/// A SIMD micro-kernel may only be called on a CPU with the instructions it uses. `f32_micro_kernel` and
+/// `f64_micro_kernel` only hand out kernels the CPU supports.
+///
+pub (super) type MicroKernel<E, const MR: usize, const NR: usize> = unsafe fn(usize, &[E], &[E], &mut [[E; NR]; MR]);
+
+///
+/// A read-only matrix with arbitrary strides, so that a transposed matrix is the same data with its strides swapped
+///
+#[derive(Clone, Copy)]
+pub (super) struct MatRef<'a, E> {
+    data: &'a [E],
+    offset: usize,
+    pub rows: usize,
+    pub cols: usize,
+    row_stride: usize,
+    col_stride: usize,
+}
+
+impl<'a, E: Float> MatRef<'a, E> {
+    ///
+    /// A contiguous row-major matrix
+    ///
+    pub fn new(data: &'a [E], rows: usize, cols: usize) -> Self {
+        assert!(data.len() >= rows * cols, "A {rows}x{cols} matrix needs {} elements, found {}", rows * cols, data.len());
+
+        Self { data, offset: 0, rows, cols, row_stride: cols, col_stride: 1 }
+    }
+
+    pub fn t(self) -> Self {
+        Self {
+            rows: self.cols,
+            cols: self.rows,
+            row_stride: self.col_stride,
+            col_stride: self.row_stride,
+            ..self
+        }
+    }
+
+    pub fn row_range(self, start: usize, rows: usize) -> Self {
+        assert!(start + rows <= self.rows);
+
+        Self { offset: self.offset + start * self.row_stride, rows, ..self }
+    }
+
+    pub fn col_range(self, start: usize, cols: usize) -> Self {
+        assert!(start + cols <= self.cols);
+
+        Self { offset: self.offset + start * self.col_stride, cols, ..self }
+    }
+
+    fn at(&self, row: usize, col: usize) -> E {
+        self.data[self.offset + row * self.row_stride + col * self.col_stride]
+    }
+}
+
+///
+/// c += a * b, where c is a contiguous row-major `a.rows x b.cols` matrix
+///
+/// A and B are packed block by block into panels that the micro-kernel reads in order, padded with zeros to whole
+/// panels, so every element of C is calculated the same way wherever it falls in a block. Each element adds up its
+/// terms in blocks of `KC`, which makes the result independent of how C is split between threads.
+///
+pub (super) fn gemm<E: Float, const MR: usize, const NR: usize>(micro_kernel: MicroKernel<E, MR, NR>, a: MatRef<E>, b: MatRef<E>, c: &mut [E]) {
+    let (m, k, n) = (a.rows, a.cols, b.cols);
+
+    assert_eq!(b.rows, k, "Cannot multiply a {m}x{k} matrix by a {}x{n} one", b.rows);
+    assert_eq!(c.len(), m * n);
+
+    let mut packed_a = Vec::with_capacity(MC.min(m).next_multiple_of(MR) * KC.min(k));
+    let mut packed_b = Vec::with_capacity(NC.min(n).next_multiple_of(NR) * KC.min(k));
+
+    for jc in (0..n).step_by(NC) {
+        let nc = NC.min(n - jc);
+
+        for pc in (0..k).step_by(KC) {
+            let kc = KC.min(k - pc);
+
+            pack(&mut packed_b, b.row_range(pc, kc).col_range(jc, nc).t(), NR);
+
+            for ic in (0..m).step_by(MC) {
+                let mc = MC.min(m - ic);
+
+                pack(&mut packed_a, a.row_range(ic, mc).col_range(pc, kc), MR);
+
+                for (jr, panel_b) in packed_b.chunks(kc * NR).enumerate().map(|(p, panel)| (p * NR, panel)) {
+                    for (ir, panel_a) in packed_a.chunks(kc * MR).enumerate().map(|(p, panel)| (p * MR, panel)) {
+                        let mut tile = [[E::zero(); NR]; MR];
+
+                        // SAFETY: the micro-kernels are only handed out for CPUs that support them
+                        unsafe { micro_kernel(kc, panel_a, panel_b, &mut tile) };
+
+                        for (i, row) in tile.iter().enumerate().take(mc - ir) {
+                            let start = (ic + ir + i) * n + jc + jr;
+
+                            for (c, t) in c[start..].iter_mut().zip(row).take(nc - jr) {
+                                *c += *t;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+///
+/// Packs the rows of `matrix` into panels of `width` rows, each stored column by column and padded with zero rows
+///
+/// B is packed through its transpose, so that its panels of columns are stored row by row.
+///
+fn pack<E: Float>(packed: &mut Vec<E>, matrix: MatRef<E>, width: usize) {
+    packed.clear();
+
+    for start in (0..matrix.rows).step_by(width) {
+        for col in 0..matrix.cols {
+            for row in start..start + width {
+                packed.push(if row < matrix.rows { matrix.at(row, col) } else { E::zero() });
+            }
+        }
+    }
+}
+
+///
+/// The portable micro-kernel, used for types without SIMD kernels and on CPUs without the instructions they need
+///
+pub (super) fn scalar<E: Float, const MR: usize, const NR: usize>(kc: usize, a: &[E], b: &[E], tile: &mut [[E; NR]; MR]) {
+    for (a, b) in a.chunks_exact(MR).zip(b.chunks_exact(NR)).take(kc) {
+        for (row, a) in tile.iter_mut().zip(a) {
+            for (t, b) in row.iter_mut().zip(b) {
+                *t += *a * *b;
+            }
+        }
+    }
+}
+
+pub (super) fn f32_micro_kernel() -> MicroKernel<f32, 6, 16> {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+        return x86::f32_6x16;
+    }
+
+    scalar
+}
+
+pub (super) fn f64_micro_kernel() -> MicroKernel<f64, 6, 8> {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+        return x86::f64_6x8;
+    }
+
+    scalar
+}
+
+///
+/// AVX2 micro-kernels, which keep the whole tile in registers as two vectors per row, and add a broadcast element of
+/// A times a row of B to each row with a fused multiply-add
+///
+/// They are only handed out once the CPU is known to support AVX2 and FMA.
+///
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support AVX2 and FMA. The lengths of the panels are checked
+    ///
+    pub unsafe fn f32_6x16(kc: usize, a: &[f32], b: &[f32], tile: &mut [[f32; 16]; 6]) {
+        assert!(a.len() >= kc * 6 && b.len() >= kc * 16);
+
+        // SAFETY: the caller ensures the CPU supports AVX2 and FMA, and the panels were checked to be long enough for
+        // every load
+        unsafe { f32_6x16_avx2(kc, a.as_ptr(), b.as_ptr(), tile) }
+    }
+
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support AVX2 and FMA. The lengths of the panels are checked
+    ///
+    pub unsafe fn f64_6x8(kc: usize, a: &[f64], b: &[f64], tile: &mut [[f64; 8]; 6]) {
+        assert!(a.len() >= kc * 6 && b.len() >= kc * 8);
+
+        // SAFETY: as for f32_6x16
+        unsafe { f64_6x8_avx2(kc, a.as_ptr(), b.as_ptr(), tile) }
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn f32_6x16_avx2(kc: usize, a: *const f32, b: *const f32, tile: &mut [[f32; 16]; 6]) {
+        let mut acc = [[_mm256_setzero_ps(); 2]; 6];
+
+        for p in 0..kc {
+            let b0 = _mm256_loadu_ps(b.add(p * 16));
+            let b1 = _mm256_loadu_ps(b.add(p * 16 + 8));
+
+            for (i, acc) in acc.iter_mut().enumerate() {
+                let a = _mm256_broadcast_ss(&*a.add(p * 6 + i));
+
+                acc[0] = _mm256_fmadd_ps(a, b0, acc[0]);
+                acc[1] = _mm256_fmadd_ps(a, b1, acc[1]);
+            }
+        }
+
+        for (row, acc) in tile.iter_mut().zip(acc) {
+            _mm256_storeu_ps(row.as_mut_ptr(), acc[0]);
+            _mm256_storeu_ps(row.as_mut_ptr().add(8), acc[1]);
+        }
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn f64_6x8_avx2(kc: usize, a: *const f64, b: *const f64, tile: &mut [[f64; 8]; 6]) {
+        let mut acc = [[_mm256_setzero_pd(); 2]; 6];
+
+        for p in 0..kc {
+            let b0 = _mm256_loadu_pd(b.add(p * 8));
+            let b1 = _mm256_loadu_pd(b.add(p * 8 + 4));
+
+            for (i, acc) in acc.iter_mut().enumerate() {
+                let a = _mm256_broadcast_sd(&*a.add(p * 6 + i));
+
+                acc[0] = _mm256_fmadd_pd(a, b0, acc[0]);
+                acc[1] = _mm256_fmadd_pd(a, b1, acc[1]);
+            }
+        }
+
+        for (row, acc) in tile.iter_mut().zip(acc) {
+            _mm256_storeu_pd(row.as_mut_ptr(), acc[0]);
+            _mm256_storeu_pd(row.as_mut_ptr().add(4), acc[1]);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use half::{bf16, f16};
+
+use crate::tensor::{DType, Float, Layout};
+
+mod gemm;
+mod naive;
+mod packed;
+mod threaded;
+
+#[cfg(test)]
+mod tests;
+
+pub use naive::Naive;
+pub use packed::Packed;
+pub use threaded::Threaded;
+
+///
+/// An implementation of the compute kernels that ops run on
+///
+/// Ops keep their bookkeeping (shapes, allocation and the computation graph) to themselves, and hand the number
+/// crunching to the device's backend. A backend gives kernels for every float type. Each type defaults to the
+/// `Naive` reference kernels, so a backend only needs to provide the types it speeds up.
+///
+pub trait Backend: Send + Sync {
+    ///
+    /// The name of the backend, for logs and test failures
+    ///
+    fn name(&self) -> &'static str;
+
+    ///
+    /// How many threads the backend splits its kernels across
+    ///
+    fn threads(&self) -> usize {
+        1
+    }
+
+    fn f32(&self) -> &dyn Kernels<f32> {
+        &Naive
+    }
+
+    fn f64(&self) -> &dyn Kernels<f64> {
+        &Naive
+    }
+
+    fn f16(&self) -> &dyn Kernels<f16> {
+        &Naive
+    }
+
+    fn bf16(&self) -> &dyn Kernels<bf16> {
+        &Naive
+    }
+
+    fn i64(&self) -> &dyn DataKernels<i64> {
+        &Naive
+    }
+
+    fn i32(&self) -> &dyn DataKernels<i32> {
+        &Naive
+    }
+
+    fn u8(&self) -> &dyn DataKernels<u8> {
+        &Naive
+    }
+}
+
+///
+/// The dimensions of a matrix multiplication of an `m x k` matrix by a `k x n` matrix
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatMulDims {
+    pub m: usize,
+    pub k: usize,
+    pub n: usize,
+}
+
+///
+/// The dimensions of a 2D convolution, which keeps the input's dimensions and leaves the last `kernel - 1` rows and
+/// columns of the output at zero
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Conv2dDims {
+    pub input: (usize, usize),
+    pub kernel: (usize, usize),
+}
+
+///
+/// The lengths of the blocks of `lhs` and `rhs` that alternate in the output of a concatenation, which are their
+/// elements from the concatenation axis on
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConcatDims {
+    pub lhs: usize,
+    pub rhs: usize,
+}
+
+///
+/// The length of the axis that an index selection picks entries from, and the number of elements in each entry, which
+/// are those of the later axes
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexSelectDims {
+    pub len: usize,
+    pub inner: usize,
+}
+
+///
+/// The kernels that every element type has, which move data around and add it up without any floating point math
+///
+/// Like `Kernels`, those that produce a gradient add to it rather than overwriting it.
+///
+pub trait DataKernels<E: DType>: Send + Sync {
+    ///
+    /// Sets every element of `output` to the sum of the matching elements of `lhs` and `rhs`
+    ///
+    fn add(&self, lhs: &[E], rhs: &[E], output: &mut [E]);
+
+    ///
+    /// Sets every element of `output` to `value`
+    ///
+    fn broadcast(&self, value: E, output: &mut [E]);
+
+    ///
+    /// Converts every element of `input` to an `f64`, which casts between element types go through
+    ///
+    fn cast_to_f64(&self, input: &[E], output: &mut [f64]);
+
+    ///
+    /// Converts every element of `input` from an `f64`, with the semantics of `DType::from_f64`
+    ///
+    fn cast_from_f64(&self, input: &[f64], output: &mut [E]);
+
+    ///
+    /// Sets `output` to alternating blocks of `lhs` and `rhs`, starting with `lhs`
+    ///
+    fn concat(&self, lhs: &[E], rhs: &[E], output: &mut [E], dims: ConcatDims);
+
+    ///
+    /// Adds each block of the output's gradient to the gradient of the input it came from
+    ///
+    fn concat_gradients(&self, output_gradient: &[E], lhs_gradient: &mut [E], rhs_gradient: &mut [E], dims: ConcatDims);
+
+    ///
+    /// Sets `output` to the inputs one after another
+    ///
+    fn stack(&self, inputs: &[&[E]], output: &mut [E]);
+
+    ///
+    /// Sets row `i` of `output` to row `rows[i]` of `weights`, where rows are `dim` elements long
+    ///
+    fn embedding(&self, weights: &[E], rows: &[usize], output: &mut [E], dim: usize);
+
+    ///
+    /// Adds row `i` of the output's gradient to row `rows[i]` of the gradient of the weights
+    ///
+    fn embedding_gradient(&self, output_gradient: &[E], rows: &[usize], weights_gradient: &mut [E], dim: usize);
+
+    ///
+    /// Sets entry `i` of each block of `output` to entry `indices[i]` of the matching block of `input`, where a block
+    /// is the entries along the selected axis
+    ///
+    fn index_select(&self, input: &[E], indices: &[usize], output: &mut [E], dims: IndexSelectDims);
+
+    ///
+    /// Adds entry `i` of each block of the output's gradient to entry `indices[i]` of the matching block of the
+    /// input's gradient
+    ///
+    fn index_select_gradient(&self, output_gradient: &[E], indices: &[usize], input_gradient: &mut [E], dims: IndexSelectDims);
+
+    ///
+    /// Sets `output` to the elements that `layout` describes in `input`, in row-major order
+    ///
+    fn gather(&self, input: &[E], layout: &Layout, output: &mut [E]);
+
+    ///
+    /// Adds each element of the output's gradient to the element of the input's gradient that `layout` gathered it
+    /// from
+    ///
+    fn gather_gradient(&self, output_gradient: &[E], layout: &Layout, input_gradient: &mut [E]);
+}
+
+///
+/// The kernels for one element type
+///
+/// All matrices are contiguous and row-major. Kernels that produce a matrix add to `output` rather than overwriting
+/// it, so that gradients can be accumulated in place.
+///
+pub trait Kernels<E: Float>: DataKernels<E> {
+    ///
+    /// Sets every element of `output` to `f` of the matching element of `input`
+    ///
+    fn map(&self, input: &[E], output: &mut [E], f: &(dyn Fn(E) -> E + Sync));
+
+    ///
+    /// Sets every element of `output` to `f` of the matching elements of `lhs` and `rhs`
+    ///
+    fn zip(&self, lhs: &[E], rhs: &[E], output: &mut [E], f: &(dyn Fn(E, E) -> E + Sync));
+
+    fn sum(&self, input: &[E]) -> E;
+
+    ///
+    /// output += lhs * rhs
+    ///
+    fn matmul(&self, lhs: &[E], rhs: &[E], output: &mut [E], dims: MatMulDims);
+
+    ///
+    /// lhs_gradient += output_gradient * rhs^T
+    ///
+    fn matmul_lhs_gradient(&self, output_gradient: &[E], rhs: &[E], lhs_gradient: &mut [E], dims: MatMulDims);
+
+    ///
+    /// rhs_gradient += lhs^T * output_gradient
+    ///
+    fn matmul_rhs_gradient(&self, lhs: &[E], output_gradient: &[E], rhs_gradient: &mut [E], dims: MatMulDims);
+
+    ///
+    /// output(k, l) += sum[i, j] input(k + i, l + j) * kernel(i, j)
+    ///
+    fn conv2d(&self, input: &[E], kernel: &[E], output: &mut [E], dims: Conv2dDims);
+
+    ///
+    /// Adds the gradient of a convolution's input, given its kernel and the gradient of its output
+    ///
+    fn conv2d_input_gradient(&self, kernel: &[E], output_gradient: &[E], input_gradient: &mut [E], dims: Conv2dDims);
+
+    ///
+    /// Adds the gradient of a convolution's kernel, given its input and the gradient of its output
+    ///
+    fn conv2d_kernel_gradient(&self, input: &[E], output_gradient: &[E], kernel_gradient: &mut [E], dims: Conv2dDims);
+
+    ///
+    /// Sets `output` to e^input, divided by its sum. The largest input is taken off first so that it can't overflow
+    ///
+    fn softmax(&self, input: &[E], output: &mut [E]);
+
+    ///
+    /// input_gradient += output * (output_gradient - sum(output_gradient * output))
+    ///
+    fn softmax_gradient(&self, output: &[E], output_gradient: &[E], input_gradient: &mut [E]);
+
+    ///
+    /// The mean of the squared differences between `a` and `targets`
+    ///
+    fn mse(&self, a: &[E], targets: &[E]) -> E;
+
+    ///
+    /// Adds (2 / N) * (a - targets), scaled by the gradient of the loss, to `a_gradient`, and takes it from
+    /// `target_gradient`
+    ///
+    fn mse_gradients(&self, a: &[E], targets: &[E], output_gradient: E, a_gradient: &mut [E], target_gradient: &mut [E]);
+
+    ///
+    /// -sum(targets * ln(a))
+    ///
+    fn cross_entropy(&self, a: &[E], targets: &[E]) -> E;
+
+    ///
+    /// Adds -targets / a and -ln(a), scaled by the gradient of the loss, to the gradients of `a` and `targets`.
+    /// Probabilities of zero are skipped rather than giving an infinite gradient
+    ///
+    fn cross_entropy_gradients(&self, a: &[E], targets: &[E], output_gradient: E, a_gradient: &mut [E], target_gradient: &mut [E]);
+
+    ///
+    /// -ln(a[label])
+    ///
+    fn sparse_cross_entropy(&self, a: &[E], label: usize) -> E;
+
+    ///
+    /// Adds -1 / a[label], scaled by the gradient of the loss, to the label's element of `a_gradient`, unless the
+    /// probability is zero
+    ///
+    fn sparse_cross_entropy_gradient(&self, a: &[E], label: usize, output_gradient: E, a_gradient: &mut [E]);
+}
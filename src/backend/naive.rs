@@ -0,0 +1,280 @@
+use crate::tensor::{DType, Float, Layout};
+
+use super::{Backend, ConcatDims, Conv2dDims, DataKernels, IndexSelectDims, Kernels, MatMulDims};
+
+///
+/// Straightforward single-threaded loops, which other backends are checked against
+///
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Naive;
+
+impl Backend for Naive {
+    fn name(&self) -> &'static str {
+        "naive"
+    }
+}
+
+impl<E: DType> DataKernels<E> for Naive {
+    fn add(&self, lhs: &[E], rhs: &[E], output: &mut [E]) {
+        for (o, (a, b)) in output.iter_mut().zip(lhs.iter().zip(rhs)) {
+            *o = *a + *b;
+        }
+    }
+
+    fn broadcast(&self, value: E, output: &mut [E]) {
+        output.fill(value);
+    }
+
+    fn cast_to_f64(&self, input: &[E], output: &mut [f64]) {
+        for (o, i) in output.iter_mut().zip(input) {
+            *o = i.as_f64();
+        }
+    }
+
+    fn cast_from_f64(&self, input: &[f64], output: &mut [E]) {
+        for (o, i) in output.iter_mut().zip(input) {
+            *o = E::from_f64(*i);
+        }
+    }
+
+    fn concat(&self, lhs: &[E], rhs: &[E], output: &mut [E], dims: ConcatDims) {
+        let ConcatDims { lhs: l, rhs: r } = dims;
+
+        for (block, output) in output.chunks_mut(l + r).enumerate() {
+            output[..l].copy_from_slice(&lhs[block * l..(block + 1) * l]);
+            output[l..].copy_from_slice(&rhs[block * r..(block + 1) * r]);
+        }
+    }
+
+    fn concat_gradients(&self, output_gradient: &[E], lhs_gradient: &mut [E], rhs_gradient: &mut [E], dims: ConcatDims) {
+        let ConcatDims { lhs: l, rhs: r } = dims;
+
+        for (block, gradient) in output_gradient.chunks(l + r).enumerate() {
+            for (g, o) in lhs_gradient[block * l..(block + 1) * l].iter_mut().zip(&gradient[..l]) {
+                *g += *o;
+            }
+
+            for (g, o) in rhs_gradient[block * r..(block + 1) * r].iter_mut().zip(&gradient[l..]) {
+                *g += *o;
+            }
+        }
+    }
+
+    fn stack(&self, inputs: &[&[E]], output: &mut [E]) {
+        for (output, input) in output.chunks_mut(output.len() / inputs.len().max(1)).zip(inputs) {
+            output.copy_from_slice(input);
+        }
+    }
+
+    fn embedding(&self, weights: &[E], rows: &[usize], output: &mut [E], dim: usize) {
+        for (output, row) in output.chunks_mut(dim).zip(rows) {
+            output.copy_from_slice(&weights[row * dim..(row + 1) * dim]);
+        }
+    }
+
+    fn embedding_gradient(&self, output_gradient: &[E], rows: &[usize], weights_gradient: &mut [E], dim: usize) {
+        for (gradient, row) in output_gradient.chunks(dim).zip(rows) {
+            for (w, g) in weights_gradient[row * dim..(row + 1) * dim].iter_mut().zip(gradient) {
+                *w += *g;
+            }
+        }
+    }
+
+    fn index_select(&self, input: &[E], indices: &[usize], output: &mut [E], dims: IndexSelectDims) {
+        let IndexSelectDims { len, inner } = dims;
+
+        for (entry, output) in output.chunks_mut(inner).enumerate() {
+            let base = (entry / indices.len() * len + indices[entry % indices.len()]) * inner;
+
+            output.copy_from_slice(&input[base..base + inner]);
+        }
+    }
+
+    fn index_select_gradient(&self, output_gradient: &[E], indices: &[usize], input_gradient: &mut [E], dims: IndexSelectDims) {
+        let IndexSelectDims { len, inner } = dims;
+
+        for (entry, gradient) in output_gradient.chunks(inner).enumerate() {
+            let base = (entry / indices.len() * len + indices[entry % indices.len()]) * inner;
+
+            for (g, o) in input_gradient[base..base + inner].iter_mut().zip(gradient) {
+                *g += *o;
+            }
+        }
+    }
+
+    fn gather(&self, input: &[E], layout: &Layout, output: &mut [E]) {
+        for (o, i) in output.iter_mut().zip(layout.offsets()) {
+            *o = input[i];
+        }
+    }
+
+    fn gather_gradient(&self, output_gradient: &[E], layout: &Layout, input_gradient: &mut [E]) {
+        for (o, i) in output_gradient.iter().zip(layout.offsets()) {
+            input_gradient[i] += *o;
+        }
+    }
+}
+
+impl<E: Float> Kernels<E> for Naive {
+    fn map(&self, input: &[E], output: &mut [E], f: &(dyn Fn(E) -> E + Sync)) {
+        for (o, i) in output.iter_mut().zip(input) {
+            *o = f(*i);
+        }
+    }
+
+    fn zip(&self, lhs: &[E], rhs: &[E], output: &mut [E], f: &(dyn Fn(E, E) -> E + Sync)) {
+        for (o, (a, b)) in output.iter_mut().zip(lhs.iter().zip(rhs)) {
+            *o = f(*a, *b);
+        }
+    }
+
+    fn sum(&self, input: &[E]) -> E {
+        input.iter().cloned().sum()
+    }
+
+    fn matmul(&self, lhs: &[E], rhs: &[E], output: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+
+        for row in 0..m {
+            for j in 0..k {
+                let a = lhs[row * k + j];
+
+                for i in 0..n {
+                    output[row * n + i] += a * rhs[j * n + i];
+                }
+            }
+        }
+    }
+
+    fn matmul_lhs_gradient(&self, output_gradient: &[E], rhs: &[E], lhs_gradient: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+
+        for row in 0..m {
+            for j in 0..k {
+                for i in 0..n {
+                    lhs_gradient[row * k + j] += output_gradient[row * n + i] * rhs[j * n + i];
+                }
+            }
+        }
+    }
+
+    fn matmul_rhs_gradient(&self, lhs: &[E], output_gradient: &[E], rhs_gradient: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+
+        for row in 0..m {
+            for j in 0..k {
+                let a = lhs[row * k + j];
+
+                for i in 0..n {
+                    rhs_gradient[j * n + i] += a * output_gradient[row * n + i];
+                }
+            }
+        }
+    }
+
+    fn conv2d(&self, input: &[E], kernel: &[E], output: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+
+        for i in 0..=(i1 - k1) {
+            for j in 0..=(i2 - k2) {
+                for k in 0..k1 {
+                    for l in 0..k2 {
+                        output[i * i2 + j] += input[(i + k) * i2 + (j + l)] * kernel[k * k2 + l];
+                    }
+                }
+            }
+        }
+    }
+
+    fn conv2d_input_gradient(&self, kernel: &[E], output_gradient: &[E], input_gradient: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+
+        // Z(k, l) = sum[i, j] X(k + i, l + j) * K(i, j), so each product term sends grad Z(k, l) to the input:
+        //
+        // grad X(k + i, l + j) += K(i, j) * grad Z(k, l)
+        for i in 0..k1 {
+            for j in 0..k2 {
+                for k in 0..=(i1 - k1) {
+                    for l in 0..=(i2 - k2) {
+                        input_gradient[(k + i) * i2 + (l + j)] += kernel[i * k2 + j] * output_gradient[k * i2 + l];
+                    }
+                }
+            }
+        }
+    }
+
+    fn conv2d_kernel_gradient(&self, input: &[E], output_gradient: &[E], kernel_gradient: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+
+        // And to the kernel:
+        //
+        // grad K(i, j) += X(k + i, l + j) * grad Z(k, l)
+        for i in 0..k1 {
+            for j in 0..k2 {
+                for k in 0..=(i1 - k1) {
+                    for l in 0..=(i2 - k2) {
+                        kernel_gradient[i * k2 + j] += input[(k + i) * i2 + (l + j)] * output_gradient[k * i2 + l];
+                    }
+                }
+            }
+        }
+    }
+
+    fn softmax(&self, input: &[E], output: &mut [E]) {
+        let max = input.iter().cloned().reduce(E::max).unwrap();
+
+        self.map(input, output, &|i| (i - max).exp());
+        let sum = self.sum(output);
+
+        for o in output.iter_mut() {
+            *o /= sum;
+        }
+    }
+
+    fn softmax_gradient(&self, output: &[E], output_gradient: &[E], input_gradient: &mut [E]) {
+        let dot = output_gradient.iter().zip(output).map(|(g, x)| *g * *x).sum::<E>();
+
+        for (i, (x, g)) in input_gradient.iter_mut().zip(output.iter().zip(output_gradient)) {
+            *i += *x * (*g - dot);
+        }
+    }
+
+    fn mse(&self, a: &[E], targets: &[E]) -> E {
+        a.iter().zip(targets).map(|(a, t)| (*a - *t).powi(2)).sum::<E>() / E::from_f64(a.len() as f64)
+    }
+
+    fn mse_gradients(&self, a: &[E], targets: &[E], output_gradient: E, a_gradient: &mut [E], target_gradient: &mut [E]) {
+        let factor = output_gradient * (E::from_f64(2.0) / E::from_f64(a.len() as f64));
+
+        for (i, (a, t)) in a.iter().zip(targets).enumerate() {
+            let gradient = factor * (*a - *t);
+
+            a_gradient[i] += gradient;
+            target_gradient[i] -= gradient;
+        }
+    }
+
+    fn cross_entropy(&self, a: &[E], targets: &[E]) -> E {
+        -a.iter().zip(targets).map(|(a, t)| *t * a.ln()).sum::<E>()
+    }
+
+    fn cross_entropy_gradients(&self, a: &[E], targets: &[E], output_gradient: E, a_gradient: &mut [E], target_gradient: &mut [E]) {
+        for (i, (a, t)) in a.iter().zip(targets).enumerate() {
+            if !a.is_zero() {
+                a_gradient[i] += -output_gradient * *t / *a;
+            }
+
+            target_gradient[i] += -output_gradient * a.ln();
+        }
+    }
+
+    fn sparse_cross_entropy(&self, a: &[E], label: usize) -> E {
+        -a[label].ln()
+    }
+
+    fn sparse_cross_entropy_gradient(&self, a: &[E], label: usize, output_gradient: E, a_gradient: &mut [E]) {
+        if !a[label].is_zero() {
+            a_gradient[label] += -output_gradient / a[label];
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use crate::tensor::{Float, Layout};
+
+use super::{gemm::{self, gemm, MatRef, MicroKernel}, Backend, ConcatDims, Conv2dDims, DataKernels, IndexSelectDims, Kernels, MatMulDims, Threaded};
+
+///
+/// Runs matrix multiplications and convolutions through a packed, cache-blocked GEMM with SIMD micro-kernels, split
+/// across a pool of threads
+///
+/// The micro-kernels are picked for the CPU when the backend is created, falling back to portable scalar ones. Their
+/// fused multiply-adds and blocked sums round differently to `Naive`, but every element is calculated the same way
+/// whatever the number of threads, so the results are still identical to the single-threaded ones. The other kernels
+/// are `Threaded`'s.
+///
+/// As the micro-kernels depend on the CPU, results can differ from one machine to another, so the backend is opt-in
+/// through `Device::with_packed`.
+///
+pub struct Packed {
+    pub (super) f32: PackedKernels<f32, 6, 16>,
+    pub (super) f64: PackedKernels<f64, 6, 8>,
+}
+
+///
+/// The kernels of `Packed` for one element type, with an `MR x NR` micro-kernel
+///
+pub (super) struct PackedKernels<E, const MR: usize, const NR: usize> {
+    pub threaded: Arc<Threaded>,
+    pub micro_kernel: MicroKernel<E, MR, NR>,
+}
+
+impl Packed {
+    pub fn new(threads: usize) -> Self {
+        let threaded = Arc::new(Threaded::new(threads));
+
+        Self {
+            f32: PackedKernels { threaded: threaded.clone(), micro_kernel: gemm::f32_micro_kernel() },
+            f64: PackedKernels { threaded, micro_kernel: gemm::f64_micro_kernel() },
+        }
+    }
+}
+
+impl Backend for Packed {
+    fn name(&self) -> &'static str {
+        "packed"
+    }
+
+    fn threads(&self) -> usize {
+        self.f32.threaded.threads()
+    }
+
+    fn f32(&self) -> &dyn Kernels<f32> {
+        &self.f32
+    }
+
+    fn f64(&self) -> &dyn Kernels<f64> {
+        &self.f64
+    }
+
+    fn i64(&self) -> &dyn DataKernels<i64> {
+        &*self.f32.threaded
+    }
+
+    fn i32(&self) -> &dyn DataKernels<i32> {
+        &*self.f32.threaded
+    }
+
+    fn u8(&self) -> &dyn DataKernels<u8> {
+        &*self.f32.threaded
+    }
+}
+
+impl<E: Float, const MR: usize, const NR: usize> PackedKernels<E, MR, NR> {
+    ///
+    /// c += a * b, split between the threads by rows of c, or by columns when it only has the one row
+    ///
+    fn product(&self, a: MatRef<E>, b: MatRef<E>, c: &mut [E]) {
+        let (m, k, n) = (a.rows, a.cols, b.cols);
+        let work = m * k * n;
+
+        if m == 1 {
+            return self.threaded.rows(c, 1, work, |start, chunk| {
+                gemm(self.micro_kernel, a, b.col_range(start, chunk.len()), chunk);
+            });
+        }
+
+        self.threaded.rows(c, n, work, |start, chunk| {
+            gemm(self.micro_kernel, a.row_range(start, chunk.len() / n), b, chunk);
+        });
+    }
+}
+
+///
+/// Lays out the patches of the input that a convolution multiplies by its kernel as the columns of a matrix, one row for
+/// each element of the kernel and one column for each element of the output that isn't left at zero
+///
+fn im2col<E: Float>(input: &[E], dims: Conv2dDims) -> Vec<E> {
+    let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+    let (o1, o2) = (i1 - k1 + 1, i2 - k2 + 1);
+
+    let mut columns = Vec::with_capacity(k1 * k2 * o1 * o2);
+
+    for i in 0..k1 {
+        for j in 0..k2 {
+            for k in 0..o1 {
+                columns.extend_from_slice(&input[(k + i) * i2 + j..][..o2]);
+            }
+        }
+    }
+
+    columns
+}
+
+///
+/// Packs the part of a convolution's output that the kernel covers, leaving out the rows and columns kept at zero
+///
+fn valid_output<E: Float>(output: &[E], dims: Conv2dDims) -> Vec<E> {
+    let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+    let (o1, o2) = (i1 - k1 + 1, i2 - k2 + 1);
+
+    output.chunks(i2).take(o1).flat_map(|row| &row[..o2]).cloned().collect()
+}
+
+impl<E: Float, const MR: usize, const NR: usize> DataKernels<E> for PackedKernels<E, MR, NR> {
+    fn add(&self, lhs: &[E], rhs: &[E], output: &mut [E]) {
+        self.threaded.add(lhs, rhs, output);
+    }
+
+    fn broadcast(&self, value: E, output: &mut [E]) {
+        self.threaded.broadcast(value, output);
+    }
+
+    fn cast_to_f64(&self, input: &[E], output: &mut [f64]) {
+        self.threaded.cast_to_f64(input, output);
+    }
+
+    fn cast_from_f64(&self, input: &[f64], output: &mut [E]) {
+        self.threaded.cast_from_f64(input, output);
+    }
+
+    fn concat(&self, lhs: &[E], rhs: &[E], output: &mut [E], dims: ConcatDims) {
+        self.threaded.concat(lhs, rhs, output, dims);
+    }
+
+    fn concat_gradients(&self, output_gradient: &[E], lhs_gradient: &mut [E], rhs_gradient: &mut [E], dims: ConcatDims) {
+        self.threaded.concat_gradients(output_gradient, lhs_gradient, rhs_gradient, dims);
+    }
+
+    fn stack(&self, inputs: &[&[E]], output: &mut [E]) {
+        self.threaded.stack(inputs, output);
+    }
+
+    fn embedding(&self, weights: &[E], rows: &[usize], output: &mut [E], dim: usize) {
+        self.threaded.embedding(weights, rows, output, dim);
+    }
+
+    fn embedding_gradient(&self, output_gradient: &[E], rows: &[usize], weights_gradient: &mut [E], dim: usize) {
+        self.threaded.embedding_gradient(output_gradient, rows, weights_gradient, dim);
+    }
+
+    fn index_select(&self, input: &[E], indices: &[usize], output: &mut [E], dims: IndexSelectDims) {
+        self.threaded.index_select(input, indices, output, dims);
+    }
+
+    fn index_select_gradient(&self, output_gradient: &[E], indices: &[usize], input_gradient: &mut [E], dims: IndexSelectDims) {
+        self.threaded.index_select_gradient(output_gradient, indices, input_gradient, dims);
+    }
+
+    fn gather(&self, input: &[E], layout: &Layout, output: &mut [E]) {
+        self.threaded.gather(input, layout, output);
+    }
+
+    fn gather_gradient(&self, output_gradient: &[E], layout: &Layout, input_gradient: &mut [E]) {
+        self.threaded.gather_gradient(output_gradient, layout, input_gradient);
+    }
+}
+
+impl<E: Float, const MR: usize, const NR: usize> Kernels<E> for PackedKernels<E, MR, NR> {
+    fn map(&self, input: &[E], output: &mut [E], f: &(dyn Fn(E) -> E + Sync)) {
+        self.threaded.map(input, output, f);
+    }
+
+    fn zip(&self, lhs: &[E], rhs: &[E], output: &mut [E], f: &(dyn Fn(E, E) -> E + Sync)) {
+        self.threaded.zip(lhs, rhs, output, f);
+    }
+
+    fn sum(&self, input: &[E]) -> E {
+        Kernels::<E>::sum(&*self.threaded, input)
+    }
+
+    fn matmul(&self, lhs: &[E], rhs: &[E], output: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+
+        self.product(MatRef::new(lhs, m, k), MatRef::new(rhs, k, n), output);
+    }
+
+    fn matmul_lhs_gradient(&self, output_gradient: &[E], rhs: &[E], lhs_gradient: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+
+        self.product(MatRef::new(output_gradient, m, n), MatRef::new(rhs, k, n).t(), lhs_gradient);
+    }
+
+    fn matmul_rhs_gradient(&self, lhs: &[E], output_gradient: &[E], rhs_gradient: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+
+        self.product(MatRef::new(lhs, m, k).t(), MatRef::new(output_gradient, m, n), rhs_gradient);
+    }
+
+    fn conv2d(&self, input: &[E], kernel: &[E], output: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+        let (o1, o2) = (i1 - k1 + 1, i2 - k2 + 1);
+
+        // Z = K * X_col, as a 1 x (o1 * o2) row that is then spread over the rows of the output
+        let columns = im2col(input, dims);
+        let mut result = vec![E::zero(); o1 * o2];
+
+        self.product(MatRef::new(kernel, 1, k1 * k2), MatRef::new(&columns, k1 * k2, o1 * o2), &mut result);
+
+        for (row, result) in output.chunks_mut(i2).zip(result.chunks(o2)) {
+            for (o, r) in row.iter_mut().zip(result) {
+                *o += *r;
+            }
+        }
+    }
+
+    fn conv2d_input_gradient(&self, kernel: &[E], output_gradient: &[E], input_gradient: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+        let (o1, o2) = (i1 - k1 + 1, i2 - k2 + 1);
+
+        let output_gradient = valid_output(output_gradient, dims);
+        let output_gradient = MatRef::new(&output_gradient, 1, o1 * o2);
+
+        // grad X_col = K^T * grad Z, whose columns are added back to the patches of the input they came from
+        let mut column_gradients = vec![E::zero(); k1 * k2 * o1 * o2];
+        self.product(MatRef::new(kernel, 1, k1 * k2).t(), output_gradient, &mut column_gradients);
+
+        for (ij, gradient) in column_gradients.chunks(o1 * o2).enumerate() {
+            let (i, j) = (ij / k2, ij % k2);
+
+            for (k, gradient) in gradient.chunks(o2).enumerate() {
+                for (x, g) in input_gradient[(k + i) * i2 + j..][..o2].iter_mut().zip(gradient) {
+                    *x += *g;
+                }
+            }
+        }
+    }
+
+    fn conv2d_kernel_gradient(&self, input: &[E], output_gradient: &[E], kernel_gradient: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+        let (o1, o2) = (i1 - k1 + 1, i2 - k2 + 1);
+
+        let output_gradient = valid_output(output_gradient, dims);
+        let columns = im2col(input, dims);
+
+        // grad K += grad Z * X_col^T
+        self.product(MatRef::new(&output_gradient, 1, o1 * o2), MatRef::new(&columns, k1 * k2, o1 * o2).t(), kernel_gradient);
+    }
+
+    fn softmax(&self, input: &[E], output: &mut [E]) {
+        self.threaded.softmax(input, output);
+    }
+
+    fn softmax_gradient(&self, output: &[E], output_gradient: &[E], input_gradient: &mut [E]) {
+        self.threaded.softmax_gradient(output, output_gradient, input_gradient);
+    }
+
+    fn mse(&self, a: &[E], targets: &[E]) -> E {
+        Kernels::<E>::mse(&*self.threaded, a, targets)
+    }
+
+    fn mse_gradients(&self, a: &[E], targets: &[E], output_gradient: E, a_gradient: &mut [E], target_gradient: &mut [E]) {
+        self.threaded.mse_gradients(a, targets, output_gradient, a_gradient, target_gradient);
+    }
+
+    fn cross_entropy(&self, a: &[E], targets: &[E]) -> E {
+        Kernels::<E>::cross_entropy(&*self.threaded, a, targets)
+    }
+
+    fn cross_entropy_gradients(&self, a: &[E], targets: &[E], output_gradient: E, a_gradient: &mut [E], target_gradient: &mut [E]) {
+        self.threaded.cross_entropy_gradients(a, targets, output_gradient, a_gradient, target_gradient);
+    }
+
+    fn sparse_cross_entropy(&self, a: &[E], label: usize) -> E {
+        Kernels::<E>::sparse_cross_entropy(&*self.threaded, a, label)
+    }
+
+    fn sparse_cross_entropy_gradient(&self, a: &[E], label: usize, output_gradient: E, a_gradient: &mut [E]) {
+        self.threaded.sparse_cross_entropy_gradient(a, label, output_gradient, a_gradient);
+    }
+}
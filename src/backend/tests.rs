@@ -0,0 +1,404 @@
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{device::Device, tensor::{DType, Float, Layout, Rank1, Rank2}, tensor_ops::{cross_entropy_loss, matmul, mse, relu, sigmoid, softmax, tanh}};
+
+use super::*;
+
+///
+/// Sizes that don't line up with any block or vector width, as well as degenerate and larger ones
+///
+const MATMUL_DIMS: [(usize, usize, usize); 6] = [(1, 1, 1), (1, 7, 5), (3, 1, 4), (5, 8, 3), (17, 33, 9), (64, 70, 65)];
+
+const CONV2D_DIMS: [((usize, usize), (usize, usize)); 4] = [((3, 3), (1, 1)), ((5, 7), (2, 3)), ((9, 9), (9, 9)), ((28, 28), (5, 5))];
+
+///
+/// The number of blocks and the lengths of the blocks of each input of a concatenation
+///
+const CONCAT_DIMS: [(usize, usize, usize); 4] = [(1, 1, 1), (1, 3, 5), (7, 2, 1), (300, 9, 4)];
+
+///
+/// The number of rows looked up, the rows in the table and the length of each row
+///
+const EMBEDDING_DIMS: [(usize, usize, usize); 3] = [(1, 1, 1), (9, 4, 3), (500, 20, 16)];
+
+///
+/// The number of blocks, the length of the selected axis, the length of each entry and the number of indices
+///
+const INDEX_SELECT_DIMS: [(usize, usize, usize, usize); 4] = [(1, 1, 1, 1), (1, 6, 4, 9), (5, 7, 1, 3), (40, 30, 8, 50)];
+
+///
+/// Views of a contiguous `4 x 30 x 20` buffer, as the layout ops make them
+///
+fn gather_layouts() -> Vec<Layout> {
+    let contiguous = Layout::contiguous(vec![4, 30, 20]);
+
+    vec![
+        contiguous.clone(),
+        contiguous.permute(&[2, 0, 1]),
+        contiguous.narrow(1, 10, 15),
+        contiguous.permute(&[1, 2, 0]).narrow(0, 3, 1),
+    ]
+}
+
+fn values<E: Float>(rng: &mut StdRng, len: usize) -> Vec<E> {
+    (0..len).map(|_| E::from_f64(rng.gen_range(-1.0..1.0))).collect()
+}
+
+///
+/// Asserts that two results agree to within `tolerance`, relative to the size of the expected values, or are the same
+/// infinity
+///
+fn assert_close<E: DType>(backend: &dyn Backend, tolerance: f64, kernel: &str, expected: &[E], actual: &[E]) {
+    assert_eq!(expected.len(), actual.len());
+
+    for (i, (e, a)) in expected.iter().zip(actual).enumerate() {
+        let (e, a) = (e.as_f64(), a.as_f64());
+
+        assert!(
+            e == a || (e - a).abs() <= tolerance * (1.0 + e.abs()),
+            "{} {kernel} for {} differs from the reference at {i}: expected {e}, found {a}",
+            backend.name(),
+            E::NAME
+        );
+    }
+}
+
+///
+/// Runs a kernel that writes to `output`, which starts as `initial`. Kernels that accumulate are given a non-zero
+/// output, to check they add to it
+///
+fn run<E: Float>(kernels: &dyn Kernels<E>, initial: &[E], f: impl Fn(&dyn Kernels<E>, &mut [E])) -> Vec<E> {
+    let mut output = initial.to_vec();
+    f(kernels, &mut output);
+
+    output
+}
+
+fn run_data<E: DType>(kernels: &dyn DataKernels<E>, initial: &[E], f: impl Fn(&dyn DataKernels<E>, &mut [E])) -> Vec<E> {
+    let mut output = initial.to_vec();
+    f(kernels, &mut output);
+
+    output
+}
+
+///
+/// Runs the data kernels of a backend for one type on random inputs, and checks the results against the `Naive`
+/// reference. Values are drawn from `-range..range`
+///
+fn check_data_kernels<E: DType>(backend: &dyn Backend, tolerance: f64, range: f64) {
+    let kernels = E::data_kernels(backend);
+    let reference = &Naive as &dyn DataKernels<E>;
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let values = |rng: &mut StdRng, len: usize| (0..len).map(|_| E::from_f64(rng.gen_range(-range..range))).collect::<Vec<E>>();
+
+    for len in [1, 7, 64, 1000] {
+        let (lhs, rhs) = (values(&mut rng, len), values(&mut rng, len));
+        let zeros = vec![E::zero(); len];
+
+        let add = |k: &dyn DataKernels<E>, o: &mut [E]| k.add(&lhs, &rhs, o);
+        assert_close(backend, tolerance, "add", &run_data(reference, &zeros, add), &run_data(kernels, &zeros, add));
+
+        let broadcast = |k: &dyn DataKernels<E>, o: &mut [E]| k.broadcast(lhs[0], o);
+        assert_close(backend, tolerance, "broadcast", &run_data(reference, &zeros, broadcast), &run_data(kernels, &zeros, broadcast));
+
+        let cast = |k: &dyn DataKernels<E>, o: &mut [E]| {
+            let mut values = vec![0.0; len];
+            k.cast_to_f64(&lhs, &mut values);
+
+            // Halved, so that the conversion back has to round
+            values.iter_mut().for_each(|v| *v /= 2.0);
+            k.cast_from_f64(&values, o);
+        };
+        assert_close(backend, tolerance, "cast", &run_data(reference, &zeros, cast), &run_data(kernels, &zeros, cast));
+
+        let inputs = [&lhs[..], &rhs[..], &lhs[..]];
+        let stack = |k: &dyn DataKernels<E>, o: &mut [E]| k.stack(&inputs, o);
+        let zeros = vec![E::zero(); 3 * len];
+        assert_close(backend, tolerance, "stack", &run_data(reference, &zeros, stack), &run_data(kernels, &zeros, stack));
+    }
+
+    for (blocks, l, r) in CONCAT_DIMS {
+        let dims = ConcatDims { lhs: l, rhs: r };
+        let (lhs, rhs) = (values(&mut rng, blocks * l), values(&mut rng, blocks * r));
+
+        let concat = |k: &dyn DataKernels<E>, o: &mut [E]| k.concat(&lhs, &rhs, o, dims);
+        let zeros = vec![E::zero(); blocks * (l + r)];
+        assert_close(backend, tolerance, "concat", &run_data(reference, &zeros, concat), &run_data(kernels, &zeros, concat));
+
+        let output_gradient = values(&mut rng, blocks * (l + r));
+
+        let gradients = |k: &dyn DataKernels<E>| {
+            let (mut lhs_gradient, mut rhs_gradient) = (lhs.clone(), rhs.clone());
+            k.concat_gradients(&output_gradient, &mut lhs_gradient, &mut rhs_gradient, dims);
+
+            (lhs_gradient, rhs_gradient)
+        };
+
+        let (expected, actual) = (gradients(reference), gradients(kernels));
+        assert_close(backend, tolerance, "concat_gradients", &expected.0, &actual.0);
+        assert_close(backend, tolerance, "concat_gradients", &expected.1, &actual.1);
+    }
+
+    for (n, v, d) in EMBEDDING_DIMS {
+        let weights = values(&mut rng, v * d);
+        let output_gradient = values(&mut rng, n * d);
+        let rows = (0..n).map(|_| rng.gen_range(0..v)).collect::<Vec<_>>();
+
+        let embedding = |k: &dyn DataKernels<E>, o: &mut [E]| k.embedding(&weights, &rows, o, d);
+        let zeros = vec![E::zero(); n * d];
+        assert_close(backend, tolerance, "embedding", &run_data(reference, &zeros, embedding), &run_data(kernels, &zeros, embedding));
+
+        let gradient = |k: &dyn DataKernels<E>, o: &mut [E]| k.embedding_gradient(&output_gradient, &rows, o, d);
+        assert_close(backend, tolerance, "embedding_gradient", &run_data(reference, &weights, gradient), &run_data(kernels, &weights, gradient));
+    }
+
+    for (blocks, len, inner, count) in INDEX_SELECT_DIMS {
+        let dims = IndexSelectDims { len, inner };
+        let input = values(&mut rng, blocks * len * inner);
+        let output_gradient = values(&mut rng, blocks * count * inner);
+        let indices = (0..count).map(|_| rng.gen_range(0..len)).collect::<Vec<_>>();
+
+        let index_select = |k: &dyn DataKernels<E>, o: &mut [E]| k.index_select(&input, &indices, o, dims);
+        let zeros = vec![E::zero(); blocks * count * inner];
+        assert_close(backend, tolerance, "index_select", &run_data(reference, &zeros, index_select), &run_data(kernels, &zeros, index_select));
+
+        let gradient = |k: &dyn DataKernels<E>, o: &mut [E]| k.index_select_gradient(&output_gradient, &indices, o, dims);
+        assert_close(backend, tolerance, "index_select_gradient", &run_data(reference, &input, gradient), &run_data(kernels, &input, gradient));
+    }
+
+    for layout in gather_layouts() {
+        let input = values(&mut rng, 4 * 30 * 20);
+        let output_gradient = values(&mut rng, layout.size());
+
+        let gather = |k: &dyn DataKernels<E>, o: &mut [E]| k.gather(&input, &layout, o);
+        let zeros = vec![E::zero(); layout.size()];
+        assert_close(backend, tolerance, "gather", &run_data(reference, &zeros, gather), &run_data(kernels, &zeros, gather));
+
+        let gradient = |k: &dyn DataKernels<E>, o: &mut [E]| k.gather_gradient(&output_gradient, &layout, o);
+        assert_close(backend, tolerance, "gather_gradient", &run_data(reference, &input, gradient), &run_data(kernels, &input, gradient));
+    }
+}
+
+///
+/// Runs every kernel of a backend on random inputs, and checks the results against the `Naive` reference
+///
+fn check_kernels<E: Float>(backend: &dyn Backend, tolerance: f64) {
+    check_data_kernels::<E>(backend, tolerance, 1.0);
+
+    let kernels = E::kernels(backend);
+    let reference = &Naive as &dyn Kernels<E>;
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for len in [1, 7, 64, 1000] {
+        let (lhs, rhs) = (values::<E>(&mut rng, len), values::<E>(&mut rng, len));
+        let zeros = vec![E::zero(); len];
+
+        let map = |k: &dyn Kernels<E>, o: &mut [E]| k.map(&lhs, o, &|x| x.tanh() * E::from_f64(2.0));
+        assert_close(backend, tolerance, "map", &run(reference, &zeros, map), &run(kernels, &zeros, map));
+
+        let zip = |k: &dyn Kernels<E>, o: &mut [E]| k.zip(&lhs, &rhs, o, &|a, b| a * b - b);
+        assert_close(backend, tolerance, "zip", &run(reference, &zeros, zip), &run(kernels, &zeros, zip));
+
+        assert_close(backend, tolerance, "sum", &[reference.sum(&lhs)], &[kernels.sum(&lhs)]);
+
+        let softmax = |k: &dyn Kernels<E>, o: &mut [E]| k.softmax(&lhs, o);
+        let output = run(reference, &zeros, softmax);
+        assert_close(backend, tolerance, "softmax", &output, &run(kernels, &zeros, softmax));
+
+        let softmax_gradient = |k: &dyn Kernels<E>, o: &mut [E]| k.softmax_gradient(&output, &rhs, o);
+        assert_close(backend, tolerance, "softmax_gradient", &run(reference, &lhs, softmax_gradient), &run(kernels, &lhs, softmax_gradient));
+
+        // Probabilities for the cross entropy, one of which is zero to check that it is skipped
+        let mut probabilities = output.clone();
+        probabilities[len / 2] = E::zero();
+
+        let losses = |k: &dyn Kernels<E>| [k.mse(&lhs, &rhs), k.cross_entropy(&output, &rhs), k.sparse_cross_entropy(&output, len / 3)];
+        assert_close(backend, tolerance, "losses", &losses(reference), &losses(kernels));
+
+        let output_gradient = E::from_f64(0.7);
+
+        let gradients = |k: &dyn Kernels<E>| {
+            let (mut mse_a, mut mse_targets) = (lhs.clone(), rhs.clone());
+            k.mse_gradients(&lhs, &rhs, output_gradient, &mut mse_a, &mut mse_targets);
+
+            let (mut cross_entropy_a, mut cross_entropy_targets) = (lhs.clone(), rhs.clone());
+            k.cross_entropy_gradients(&probabilities, &rhs, output_gradient, &mut cross_entropy_a, &mut cross_entropy_targets);
+
+            let mut sparse_a = lhs.clone();
+            k.sparse_cross_entropy_gradient(&probabilities, len / 3, output_gradient, &mut sparse_a);
+
+            [mse_a, mse_targets, cross_entropy_a, cross_entropy_targets, sparse_a]
+        };
+
+        for (expected, actual) in gradients(reference).iter().zip(&gradients(kernels)) {
+            assert_close(backend, tolerance, "loss gradients", expected, actual);
+        }
+    }
+
+    for (m, k, n) in MATMUL_DIMS {
+        let dims = MatMulDims { m, k, n };
+
+        let lhs = values::<E>(&mut rng, m * k);
+        let rhs = values::<E>(&mut rng, k * n);
+        let output_gradient = values::<E>(&mut rng, m * n);
+
+        let initial = values::<E>(&mut rng, m * n);
+        let forward = |kn: &dyn Kernels<E>, o: &mut [E]| kn.matmul(&lhs, &rhs, o, dims);
+        assert_close(backend, tolerance, "matmul", &run(reference, &initial, forward), &run(kernels, &initial, forward));
+
+        let initial = values::<E>(&mut rng, m * k);
+        let lhs_gradient = |kn: &dyn Kernels<E>, o: &mut [E]| kn.matmul_lhs_gradient(&output_gradient, &rhs, o, dims);
+        assert_close(backend, tolerance, "matmul_lhs_gradient", &run(reference, &initial, lhs_gradient), &run(kernels, &initial, lhs_gradient));
+
+        let initial = values::<E>(&mut rng, k * n);
+        let rhs_gradient = |kn: &dyn Kernels<E>, o: &mut [E]| kn.matmul_rhs_gradient(&lhs, &output_gradient, o, dims);
+        assert_close(backend, tolerance, "matmul_rhs_gradient", &run(reference, &initial, rhs_gradient), &run(kernels, &initial, rhs_gradient));
+    }
+
+    for (input, kernel) in CONV2D_DIMS {
+        let dims = Conv2dDims { input, kernel };
+
+        let x = values::<E>(&mut rng, input.0 * input.1);
+        let w = values::<E>(&mut rng, kernel.0 * kernel.1);
+        let output_gradient = values::<E>(&mut rng, input.0 * input.1);
+
+        let initial = values::<E>(&mut rng, input.0 * input.1);
+        let forward = |kn: &dyn Kernels<E>, o: &mut [E]| kn.conv2d(&x, &w, o, dims);
+        assert_close(backend, tolerance, "conv2d", &run(reference, &initial, forward), &run(kernels, &initial, forward));
+
+        let input_gradient = |kn: &dyn Kernels<E>, o: &mut [E]| kn.conv2d_input_gradient(&w, &output_gradient, o, dims);
+        assert_close(backend, tolerance, "conv2d_input_gradient", &run(reference, &x, input_gradient), &run(kernels, &x, input_gradient));
+
+        let kernel_gradient = |kn: &dyn Kernels<E>, o: &mut [E]| kn.conv2d_kernel_gradient(&x, &output_gradient, o, dims);
+        assert_close(backend, tolerance, "conv2d_kernel_gradient", &run(reference, &w, kernel_gradient), &run(kernels, &w, kernel_gradient));
+    }
+}
+
+///
+/// Checks a backend's kernels for every float type, then trains a small network on it and on the reference, which
+/// must agree step for step
+///
+pub (crate) fn cross_check(backend: impl Backend + 'static) {
+    // Allows for the rounding differences of adding in another order
+    check_kernels::<f32>(&backend, f32::EPSILON.sqrt() as f64);
+    check_kernels::<f64>(&backend, f64::EPSILON.sqrt());
+    check_data_kernels::<i64>(&backend, 0.0, 1000.0);
+    check_data_kernels::<u8>(&backend, 0.0, 8.0);
+
+    let name = backend.name();
+    let devices = [Device::new(), Device::with_backend(backend)];
+
+    let losses = devices.map(|device| {
+        device.seed(0);
+
+        let w1 = device.uniform::<Rank2<6, 9>, f64>(-1.0, 1.0);
+        let w2 = device.uniform::<Rank2<9, 3>, f64>(-1.0, 1.0);
+        w1.set_requires_grad(true);
+        w2.set_requires_grad(true);
+
+        (0..5).map(|_| {
+            let x = device.uniform::<Rank1<6>, f64>(-1.0, 1.0);
+            let target = device.uniform::<Rank1<3>, f64>(0.0, 1.0);
+
+            let logits = matmul(tanh(relu(matmul(x, w1.clone()))), w2.clone());
+            let loss = mse(sigmoid(logits.clone()), target.clone()) + cross_entropy_loss(softmax(logits), target);
+            loss.back();
+
+            for w in [w1.as_ref(), w2.as_ref()] {
+                let gradient = w.gradient();
+
+                for (p, g) in w.buffer_mut().iter_mut().zip(gradient.iter()) {
+                    *p -= 0.5 * g;
+                }
+
+                w.gradient_mut().fill(0.0);
+            }
+
+            device.get_tensor_buffer(&loss)[0]
+        }).collect::<Vec<_>>()
+    });
+
+    for (step, (expected, actual)) in losses[0].iter().zip(&losses[1]).enumerate() {
+        assert!((expected - actual).abs() < 1e-9, "Training on {name} diverged from the reference at step {step}");
+    }
+}
+
+#[test]
+fn naive_backend() {
+    cross_check(Naive);
+}
+
+#[test]
+fn threaded_backend() {
+    cross_check(Threaded::new(3));
+}
+
+#[test]
+fn threaded_matches_serial_exactly() {
+    for threads in [1, 2, 3, 8] {
+        let mut backend = Threaded::new(threads);
+
+        // Split even the smallest kernels, so that every way of splitting them is checked
+        backend.min_parallel_work = 0;
+
+        check_kernels::<f32>(&backend, 0.0);
+        check_kernels::<f64>(&backend, 0.0);
+        check_data_kernels::<i32>(&backend, 0.0, 1000.0);
+    }
+}
+
+#[test]
+fn packed_backend() {
+    cross_check(Packed::new(3));
+}
+
+#[test]
+fn packed_scalar_fallback() {
+    let mut backend = Packed::new(1);
+
+    // As on a CPU without the SIMD kernels
+    backend.f32.micro_kernel = gemm::scalar;
+    backend.f64.micro_kernel = gemm::scalar;
+
+    cross_check(backend);
+}
+
+#[test]
+fn packed_independent_of_threads() {
+    let results = [1, 2, 3, 8].map(|threads| {
+        let mut threaded = Threaded::new(threads);
+        threaded.min_parallel_work = 0;
+
+        let kernels = packed::PackedKernels { threaded: Arc::new(threaded), micro_kernel: gemm::f32_micro_kernel() };
+        let mut rng = StdRng::seed_from_u64(0);
+
+        MATMUL_DIMS.map(|(m, k, n)| {
+            let (lhs, rhs) = (values::<f32>(&mut rng, m * k), values::<f32>(&mut rng, k * n));
+            let mut output = vec![0.0; m * n];
+
+            kernels.matmul(&lhs, &rhs, &mut output, MatMulDims { m, k, n });
+
+            output
+        })
+    });
+
+    for result in &results[1..] {
+        assert_eq!(result, &results[0]);
+    }
+}
+
+#[test]
+fn naive_is_default() {
+    assert_eq!(Device::new().backend().name(), "naive");
+}
+
+#[test]
+fn constructors_pick_backends() {
+    assert_eq!(Device::with_threads(2).backend().name(), "threaded");
+
+    let packed = Device::with_packed(2);
+    assert_eq!((packed.backend().name(), packed.num_threads()), ("packed", 2));
+}
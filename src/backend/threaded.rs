@@ -0,0 +1,447 @@
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+
+use crate::tensor::{DType, Float, Layout};
+
+use super::{Backend, ConcatDims, Conv2dDims, DataKernels, IndexSelectDims, Kernels, MatMulDims, Naive};
+
+///
+/// Kernels with less work than this, counted in multiply-adds, run on the calling thread, as handing them to the pool
+/// would cost more than it saves
+///
+const MIN_PARALLEL_WORK: usize = 1 << 15;
+
+///
+/// Splits kernels across a pool of threads
+///
+/// Every element of a kernel's output is calculated by one thread, adding up its terms in the same order as `Naive`,
+/// so the results are identical to the single-threaded ones whatever the number of threads. Sums, and the losses and
+/// softmax that are built on them, are the exception that is left on one thread, as splitting them would change the
+/// order they are added in.
+///
+pub struct Threaded {
+    pool: ThreadPool,
+    threads: usize,
+    pub (super) min_parallel_work: usize,
+}
+
+impl Threaded {
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0, "A thread pool needs at least one thread");
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("backprop-worker-{i}"))
+            .build()
+            .expect("Failed to start the thread pool");
+
+        Self { pool, threads, min_parallel_work: MIN_PARALLEL_WORK }
+    }
+
+    ///
+    /// Calls `f` with batches of whole rows of `output`, each `row_len` elements long, and the index of the first row
+    /// in the batch. The batches run on the pool if there is enough `work` to go round
+    ///
+    pub (super) fn rows<E: Send>(&self, output: &mut [E], row_len: usize, work: usize, f: impl Fn(usize, &mut [E]) + Sync) {
+        let rows = output.len() / row_len.max(1);
+
+        if self.threads == 1 || work < self.min_parallel_work || rows < 2 {
+            return f(0, output);
+        }
+
+        let rows_per_batch = rows.div_ceil(self.threads);
+
+        self.pool.install(|| {
+            output.par_chunks_mut(rows_per_batch * row_len)
+                  .enumerate()
+                  .for_each(|(batch, chunk)| f(batch * rows_per_batch, chunk));
+        });
+    }
+}
+
+impl Backend for Threaded {
+    fn name(&self) -> &'static str {
+        "threaded"
+    }
+
+    fn threads(&self) -> usize {
+        self.threads
+    }
+
+    fn f32(&self) -> &dyn Kernels<f32> {
+        self
+    }
+
+    fn f64(&self) -> &dyn Kernels<f64> {
+        self
+    }
+
+    fn i64(&self) -> &dyn DataKernels<i64> {
+        self
+    }
+
+    fn i32(&self) -> &dyn DataKernels<i32> {
+        self
+    }
+
+    fn u8(&self) -> &dyn DataKernels<u8> {
+        self
+    }
+}
+
+impl<E: DType> DataKernels<E> for Threaded {
+    fn add(&self, lhs: &[E], rhs: &[E], output: &mut [E]) {
+        self.rows(output, 1, lhs.len(), |start, chunk| {
+            let range = start..start + chunk.len();
+
+            Naive.add(&lhs[range.clone()], &rhs[range], chunk);
+        });
+    }
+
+    fn broadcast(&self, value: E, output: &mut [E]) {
+        let work = output.len();
+
+        self.rows(output, 1, work, |_, chunk| Naive.broadcast(value, chunk));
+    }
+
+    fn cast_to_f64(&self, input: &[E], output: &mut [f64]) {
+        self.rows(output, 1, input.len(), |start, chunk| {
+            Naive.cast_to_f64(&input[start..start + chunk.len()], chunk);
+        });
+    }
+
+    fn cast_from_f64(&self, input: &[f64], output: &mut [E]) {
+        self.rows(output, 1, input.len(), |start, chunk| {
+            Naive.cast_from_f64(&input[start..start + chunk.len()], chunk);
+        });
+    }
+
+    fn concat(&self, lhs: &[E], rhs: &[E], output: &mut [E], dims: ConcatDims) {
+        let ConcatDims { lhs: l, rhs: r } = dims;
+        let work = output.len();
+
+        self.rows(output, l + r, work, |start, chunk| {
+            let blocks = start..start + chunk.len() / (l + r);
+
+            Naive.concat(&lhs[blocks.start * l..blocks.end * l], &rhs[blocks.start * r..blocks.end * r], chunk, dims);
+        });
+    }
+
+    fn concat_gradients(&self, output_gradient: &[E], lhs_gradient: &mut [E], rhs_gradient: &mut [E], dims: ConcatDims) {
+        let ConcatDims { lhs: l, rhs: r } = dims;
+        let work = output_gradient.len();
+
+        // Each input's gradient only needs its own part of every block
+        self.rows(lhs_gradient, l, work, |start, chunk| {
+            for (gradient, block) in chunk.chunks_mut(l).zip(output_gradient.chunks(l + r).skip(start)) {
+                for (g, o) in gradient.iter_mut().zip(&block[..l]) {
+                    *g += *o;
+                }
+            }
+        });
+
+        self.rows(rhs_gradient, r, work, |start, chunk| {
+            for (gradient, block) in chunk.chunks_mut(r).zip(output_gradient.chunks(l + r).skip(start)) {
+                for (g, o) in gradient.iter_mut().zip(&block[l..]) {
+                    *g += *o;
+                }
+            }
+        });
+    }
+
+    fn stack(&self, inputs: &[&[E]], output: &mut [E]) {
+        let len = output.len() / inputs.len().max(1);
+        let work = output.len();
+
+        self.rows(output, len, work, |start, chunk| {
+            Naive.stack(&inputs[start..start + chunk.len() / len], chunk);
+        });
+    }
+
+    fn embedding(&self, weights: &[E], rows: &[usize], output: &mut [E], dim: usize) {
+        let work = output.len();
+
+        self.rows(output, dim, work, |start, chunk| {
+            Naive.embedding(weights, &rows[start..start + chunk.len() / dim], chunk, dim);
+        });
+    }
+
+    fn embedding_gradient(&self, output_gradient: &[E], rows: &[usize], weights_gradient: &mut [E], dim: usize) {
+        // Rows can be looked up more than once, so each batch of rows of the gradient gathers from every row of the
+        // output's gradient that it was looked up by, in order
+        self.rows(weights_gradient, dim, output_gradient.len(), |start, chunk| {
+            let batch = start..start + chunk.len() / dim;
+
+            for (gradient, row) in output_gradient.chunks(dim).zip(rows).filter(|(_, row)| batch.contains(row)) {
+                for (w, g) in chunk[(row - start) * dim..(row - start + 1) * dim].iter_mut().zip(gradient) {
+                    *w += *g;
+                }
+            }
+        });
+    }
+
+    fn index_select(&self, input: &[E], indices: &[usize], output: &mut [E], dims: IndexSelectDims) {
+        let IndexSelectDims { len, inner } = dims;
+        let work = output.len();
+
+        self.rows(output, inner, work, |start, chunk| {
+            for (entry, output) in (start..).zip(chunk.chunks_mut(inner)) {
+                let base = (entry / indices.len() * len + indices[entry % indices.len()]) * inner;
+
+                output.copy_from_slice(&input[base..base + inner]);
+            }
+        });
+    }
+
+    fn index_select_gradient(&self, output_gradient: &[E], indices: &[usize], input_gradient: &mut [E], dims: IndexSelectDims) {
+        let IndexSelectDims { len, inner } = dims;
+
+        // As with embeddings, each batch of entries of the gradient gathers from every entry of the output's gradient
+        // that selected it, in order
+        self.rows(input_gradient, inner, output_gradient.len(), |start, chunk| {
+            let batch = start..start + chunk.len() / inner;
+
+            for (entry, gradient) in output_gradient.chunks(inner).enumerate() {
+                let selected = entry / indices.len() * len + indices[entry % indices.len()];
+
+                if batch.contains(&selected) {
+                    for (g, o) in chunk[(selected - start) * inner..(selected - start + 1) * inner].iter_mut().zip(gradient) {
+                        *g += *o;
+                    }
+                }
+            }
+        });
+    }
+
+    fn gather(&self, input: &[E], layout: &Layout, output: &mut [E]) {
+        let Some(&outer) = layout.dims().first() else {
+            return Naive.gather(input, layout, output);
+        };
+
+        let row_len = layout.size() / outer.max(1);
+        let work = output.len();
+
+        // Each batch gathers a range of the outermost axis
+        self.rows(output, row_len, work, |start, chunk| {
+            Naive.gather(input, &layout.narrow(0, start, chunk.len() / row_len.max(1)), chunk);
+        });
+    }
+
+    fn gather_gradient(&self, output_gradient: &[E], layout: &Layout, input_gradient: &mut [E]) {
+        // Each batch of the input's gradient picks out the elements that were gathered from it, in order
+        self.rows(input_gradient, 1, output_gradient.len(), |start, chunk| {
+            let batch = start..start + chunk.len();
+
+            for (o, i) in output_gradient.iter().zip(layout.offsets()).filter(|(_, i)| batch.contains(i)) {
+                chunk[i - start] += *o;
+            }
+        });
+    }
+}
+
+impl<E: Float> Kernels<E> for Threaded {
+    fn map(&self, input: &[E], output: &mut [E], f: &(dyn Fn(E) -> E + Sync)) {
+        self.rows(output, 1, input.len(), |start, chunk| {
+            Naive.map(&input[start..start + chunk.len()], chunk, f);
+        });
+    }
+
+    fn zip(&self, lhs: &[E], rhs: &[E], output: &mut [E], f: &(dyn Fn(E, E) -> E + Sync)) {
+        self.rows(output, 1, lhs.len(), |start, chunk| {
+            let range = start..start + chunk.len();
+
+            Naive.zip(&lhs[range.clone()], &rhs[range], chunk, f);
+        });
+    }
+
+    fn sum(&self, input: &[E]) -> E {
+        Naive.sum(input)
+    }
+
+    fn matmul(&self, lhs: &[E], rhs: &[E], output: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+        let work = m * k * n;
+
+        if m == 1 {
+            // A vector has a single row to split, so its columns are split instead
+            return self.rows(output, 1, work, |start, chunk| {
+                for j in 0..k {
+                    let a = lhs[j];
+
+                    for (i, o) in chunk.iter_mut().enumerate() {
+                        *o += a * rhs[j * n + start + i];
+                    }
+                }
+            });
+        }
+
+        self.rows(output, n, work, |start, chunk| {
+            let rows = chunk.len() / n;
+
+            Naive.matmul(&lhs[start * k..(start + rows) * k], rhs, chunk, MatMulDims { m: rows, k, n });
+        });
+    }
+
+    fn matmul_lhs_gradient(&self, output_gradient: &[E], rhs: &[E], lhs_gradient: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+        let work = m * k * n;
+
+        if m == 1 {
+            // Each column of the gradient only needs the matching row of rhs
+            return self.rows(lhs_gradient, 1, work, |start, chunk| {
+                let rhs = &rhs[start * n..(start + chunk.len()) * n];
+
+                Naive.matmul_lhs_gradient(output_gradient, rhs, chunk, MatMulDims { m: 1, k: chunk.len(), n });
+            });
+        }
+
+        self.rows(lhs_gradient, k, work, |start, chunk| {
+            let rows = chunk.len() / k;
+
+            Naive.matmul_lhs_gradient(&output_gradient[start * n..(start + rows) * n], rhs, chunk, MatMulDims { m: rows, k, n });
+        });
+    }
+
+    fn matmul_rhs_gradient(&self, lhs: &[E], output_gradient: &[E], rhs_gradient: &mut [E], dims: MatMulDims) {
+        let MatMulDims { m, k, n } = dims;
+
+        self.rows(rhs_gradient, n, m * k * n, |start, chunk| {
+            for row in 0..m {
+                for (j, gradient) in chunk.chunks_mut(n).enumerate() {
+                    let a = lhs[row * k + start + j];
+
+                    for (i, g) in gradient.iter_mut().enumerate() {
+                        *g += a * output_gradient[row * n + i];
+                    }
+                }
+            }
+        });
+    }
+
+    fn conv2d(&self, input: &[E], kernel: &[E], output: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+
+        self.rows(output, i2, i1 * i2 * k1 * k2, |start, chunk| {
+            // The last k1 - 1 rows of the output are left at zero
+            if start + k1 > i1 {
+                return;
+            }
+
+            // The rows of input that the batch's rows of output are calculated from
+            let end = (start + chunk.len() / i2 + k1 - 1).min(i1);
+
+            Naive.conv2d(&input[start * i2..end * i2], kernel, chunk, Conv2dDims { input: (end - start, i2), kernel: (k1, k2) });
+        });
+    }
+
+    fn conv2d_input_gradient(&self, kernel: &[E], output_gradient: &[E], input_gradient: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+        let work = i1 * i2 * k1 * k2;
+
+        // grad X(k + i, l + j) += K(i, j) * grad Z(k, l), gathered for each row of X from the rows of Z that use it
+        self.rows(input_gradient, i2, work, |start, chunk| {
+            for (row, gradient) in chunk.chunks_mut(i2).enumerate().map(|(r, g)| (start + r, g)) {
+                for i in 0..k1 {
+                    let Some(k) = row.checked_sub(i).filter(|k| *k <= i1 - k1) else {
+                        continue;
+                    };
+
+                    for j in 0..k2 {
+                        for l in 0..=(i2 - k2) {
+                            gradient[l + j] += kernel[i * k2 + j] * output_gradient[k * i2 + l];
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn conv2d_kernel_gradient(&self, input: &[E], output_gradient: &[E], kernel_gradient: &mut [E], dims: Conv2dDims) {
+        let Conv2dDims { input: (i1, i2), kernel: (k1, k2) } = dims;
+        let work = i1 * i2 * k1 * k2;
+
+        // grad K(i, j) += X(k + i, l + j) * grad Z(k, l)
+        self.rows(kernel_gradient, k2, work, |start, chunk| {
+            for (i, gradient) in chunk.chunks_mut(k2).enumerate().map(|(i, g)| (start + i, g)) {
+                for (j, g) in gradient.iter_mut().enumerate() {
+                    for k in 0..=(i1 - k1) {
+                        for l in 0..=(i2 - k2) {
+                            *g += input[(k + i) * i2 + (l + j)] * output_gradient[k * i2 + l];
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn softmax(&self, input: &[E], output: &mut [E]) {
+        let max = input.iter().cloned().reduce(E::max).unwrap();
+
+        self.map(input, output, &|i| (i - max).exp());
+        let sum = Naive.sum(output);
+
+        let work = output.len();
+        self.rows(output, 1, work, |_, chunk| {
+            for o in chunk {
+                *o /= sum;
+            }
+        });
+    }
+
+    fn softmax_gradient(&self, output: &[E], output_gradient: &[E], input_gradient: &mut [E]) {
+        let dot = output_gradient.iter().zip(output).map(|(g, x)| *g * *x).sum::<E>();
+
+        self.rows(input_gradient, 1, output.len(), |start, chunk| {
+            for (i, (x, g)) in chunk.iter_mut().zip(output[start..].iter().zip(&output_gradient[start..])) {
+                *i += *x * (*g - dot);
+            }
+        });
+    }
+
+    fn mse(&self, a: &[E], targets: &[E]) -> E {
+        Naive.mse(a, targets)
+    }
+
+    fn mse_gradients(&self, a: &[E], targets: &[E], output_gradient: E, a_gradient: &mut [E], target_gradient: &mut [E]) {
+        let factor = output_gradient * (E::from_f64(2.0) / E::from_f64(a.len() as f64));
+
+        self.rows(a_gradient, 1, a.len(), |start, chunk| {
+            for (g, (a, t)) in chunk.iter_mut().zip(a[start..].iter().zip(&targets[start..])) {
+                *g += factor * (*a - *t);
+            }
+        });
+
+        self.rows(target_gradient, 1, a.len(), |start, chunk| {
+            for (g, (a, t)) in chunk.iter_mut().zip(a[start..].iter().zip(&targets[start..])) {
+                *g -= factor * (*a - *t);
+            }
+        });
+    }
+
+    fn cross_entropy(&self, a: &[E], targets: &[E]) -> E {
+        Naive.cross_entropy(a, targets)
+    }
+
+    fn cross_entropy_gradients(&self, a: &[E], targets: &[E], output_gradient: E, a_gradient: &mut [E], target_gradient: &mut [E]) {
+        self.rows(a_gradient, 1, a.len(), |start, chunk| {
+            for (g, (a, t)) in chunk.iter_mut().zip(a[start..].iter().zip(&targets[start..])) {
+                if !a.is_zero() {
+                    *g += -output_gradient * *t / *a;
+                }
+            }
+        });
+
+        self.rows(target_gradient, 1, a.len(), |start, chunk| {
+            for (g, a) in chunk.iter_mut().zip(&a[start..]) {
+                *g += -output_gradient * a.ln();
+            }
+        });
+    }
+
+    fn sparse_cross_entropy(&self, a: &[E], label: usize) -> E {
+        Naive.sparse_cross_entropy(a, label)
+    }
+
+    fn sparse_cross_entropy_gradient(&self, a: &[E], label: usize, output_gradient: E, a_gradient: &mut [E]) {
+        Naive.sparse_cross_entropy_gradient(a, label, output_gradient, a_gradient);
+    }
+}
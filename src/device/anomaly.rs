@@ -0,0 +1,229 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::tensor::{topological_order, AnyShape, AnyTensor, DType, Tensor, TensorId};
+
+use super::Device;
+
+///
+/// How many ops of the forward stack to show when reporting an anomaly
+///
+const STACK_DEPTH: usize = 16;
+
+#[derive(Default)]
+pub (crate) struct AnomalyState {
+    pub (super) enabled: bool,
+
+    // The layer each live tensor was created in
+    tensor_layers: HashMap<TensorId, String>,
+}
+
+///
+/// The layers a device is running forward on one thread, so that threads sharing a device don't label each other's
+/// tensors
+///
+#[derive(Default)]
+struct Layers {
+    // Labels of the layers currently running forward, innermost last
+    stack: Vec<String>,
+    count: usize,
+
+    // Model forward passes in progress, which keep the count going between their layers
+    forwards: usize,
+}
+
+thread_local! {
+    // Keyed by the address of the device's state, like `NO_GRAD_DEPTH`, and removed once nothing is running
+    static LAYERS: RefCell<HashMap<usize, Layers>> = RefCell::new(HashMap::new());
+}
+
+impl Device {
+    ///
+    /// Turns anomaly detection on or off
+    ///
+    /// While on, the output of every op and every gradient calculated during backpropagation is checked for NaN
+    /// and infinite values. The first op to produce one panics with its name, the layer it ran in, and the stack
+    /// of ops that led to it. Every op is kept so the stack can be shown, even under `no_grad`, which makes this slower
+    /// and more memory hungry. It is meant for debugging.
+    ///
+    pub fn set_detect_anomaly(&self, enabled: bool) {
+        self.inner().anomaly.enabled = enabled;
+    }
+
+    pub fn detects_anomalies(&self) -> bool {
+        self.inner().anomaly.enabled
+    }
+
+    ///
+    /// Starts numbering this thread's layers from one again, for a model's forward pass that lasts as long as the
+    /// returned scope
+    ///
+    pub (crate) fn begin_forward(&self) -> ForwardScope<'_> {
+        LAYERS.with_borrow_mut(|layers| {
+            let layers = layers.entry(self.key()).or_default();
+
+            layers.count = 0;
+            layers.forwards += 1;
+        });
+
+        ForwardScope(self)
+    }
+
+    ///
+    /// Runs `f` as the forward pass of a layer, so that anomalies in it can be traced back to the layer
+    ///
+    pub (crate) fn in_layer<T>(&self, name: Option<&'static str>, f: impl FnOnce() -> T) -> T {
+        let Some(name) = name.filter(|_| self.detects_anomalies()) else {
+            return f();
+        };
+
+        LAYERS.with_borrow_mut(|layers| {
+            let layers = layers.entry(self.key()).or_default();
+
+            layers.count += 1;
+            layers.stack.push(format!("{name} #{}", layers.count));
+        });
+
+        let _scope = LayerScope(self);
+
+        f()
+    }
+
+    ///
+    /// The layer this thread is running forward on the device, if any
+    ///
+    fn current_layer(&self) -> Option<String> {
+        LAYERS.with_borrow(|layers| layers.get(&self.key())?.stack.last().cloned())
+    }
+
+    ///
+    /// Updates this thread's layers, forgetting them once no layer or model is running
+    ///
+    fn update_layers(&self, f: impl FnOnce(&mut Layers)) {
+        LAYERS.with_borrow_mut(|layers| {
+            let key = self.key();
+            let entry = layers.get_mut(&key).expect("layer scopes should be balanced");
+
+            f(entry);
+
+            if entry.stack.is_empty() && entry.forwards == 0 {
+                layers.remove(&key);
+            }
+        });
+    }
+
+    ///
+    /// Records where a new tensor was created, and checks its values if it is the output of an op
+    ///
+    pub (crate) fn check_forward<S: AnyShape, E: DType>(&self, tensor: &Tensor<S, E>) {
+        if !self.detects_anomalies() {
+            return;
+        }
+
+        if let Some(layer) = self.current_layer() {
+            self.inner().anomaly.tensor_layers.insert(tensor.id, layer);
+        }
+
+        let Some(op) = tensor.op_name() else {
+            return;
+        };
+
+        if tensor.values_are_finite() {
+            return;
+        }
+
+        // Op outputs have already been checked, so a bad input must be a constant or parameter
+        if let Some(input) = tensor.inputs().into_iter().find(|input| !input.values_are_finite()) {
+            panic!(
+                "Anomaly detected: {op}{} was given a NaN or infinite value by a {} of shape {}\n{}",
+                self.layer_suffix(tensor.id),
+                if input.requires_grad() { "parameter" } else { "constant" },
+                input.shape(),
+                self.forward_stack(tensor)
+            );
+        }
+
+        panic!(
+            "Anomaly detected: {op}{} produced a NaN or infinite value in its output of shape {}\n{}",
+            self.layer_suffix(tensor.id),
+            tensor.shape(),
+            self.forward_stack(tensor)
+        );
+    }
+
+    ///
+    /// Checks the gradients that a tensor's op has just passed back to its inputs
+    ///
+    pub (crate) fn check_backward(&self, tensor: &dyn AnyTensor) {
+        if !self.inner().anomaly.enabled {
+            return;
+        }
+
+        for (i, input) in tensor.inputs().into_iter().enumerate() {
+            if input.requires_grad() && !input.gradient_is_finite() {
+                panic!(
+                    "Anomaly detected: backward of {}{} produced a NaN or infinite gradient for input {i} of shape {}\n{}",
+                    tensor.op_name().unwrap_or("constant"),
+                    self.layer_suffix(tensor.id()),
+                    input.shape(),
+                    self.forward_stack(tensor)
+                );
+            }
+        }
+    }
+
+    pub (crate) fn forget_anomaly_source(&self, id: TensorId) {
+        self.inner().anomaly.tensor_layers.remove(&id);
+    }
+
+    fn layer_suffix(&self, id: TensorId) -> String {
+        let layer = self.inner().anomaly.tensor_layers.get(&id).cloned();
+
+        match layer {
+            Some(layer) => format!(" in {layer}"),
+            None => String::new(),
+        }
+    }
+
+    ///
+    /// Lists the ops that led to a tensor, in the order they ran
+    ///
+    fn forward_stack(&self, tensor: &dyn AnyTensor) -> String {
+        let ops = topological_order(tensor)
+            .into_iter()
+            .filter(|t| t.op_name().is_some())
+            .take(STACK_DEPTH)
+            .collect::<Vec<_>>();
+
+        let mut stack = String::from("Forward stack (most recent last):");
+
+        for t in ops.iter().rev() {
+            stack += &format!("\n    {} {}{}", t.op_name().unwrap(), t.shape(), self.layer_suffix(t.id()));
+        }
+
+        stack
+    }
+}
+
+///
+/// Leaves a layer when dropped, so the layer stack stays correct even if the forward pass panics
+///
+struct LayerScope<'a>(&'a Device);
+
+impl Drop for LayerScope<'_> {
+    fn drop(&mut self) {
+        self.0.update_layers(|layers| {
+            layers.stack.pop();
+        });
+    }
+}
+
+///
+/// Ends a model's forward pass when dropped
+///
+pub (crate) struct ForwardScope<'a>(&'a Device);
+
+impl Drop for ForwardScope<'_> {
+    fn drop(&mut self) {
+        self.0.update_layers(|layers| layers.forwards -= 1);
+    }
+}
@@ -0,0 +1,204 @@
+use rand_distr::{Distribution, Normal, Uniform};
+
+use crate::tensor::{source::TensorSource, DType, Dyn, DynShape, Float, Rank1, Rank2, Shape, ShapeError, Tensor};
+
+use super::Device;
+
+// Tensor constructors
+impl Device {
+    ///
+    /// Create a tensor with the given data
+    ///
+    pub fn constant<S: Shape, E: DType>(&self, data: &[E]) -> Tensor<S, E> {
+        self.allocate_tensor(self.collect_buffer(data.iter().cloned()), TensorSource::Constant)
+    }
+
+    ///
+    /// Create a tensor filled with zeros
+    ///
+    pub fn zeros<S: Shape, E: DType>(&self) -> Tensor<S, E> {
+        self.full(E::zero())
+    }
+
+    ///
+    /// Create a tensor filled with ones
+    ///
+    pub fn ones<S: Shape, E: DType>(&self) -> Tensor<S, E> {
+        self.full(E::one())
+    }
+
+    ///
+    /// Create a tensor with every element set to `value`
+    ///
+    pub fn full<S: Shape, E: DType>(&self, value: E) -> Tensor<S, E> {
+        self.allocate_tensor(self.filled_buffer(S::SIZE, value), TensorSource::Constant)
+    }
+
+    ///
+    /// Create a tensor counting up from `start` in increments of `step`, in row-major order
+    ///
+    /// Every value must fit in `E`, so a `u8` tensor counting in steps of one can have at most 256 elements. The
+    /// values are calculated as `f64`, which is exact for integers up to 2^53.
+    ///
+    pub fn arange<S: Shape, E: DType>(&self, start: E, step: E) -> Tensor<S, E> {
+        let (start, step) = (start.as_f64(), step.as_f64());
+        let last = start + step * S::SIZE.saturating_sub(1) as f64;
+
+        assert!(
+            last >= E::min_value().as_f64() && last <= E::max_value().as_f64(),
+            "Counting {} values from {start} in steps of {step} goes past the range of {}", S::SIZE, E::NAME
+        );
+
+        let data = self.collect_buffer((0..S::SIZE).map(|i| E::from_f64(start + step * i as f64)));
+
+        self.allocate_tensor(data, TensorSource::Constant)
+    }
+
+    ///
+    /// Create a tensor of evenly spaced values from `start` to `end`, both inclusive, in row-major order
+    ///
+    pub fn linspace<S: Shape, E: Float>(&self, start: E, end: E) -> Tensor<S, E> {
+        let start = start.as_f64();
+        let end = end.as_f64();
+
+        let step = if S::SIZE > 1 { (end - start) / (S::SIZE - 1) as f64 } else { 0.0 };
+
+        let data = self.collect_buffer((0..S::SIZE).map(|i| E::from_f64(start + step * i as f64)));
+
+        self.allocate_tensor(data, TensorSource::Constant)
+    }
+
+    ///
+    /// Create an identity matrix
+    ///
+    pub fn eye<const N: usize, E: DType>(&self) -> Tensor<Rank2<N, N>, E> {
+        self.from_fn(|index| if index[0] == index[1] { E::one() } else { E::zero() })
+    }
+
+    ///
+    /// Create a tensor by calling `f` with the multi-dimensional index of every element
+    ///
+    pub fn from_fn<S: Shape, E: DType>(&self, mut f: impl FnMut(&[usize]) -> E) -> Tensor<S, E> {
+        let dims = S::dims();
+
+        let mut index = vec![0; dims.len()];
+        let mut data = self.pool.take(S::SIZE);
+
+        for _ in 0..S::SIZE {
+            data.push(f(&index));
+
+            for axis in (0..dims.len()).rev() {
+                index[axis] += 1;
+
+                if index[axis] < dims[axis] {
+                    break;
+                }
+
+                index[axis] = 0;
+            }
+        }
+
+        self.allocate_tensor(data, TensorSource::Constant)
+    }
+
+    ///
+    /// Create a vector that is one at `index` and zero everywhere else
+    ///
+    pub fn one_hot<const N: usize, E: DType>(&self, index: usize) -> Tensor<Rank1<N>, E> {
+        assert!(index < N, "Index {index} is out of bounds for a one-hot vector of length {N}");
+
+        self.from_fn(|i| if i[0] == index { E::one() } else { E::zero() })
+    }
+
+    ///
+    /// Create a tensor with random data drawn uniformly from `[low, high)`
+    ///
+    pub fn uniform<S: Shape, E: Float>(&self, low: E, high: E) -> Tensor<S, E> {
+        assert!(low < high, "Uniform distribution needs low < high");
+
+        self.random(Uniform::new(low.as_f64(), high.as_f64()))
+    }
+
+    ///
+    /// Create a tensor with random normal data
+    ///
+    pub fn normal<S: Shape, E: Float>(&self, mean: E, std: E) -> Tensor<S, E> {
+        self.random(Self::normal_distribution(mean, std))
+    }
+
+    ///
+    /// Create a tensor with random normal data, redrawing any values more than two standard deviations from the mean
+    ///
+    pub fn truncated_normal<S: Shape, E: Float>(&self, mean: E, std: E) -> Tensor<S, E> {
+        let distr = Self::normal_distribution(mean, std);
+        let bound = 2.0 * std.as_f64();
+
+        let mut data = self.pool.take(S::SIZE);
+
+        self.with_rng(|rng| {
+            data.extend((0..S::SIZE).map(|_| {
+                loop {
+                    let value = distr.sample(rng);
+
+                    if (value - distr.mean()).abs() <= bound {
+                        return E::from_f64(value);
+                    }
+                }
+            }));
+        });
+
+        self.allocate_tensor(data, TensorSource::Constant)
+    }
+
+    ///
+    /// Create a tensor with random normal data, with a standard deviation of 0.01
+    ///
+    pub fn sample<S: Shape, E: Float>(&self) -> Tensor<S, E> {
+        self.normal(E::zero(), E::from_f64(0.01))
+    }
+
+    ///
+    /// Create a dynamically-shaped tensor with the given data
+    ///
+    /// Fails if the data does not have as many elements as the shape
+    ///
+    pub fn constant_dyn<E: DType>(&self, shape: &DynShape, data: &[E]) -> Result<Tensor<Dyn, E>, ShapeError> {
+        if shape.size() != data.len() {
+            return Err(ShapeError::Size { expected: shape.size(), found: data.len() });
+        }
+
+        Ok(self.allocate_with_dims(self.collect_buffer(data.iter().cloned()), shape.dims().to_vec(), TensorSource::Constant))
+    }
+
+    ///
+    /// Create a dynamically-shaped tensor with random normal data
+    ///
+    pub fn sample_dyn<E: Float>(&self, shape: &DynShape) -> Tensor<Dyn, E> {
+        let data = self.random_values(shape.size(), Normal::new(0.0, 0.01).unwrap());
+
+        self.allocate_with_dims(data, shape.dims().to_vec(), TensorSource::Constant)
+    }
+}
+
+impl Device {
+    fn random<S: Shape, E: Float>(&self, distr: impl Distribution<f64>) -> Tensor<S, E> {
+        self.allocate_tensor(self.random_values(S::SIZE, distr), TensorSource::Constant)
+    }
+
+    ///
+    /// Draws `size` values from a distribution
+    ///
+    pub (crate) fn random_values<E: Float>(&self, size: usize, distr: impl Distribution<f64>) -> Vec<E> {
+        let mut data = self.pool.take(size);
+
+        self.with_rng(|rng| data.extend((0..size).map(|_| E::from_f64(distr.sample(rng)))));
+
+        data
+    }
+
+    fn normal_distribution<E: Float>(mean: E, std: E) -> Normal<f64> {
+        assert!(std >= E::zero(), "Standard deviation must not be negative");
+
+        Normal::new(mean.as_f64(), std.as_f64()).unwrap()
+    }
+}
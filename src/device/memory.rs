@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::Device;
+
+///
+/// A snapshot of the memory held by a device's tensors
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Tensors that are still referenced, by a `Tensor`, a `TensorRef`, or the graph of another tensor
+    pub live_tensors: usize,
+
+    /// Bytes of tensor data and gradients currently allocated. Views that share data are only counted once
+    pub bytes_allocated: usize,
+
+    /// The most bytes that were allocated at once since the device was created, or since the peak was last reset
+    pub peak_bytes: usize,
+
+    /// Bytes of freed buffers kept for reuse, which are not counted as allocated
+    pub pooled_bytes: usize,
+}
+
+///
+/// Counts the bytes of tensor storage, which is added to and freed from many threads without locking the device
+///
+#[derive(Default)]
+pub (crate) struct MemoryCounter {
+    allocated: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl MemoryCounter {
+    pub fn allocate(&self, bytes: usize) {
+        let allocated = self.allocated.fetch_add(bytes, Ordering::Relaxed) + bytes;
+
+        self.peak.fetch_max(allocated, Ordering::Relaxed);
+    }
+
+    pub fn free(&self, bytes: usize) {
+        self.allocated.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+}
+
+impl Device {
+    ///
+    /// Reports how many tensors are alive and how much memory they hold
+    ///
+    /// A tensor is freed as soon as the last reference to it is dropped, so comparing the stats from one training
+    /// step to the next shows whether anything is being kept alive by mistake, such as a loss stored along with its
+    /// graph.
+    ///
+    pub fn memory_stats(&self) -> MemoryStats {
+        let live_tensors = self.inner().tensor_buffers.len();
+
+        MemoryStats {
+            live_tensors,
+            bytes_allocated: self.pool.memory.allocated.load(Ordering::Relaxed),
+            peak_bytes: self.pool.memory.peak.load(Ordering::Relaxed),
+            pooled_bytes: self.pool.pooled.load(Ordering::Relaxed),
+        }
+    }
+
+    ///
+    /// Starts measuring the peak from the memory allocated right now, to find the peak of a single step
+    ///
+    pub fn reset_peak_memory(&self) {
+        let allocated = self.pool.memory.allocated.load(Ordering::Relaxed);
+
+        self.pool.memory.peak.store(allocated, Ordering::Relaxed);
+    }
+}
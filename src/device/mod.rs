@@ -1,49 +1,163 @@
-use std::{cell::UnsafeCell, collections::HashMap, sync::Arc};
+use std::{cell::RefCell, collections::HashMap, marker::PhantomData, sync::{Arc, Mutex, MutexGuard, PoisonError, Weak}};
 
-use rand_distr::Distribution;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 
-use crate::{nn::{layers::{Layer, LayerBuilder}, optimizer::OptimizerConfig, Model}, tensor::{inner::TensorInner, source::TensorSource, Shape, Tensor, TensorId}};
+mod constructors;
+mod anomaly;
+mod memory;
+mod pool;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::memory::MemoryStats;
+pub (crate) use self::pool::BufferPool;
+
+use self::anomaly::AnomalyState;
+use crate::{backend::{Backend, DataKernels, Kernels, Naive, Packed, Threaded}, nn::{layers::{Layer, LayerBuilder}, optimizer::OptimizerConfig, Model}, tensor::{inner::{AnyTensorInner, Storage, TensorInner}, source::TensorSource, AnyShape, Buffer, DType, Float, Layout, Shape, Tensor, TensorId}};
 
 pub struct DeviceInner {
-    tensor_buffers: HashMap<TensorId, Arc<TensorInner>>,
+    // Weak, so that tensors are freed as soon as nothing else refers to them
+    tensor_buffers: HashMap<TensorId, Weak<dyn AnyTensorInner>>,
     tensor_allocated: usize,
+    rng: StdRng,
+    anomaly: AnomalyState,
 }
 
+thread_local! {
+    // How many `no_grad` scopes each device is in on this thread, keyed by the address of its state
+    static NO_GRAD_DEPTH: RefCell<HashMap<usize, usize>> = RefCell::new(HashMap::new());
+}
+
+///
+/// Creates tensors and keeps track of the state they share, such as the random number generator
+///
+/// A device can be cloned and shared between threads. Its state sits behind a lock that is only held briefly, never
+/// while running an op, so threads building separate graphs on the same device do not block each other.
+///
 #[derive(Clone)]
 pub struct Device {
-    inner: Arc<UnsafeCell<DeviceInner>>,
+    inner: Arc<Mutex<DeviceInner>>,
+    pool: Arc<BufferPool>,
+    backend: Arc<dyn Backend>,
 }
 
 impl Device {
-    pub fn get_tensor_buffer<S: Shape>(&self, tensor: &Tensor<S>) -> &[f32] {
-        self.inner().tensor_buffers.get(&tensor.id).unwrap().buffer()
+    ///
+    /// Gets the elements of a tensor in row-major order
+    /// 
+    /// Contiguous tensors share their storage with the buffer, while non-contiguous views are gathered into a copy.
+    /// Either way the buffer is a snapshot that later writes to the tensor don't change, rather than a borrowed slice.
+    /// 
+    pub fn get_tensor_buffer<S: AnyShape, E: DType>(&self, tensor: &Tensor<S, E>) -> Buffer<E> {
+        tensor.inner.buffer()
     }
 
-    pub fn get_gradient_buffer<S: Shape>(&self, tensor: &Tensor<S>) -> &[f32] {
-        self.inner().tensor_buffers.get(&tensor.id).unwrap().gradient()
+    pub fn get_gradient_buffer<S: AnyShape, E: DType>(&self, tensor: &Tensor<S, E>) -> Buffer<E> {
+        tensor.inner.gradient()
     }
 
-    pub (crate) fn add_to_gradient<S: Shape>(&self, tensor: &Tensor<S>, buffer: &[f32]) {
-        let gradient = self.inner().tensor_buffers.get(&tensor.id).unwrap().gradient_mut();
+    pub (crate) fn add_to_gradient<S: AnyShape, E: DType>(&self, tensor: &Tensor<S, E>, buffer: &[E]) {
+        if !tensor.requires_grad() {
+            return;
+        }
+
+        let mut gradient = tensor.inner.gradient_mut();
 
         assert_eq!(gradient.len(), buffer.len());
 
         for (g, b) in gradient.iter_mut().zip(buffer.iter()) {
-            *g += b;
+            *g += *b;
         }
     }
 }
 
 impl Device {
+    ///
+    /// Creates a device that runs ops on the `Naive` reference backend
+    ///
     pub fn new() -> Self {
+        Self::with_backend(Naive)
+    }
+
+    ///
+    /// Creates a device that runs ops on the given backend
+    ///
+    pub fn with_backend(backend: impl Backend + 'static) -> Self {
         Self {
-            inner: Arc::new(UnsafeCell::new(DeviceInner {
+            inner: Arc::new(Mutex::new(DeviceInner {
                 tensor_buffers: HashMap::new(),
-                tensor_allocated: 0
-            }))
+                tensor_allocated: 0,
+                rng: StdRng::from_entropy(),
+                anomaly: AnomalyState::default()
+            })),
+            pool: Arc::new(BufferPool::default()),
+            backend: Arc::new(backend)
         }
     }
 
+    ///
+    /// Creates a device that splits large matrix multiplications, convolutions and elementwise ops across a pool of
+    /// `threads` threads
+    ///
+    /// The results are identical to those of a single thread, so a run can be reproduced with any number of threads.
+    ///
+    pub fn with_threads(threads: usize) -> Self {
+        Self::with_backend(Threaded::new(threads))
+    }
+
+    ///
+    /// Creates a device that runs matrix multiplications and convolutions through a packed GEMM with SIMD
+    /// micro-kernels, split across a pool of `threads` threads along with everything `with_threads` splits
+    ///
+    /// The results are the same whatever the number of threads, but the micro-kernels are picked for the CPU and round
+    /// differently to the other backends, so they can differ from one machine to another.
+    ///
+    pub fn with_packed(threads: usize) -> Self {
+        Self::with_backend(Packed::new(threads))
+    }
+
+    ///
+    /// The backend that ops on this device's tensors run on
+    ///
+    pub fn backend(&self) -> &dyn Backend {
+        self.backend.as_ref()
+    }
+
+    ///
+    /// How many threads the device's backend splits its kernels across, which is one for the `Naive` backend
+    ///
+    pub fn num_threads(&self) -> usize {
+        self.backend.threads()
+    }
+
+    ///
+    /// Reseeds the device's random number generator
+    /// 
+    /// All randomness in the crate (initialization, dropout, shuffling and augmentation) is drawn from this
+    /// generator, so seeding the device before building a model makes a run reproducible.
+    /// 
+    pub fn seed(&self, seed: u64) {
+        self.inner().rng = StdRng::seed_from_u64(seed);
+    }
+
+    ///
+    /// Runs `f` with the device's random number generator, for code outside the crate that needs reproducible
+    /// randomness, such as data augmentation
+    /// 
+    /// The device is locked while `f` runs, so `f` must not use the device itself.
+    /// 
+    pub fn with_rng<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        f(&mut self.inner().rng)
+    }
+
+    ///
+    /// Shuffles a slice in place using the device's random number generator
+    /// 
+    pub fn shuffle<T>(&self, items: &mut [T]) {
+        self.with_rng(|rng| items.shuffle(rng));
+    }
+
     ///
     /// Builds a model from a LayerBuilder, initializing all the weights.
     /// 
@@ -63,72 +177,167 @@ impl Device {
     }
 
     /// 
-    /// Resets all gradient vectors
+    /// Resets the gradients of every live tensor
     /// 
     pub fn zero_grad(&self) {
-        let inner = self.inner();
+        // Gradient hooks may use the device, so the gradients are zeroed once it is unlocked
+        let buffers = self.inner().tensor_buffers.values().filter_map(Weak::upgrade).collect::<Vec<_>>();
 
-        for buffer in inner.tensor_buffers.values() {
-            buffer.gradient_mut().fill(0.0);
+        for buffer in buffers {
+            buffer.zero_gradient();
         }
     }
 }
 
-// Tensor constructors
 impl Device {
+    pub fn allocate_tensor<S: Shape, E: DType>(&self, data: Vec<E>, source: TensorSource<S, E>) -> Tensor<S, E> {
+        self.allocate_with_dims(data, S::dims(), source)
+    }
+
     ///
-    /// Create a tensor with the given data
+    /// Creates a contiguous tensor with the given dimensions, which must agree with `S` if it is a static shape
     /// 
-    pub fn constant<S: Shape>(&self, data: &[f32]) -> Tensor<S> {
-        self.allocate_tensor(data.to_vec(), TensorSource::Constant)
+    pub (crate) fn allocate_with_dims<S: AnyShape, E: DType>(&self, data: Vec<E>, dims: Vec<usize>, source: TensorSource<S, E>) -> Tensor<S, E> {
+        let layout = Layout::contiguous(dims);
+
+        assert_eq!(layout.size(), data.len());
+
+        self.allocate_view(Arc::new(Storage::pooled(data, &self.pool)), layout, source)
     }
 
     ///
-    /// Create a tensor with random normal data
+    /// Creates a tensor that shares existing storage, reading its elements through `layout`
     /// 
-    pub fn sample<S: Shape>(&self) -> Tensor<S> {
-        let mut rng = rand::thread_rng();
-        let distr = rand_distr::Normal::new(0.0f32, 0.01).unwrap();
+    pub (crate) fn allocate_view<S: AnyShape, E: DType>(&self, storage: Arc<Storage<E>>, layout: Layout, source: TensorSource<S, E>) -> Tensor<S, E> {
+        if let Some(dims) = S::static_dims() {
+            assert_eq!(dims, layout.dims());
+        }
 
-        let data = (0..S::SIZE).map(|_| distr.sample(&mut rng)).collect();
+        let grad_enabled = self.is_grad_enabled();
+        let detects_anomalies = self.detects_anomalies();
 
-        self.allocate_tensor(data, TensorSource::Constant)
-    }
-}
+        // Only keep the op if a gradient could flow through it. Dropping it here releases its inputs straight away
+        let requires_grad = match &source {
+            TensorSource::Constant => false,
+            TensorSource::Operation(op) => grad_enabled && op.inputs().iter().any(|input| input.requires_grad()),
+        };
 
-impl Device {
-    pub fn allocate_tensor<S: Shape>(&self, data: Vec<f32>, source: TensorSource<S>) -> Tensor<S> {
-        assert_eq!(S::SIZE, data.len());
+        // Anomaly detection keeps every op, so that it can show how a bad value came about
+        let source = if requires_grad || detects_anomalies { source } else { TensorSource::Constant };
 
-        let inner = self.inner();
+        let (tensor_id, tensor_inner) = {
+            let mut inner = self.inner();
 
-        let tensor_id = TensorId(inner.tensor_allocated);
-        inner.tensor_allocated += 1;
+            let tensor_id = TensorId(inner.tensor_allocated);
+            inner.tensor_allocated += 1;
 
-        let tensor_inner = Arc::new(TensorInner::new(data));
+            let tensor_inner = Arc::new(TensorInner::view(tensor_id, self.clone(), storage, layout, requires_grad));
+            inner.tensor_buffers.insert(tensor_id, Arc::downgrade(&tensor_inner) as Weak<dyn AnyTensorInner>);
 
-        inner.tensor_buffers.insert(tensor_id, tensor_inner.clone());
+            (tensor_id, tensor_inner)
+        };
 
-        Tensor {
+        let tensor = Tensor {
             id: tensor_id,
             inner: tensor_inner,
             device: self.clone(),
             source,
             _shape: std::marker::PhantomData,
-            
-        }
+        };
+
+        self.check_forward(&tensor);
+
+        tensor
+    }
+
+    ///
+    /// Runs `f` without building a computation graph
+    /// 
+    /// Tensors created inside are constants that hold no references to their inputs, so nothing can be
+    /// backpropagated through them. This saves memory and time when only the forward pass is needed, such as
+    /// during evaluation. While anomaly detection is on the ops are still kept, so bad values can be traced, and
+    /// nothing is saved.
+    /// 
+    /// This only applies to the calling thread, so other threads using the device keep building graphs.
+    /// 
+    pub fn no_grad<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _guard = self.no_grad_guard();
+
+        f()
+    }
+
+    ///
+    /// Stops building a computation graph until the returned guard is dropped
+    /// 
+    pub fn no_grad_guard(&self) -> NoGradGuard {
+        NO_GRAD_DEPTH.with_borrow_mut(|depths| *depths.entry(self.key()).or_default() += 1);
+
+        NoGradGuard { device: self.clone(), _thread: PhantomData }
+    }
+
+    ///
+    /// Whether new tensors created on this thread record the op that created them
+    /// 
+    pub fn is_grad_enabled(&self) -> bool {
+        NO_GRAD_DEPTH.with_borrow(|depths| !depths.contains_key(&self.key()))
+    }
+
+    ///
+    /// Identifies the device, and every clone of it, for as long as any of them is alive
+    /// 
+    fn key(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
     }
 
     pub (crate) fn drop_tensor(&self, id: TensorId) {
         self.inner().tensor_buffers.remove(&id);
+        self.forget_anomaly_source(id);
     }
 }
 
-impl Device {
-    fn inner(&self) -> &mut DeviceInner {
-        unsafe { &mut *self.inner.get() }
+///
+/// Restores graph construction to its previous state when dropped
+/// 
+/// The guard belongs to the thread that created it, as that is the only thread it stops building graphs.
+/// 
+pub struct NoGradGuard {
+    device: Device,
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for NoGradGuard {
+    fn drop(&mut self) {
+        NO_GRAD_DEPTH.with_borrow_mut(|depths| {
+            let key = self.device.key();
+            let depth = depths.get_mut(&key).expect("no_grad scopes should be balanced");
+
+            *depth -= 1;
+
+            if *depth == 0 {
+                depths.remove(&key);
+            }
+        });
     }
 }
 
-unsafe impl Send for Device {}
-unsafe impl Sync for Device {}
\ No newline at end of file
+impl Device {
+    ///
+    /// Locks the device's state. A panic while it was held, such as a failed assertion in a test, leaves the state
+    /// consistent, so the lock is taken back rather than poisoning the device for good
+    ///
+    fn inner(&self) -> MutexGuard<'_, DeviceInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub (crate) fn kernels<E: Float>(&self) -> &dyn Kernels<E> {
+        E::kernels(self.backend.as_ref())
+    }
+
+    pub (crate) fn data_kernels<E: DType>(&self) -> &dyn DataKernels<E> {
+        E::data_kernels(self.backend.as_ref())
+    }
+
+    pub (crate) fn pool(&self) -> &Arc<BufferPool> {
+        &self.pool
+    }
+}
@@ -0,0 +1,166 @@
+use std::{any::{Any, TypeId}, collections::HashMap, sync::{atomic::{AtomicUsize, Ordering}, Mutex, MutexGuard, PoisonError}};
+
+use crate::tensor::DType;
+
+use super::{memory::MemoryCounter, Device};
+
+type FreeBuffers = HashMap<(TypeId, usize), Vec<Box<dyn Any + Send>>>;
+
+///
+/// Keeps the buffers of freed tensors and gradients, to reuse for new ones instead of allocating
+///
+/// Training allocates buffers of the same few sizes on every step, so once the first step has run, later steps
+/// mostly reuse the buffers it freed. Buffers are bucketed by element type and capacity, which is always a power of
+/// two, so a buffer is reused for any size that rounds up to its capacity.
+///
+#[derive(Default)]
+pub (crate) struct BufferPool {
+    pub memory: MemoryCounter,
+
+    // Only changed while `free` is locked, so that it always agrees with it
+    pub (super) pooled: AtomicUsize,
+
+    // Keyed by element type and capacity
+    free: Mutex<FreeBuffers>,
+
+    // How many buffers were taken from the pool, and how many had to be allocated because it had none to give
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl BufferPool {
+    ///
+    /// Gets an empty buffer with room for at least `len` elements
+    ///
+    pub fn take<E: DType>(&self, len: usize) -> Vec<E> {
+        if len == 0 {
+            return Vec::new();
+        }
+
+        let bucket = len.next_power_of_two();
+
+        let mut free = self.free();
+
+        match free.get_mut(&(TypeId::of::<E>(), bucket)).and_then(Vec::pop) {
+            Some(buffer) => {
+                let buffer = *buffer.downcast::<Vec<E>>().expect("Pooled buffer has the wrong element type");
+                self.pooled.fetch_sub(Self::bytes::<E>(buffer.capacity()), Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+
+                buffer
+            },
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+
+                Vec::with_capacity(bucket)
+            },
+        }
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// Returns a buffer to the pool once nothing uses it
+    ///
+    /// Only buffers the pool could hand out again are kept, and never more than the peak of allocated memory, so the
+    /// pool stays as big as the largest step needs it to be.
+    ///
+    pub fn recycle<E: DType>(&self, mut buffer: Vec<E>) {
+        let bucket = buffer.capacity();
+
+        if !bucket.is_power_of_two() {
+            return;
+        }
+
+        buffer.clear();
+
+        let mut free = self.free();
+
+        if self.pooled.load(Ordering::Relaxed) + Self::bytes::<E>(bucket) > self.memory.peak() {
+            return;
+        }
+
+        self.pooled.fetch_add(Self::bytes::<E>(bucket), Ordering::Relaxed);
+        free.entry((TypeId::of::<E>(), bucket)).or_default().push(Box::new(buffer));
+    }
+
+    pub fn clear(&self) {
+        let mut free = self.free();
+
+        let buffers = std::mem::take(&mut *free);
+        self.pooled.store(0, Ordering::Relaxed);
+
+        // Deallocating can be slow, so other threads aren't kept waiting for it
+        drop(free);
+        drop(buffers);
+    }
+
+    fn free(&self) -> MutexGuard<'_, FreeBuffers> {
+        self.free.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn bytes<E: DType>(capacity: usize) -> usize {
+        capacity * std::mem::size_of::<E>()
+    }
+}
+
+impl Device {
+    ///
+    /// Gets a buffer of `len` copies of `value`, reusing a freed buffer if there is one
+    ///
+    pub (crate) fn filled_buffer<E: DType>(&self, len: usize, value: E) -> Vec<E> {
+        let mut buffer = self.pool.take(len);
+        buffer.resize(len, value);
+
+        buffer
+    }
+
+    ///
+    /// Collects values into a buffer, reusing a freed buffer if there is one
+    ///
+    pub (crate) fn collect_buffer<E: DType>(&self, values: impl IntoIterator<Item = E>) -> Vec<E> {
+        let values = values.into_iter();
+
+        let mut buffer = self.pool.take(values.size_hint().0);
+        buffer.extend(values);
+
+        buffer
+    }
+
+    ///
+    /// Gives a buffer that is no longer needed, such as a gradient once it has been added up, back to the pool
+    ///
+    pub (crate) fn recycle_buffer<E: DType>(&self, buffer: Vec<E>) {
+        self.pool.recycle(buffer);
+    }
+
+    ///
+    /// Counts the buffers taken from the pool since the device was created
+    ///
+    /// Once the first training step has filled the pool, every later step should take all of its buffers from it, so
+    /// this goes up by the same amount each step while `buffer_pool_misses` stays put.
+    ///
+    pub fn buffer_pool_hits(&self) -> usize {
+        self.pool.hits()
+    }
+
+    ///
+    /// Counts the buffers that had to be allocated since the device was created, because the pool had none to give
+    ///
+    pub fn buffer_pool_misses(&self) -> usize {
+        self.pool.misses()
+    }
+
+    ///
+    /// Frees the buffers kept for reuse, for when a device is done with a workload and its memory is needed elsewhere
+    ///
+    pub fn clear_buffer_pool(&self) {
+        self.pool.clear();
+    }
+}
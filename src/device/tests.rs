@@ -0,0 +1,222 @@
+use std::sync::Barrier;
+
+use rand::Rng;
+
+use crate::{nn::{layers::{Layer, LayerBuilder, Linear}, Activation}, tensor::{AnyTensor, DynShape, Rank1, Rank2, Rank3, ShapeError}, tensor_ops::{dropout, relu, scale, sum}};
+
+use super::*;
+
+fn device() -> Device {
+    let device = Device::new();
+    device.seed(0);
+
+    device
+}
+
+#[test]
+fn filled_constructors() {
+    let device = device();
+
+    assert_eq!(device.get_tensor_buffer(&device.zeros::<Rank2<2, 2>, f32>()), &[0.0; 4]);
+    assert_eq!(device.get_tensor_buffer(&device.ones::<Rank1<3>, i64>()), &[1; 3]);
+    assert_eq!(device.get_tensor_buffer(&device.full::<Rank3<1, 2, 1>, f64>(2.5)), &[2.5; 2]);
+    assert_eq!(device.get_tensor_buffer(&device.constant::<Rank1<3>, u8>(&[7, 8, 9])), &[7, 8, 9]);
+}
+
+#[test]
+fn counting_constructors() {
+    let device = device();
+
+    assert_eq!(device.get_tensor_buffer(&device.arange::<Rank2<2, 3>, i32>(-2, 3)), &[-2, 1, 4, 7, 10, 13]);
+    assert_eq!(device.get_tensor_buffer(&device.arange::<Rank1<4>, f64>(1.0, 0.5)), &[1.0, 1.5, 2.0, 2.5]);
+    assert_eq!(device.get_tensor_buffer(&device.linspace::<Rank1<5>, f64>(0.0, 1.0)), &[0.0, 0.25, 0.5, 0.75, 1.0]);
+    assert_eq!(device.get_tensor_buffer(&device.linspace::<Rank1<1>, f64>(3.0, 4.0)), &[3.0]);
+
+    // The whole range of the type is available
+    let bytes = device.arange::<Rank1<256>, u8>(0, 1);
+    assert_eq!(device.get_tensor_buffer(&bytes)[255], 255);
+
+    let countdown = device.arange::<Rank1<256>, u8>(255, 0);
+    assert!(device.get_tensor_buffer(&countdown).iter().all(|b| *b == 255));
+}
+
+#[test]
+#[should_panic(expected = "Counting 257 values from 0 in steps of 1 goes past the range of u8")]
+fn arange_past_integer_range() {
+    device().arange::<Rank1<257>, u8>(0, 1);
+}
+
+#[test]
+fn indexed_constructors() {
+    let device = device();
+
+    assert_eq!(device.get_tensor_buffer(&device.eye::<3, f32>()), &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    assert_eq!(device.get_tensor_buffer(&device.one_hot::<4, u8>(2)), &[0, 0, 1, 0]);
+
+    let indices = device.from_fn::<Rank3<2, 2, 3>, i64>(|index| (index[0] * 100 + index[1] * 10 + index[2]) as i64);
+    assert_eq!(device.get_tensor_buffer(&indices), &[0, 1, 2, 10, 11, 12, 100, 101, 102, 110, 111, 112]);
+}
+
+#[test]
+#[should_panic(expected = "Index 4 is out of bounds for a one-hot vector of length 4")]
+fn one_hot_out_of_bounds() {
+    device().one_hot::<4, f32>(4);
+}
+
+#[test]
+fn random_constructors() {
+    let device = device();
+
+    let uniform = device.uniform::<Rank2<50, 40>, f64>(-2.0, 3.0);
+    let uniform = device.get_tensor_buffer(&uniform);
+    assert!(uniform.iter().all(|x| (-2.0..3.0).contains(x)));
+    assert!((uniform.iter().sum::<f64>() / 2000.0 - 0.5).abs() < 0.1);
+
+    let normal = device.normal::<Rank2<50, 40>, f64>(1.0, 2.0);
+    let normal = device.get_tensor_buffer(&normal);
+    let mean = normal.iter().sum::<f64>() / 2000.0;
+    let std = (normal.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 2000.0).sqrt();
+    assert!((mean - 1.0).abs() < 0.15 && (std - 2.0).abs() < 0.15, "N(1, 2) gave a mean of {mean} and std of {std}");
+
+    let truncated = device.truncated_normal::<Rank2<50, 40>, f64>(1.0, 2.0);
+    assert!(device.get_tensor_buffer(&truncated).iter().all(|x| (x - 1.0).abs() <= 4.0));
+}
+
+#[test]
+fn dyn_constructors() {
+    let device = device();
+    let shape = DynShape::new([2, 3]);
+
+    let constant = device.constant_dyn(&shape, &[1, 2, 3, 4, 5, 6]).unwrap();
+    assert_eq!(constant.dims(), &[2, 3]);
+    assert_eq!(device.get_tensor_buffer(&constant), &[1, 2, 3, 4, 5, 6]);
+
+    assert_eq!(device.constant_dyn(&shape, &[1.0f32; 5]).unwrap_err(), ShapeError::Size { expected: 6, found: 5 });
+    assert_eq!(device.sample_dyn::<f32>(&shape).dims(), &[2, 3]);
+}
+
+#[test]
+fn seed_makes_runs_reproducible() {
+    let run = |seed| {
+        let device = Device::new();
+        device.seed(seed);
+
+        let layer = Linear::<4, 3>::new(Activation::ReLU).build_layer(&device);
+        let weights = layer.get_tensors()[0].buffer().into_owned();
+
+        let dropped = dropout(device.ones::<Rank1<16>, f32>(), 0.5);
+        let mask = device.get_tensor_buffer(&dropped).into_owned();
+
+        let mut order = (0..10).collect::<Vec<_>>();
+        device.shuffle(&mut order);
+
+        let augmentation = device.with_rng(|rng| rng.gen::<u64>());
+
+        (weights, mask, order, augmentation)
+    };
+
+    assert_eq!(run(7), run(7));
+
+    // Each source of randomness differs with the seed
+    let (first, second) = (run(7), run(8));
+
+    assert_ne!(first.0, second.0);
+    assert_ne!(first.1, second.1);
+    assert_ne!(first.2, second.2);
+    assert_ne!(first.3, second.3);
+}
+
+#[test]
+fn no_grad_builds_no_graph() {
+    let device = device();
+    let x = device.ones::<Rank1<3>, f32>();
+    x.set_requires_grad(true);
+
+    let constant = device.no_grad(|| relu(scale(x.clone(), 2.0)));
+
+    assert_eq!(constant.op_name(), None);
+    assert!(!constant.requires_grad());
+    assert!(AnyTensor::inputs(&constant).is_empty());
+    assert_eq!(device.get_tensor_buffer(&constant), &[2.0; 3]);
+
+    // Graph construction resumes once the scope ends
+    let tracked = relu(x.clone());
+
+    assert_eq!(tracked.op_name(), Some("TensorRelu"));
+    assert!(tracked.requires_grad());
+}
+
+#[test]
+fn nested_no_grad_restores_previous_state() {
+    let device = device();
+    assert!(device.is_grad_enabled());
+
+    let outer = device.no_grad_guard();
+    assert!(!device.is_grad_enabled());
+
+    {
+        let _inner = device.no_grad_guard();
+        assert!(!device.is_grad_enabled());
+    }
+
+    // Dropping the inner guard leaves the outer one in force
+    assert!(!device.is_grad_enabled());
+    assert!(device.no_grad(|| !device.is_grad_enabled()));
+    assert!(!device.is_grad_enabled());
+
+    drop(outer);
+    assert!(device.is_grad_enabled());
+}
+
+#[test]
+fn no_grad_only_applies_to_its_thread() {
+    let device = device();
+    let x = device.ones::<Rank1<3>, f32>();
+    x.set_requires_grad(true);
+
+    let barrier = Barrier::new(2);
+
+    std::thread::scope(|scope| {
+        // One thread evaluates under no_grad for the whole time the other is training
+        scope.spawn(|| {
+            device.no_grad(|| {
+                barrier.wait();
+                assert_eq!(relu(x.clone()).op_name(), None);
+                barrier.wait();
+            });
+        });
+
+        barrier.wait();
+        assert!(device.is_grad_enabled());
+
+        let loss = sum(scale(x.clone(), 3.0));
+        assert!(loss.requires_grad());
+
+        loss.back();
+        barrier.wait();
+    });
+
+    assert_eq!(device.get_gradient_buffer(&x), &[3.0; 3]);
+
+    // Guards are per device, too
+    let other = Device::new();
+    let _guard = other.no_grad_guard();
+
+    assert!(device.is_grad_enabled());
+}
+
+#[test]
+fn anomaly_layers_only_apply_to_their_thread() {
+    let device = device();
+    device.set_detect_anomaly(true);
+
+    let x = device.ones::<Rank1<3>, f32>();
+
+    // One thread is inside a layer while the other overflows outside of any
+    let payload = device.in_layer(Some("LinearLayer"), || {
+        std::thread::scope(|scope| scope.spawn(|| scale(x.clone(), f32::MAX) + scale(x.clone(), f32::MAX)).join().unwrap_err())
+    });
+
+    let message = payload.downcast::<String>().unwrap();
+    assert!(message.contains("TensorAdd produced a NaN or infinite value"), "{message}");
+}
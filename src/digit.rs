@@ -1,6 +1,6 @@
 use std::fs::File;
 
-use crate::{device::Device, nn::{layers::{Convolution2d, Layer, Linear, Reshape}, Activation, Model}, tensor::{Rank1, Rank2}};
+use crate::{device::Device, nn::{layers::{Convolution2d, Layer, Linear, Reshape}, Activation, Init, Model}, tensor::{Rank1, Rank2}};
 
 pub enum DatasetType {
     Test,
@@ -57,8 +57,8 @@ pub fn mnist_model(device: &Device) -> Model<impl Layer<InputShape = Rank1<784>,
 
         // Go through a series of convolutions
         (
-            Convolution2d::<28, 28, 5, 5>(Activation::ReLU),
-            Convolution2d::<28, 28, 7, 7>(Activation::ReLU),
+            Convolution2d::<28, 28, 5, 5>::new(Activation::ReLU).with_init(Init::KaimingNormal),
+            Convolution2d::<28, 28, 7, 7>::new(Activation::ReLU).with_init(Init::KaimingNormal),
         ),
 
         // Reshape the data back to a 1d line
@@ -66,7 +66,7 @@ pub fn mnist_model(device: &Device) -> Model<impl Layer<InputShape = Rank1<784>,
 
         // Run a series of linear transforms
         (
-            Linear::<784, 10>(Activation::Softmax)
+            Linear::<784, 10>::new(Activation::Softmax)
         )
     ));
 
@@ -13,11 +13,18 @@ mod digit;
 
 pub mod tensor;
 pub mod device;
+pub mod backend;
 pub mod tensor_ops;
 pub mod nn;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let device = Device::new();
+    // The packed backend gives the same results whatever the number of threads, so runs are reproducible on any
+    // number of cores of the same kind of CPU
+    let device = Device::with_packed(std::thread::available_parallelism().map_or(1, |n| n.get()));
+    device.seed(0);
+
+    // Set DETECT_ANOMALY to find the op that first produces a NaN, instead of only noticing it in the loss
+    device.set_detect_anomaly(std::env::var_os("DETECT_ANOMALY").is_some());
 
     let model = digit::mnist_model(&device);
     let training_data = MNISTDataset::load(DatasetType::Train).unwrap();
@@ -27,9 +34,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let epochs = 20;
 
-    let targets = (0..10).map(|n| {
-        device.constant(&(0..10).map(|i| if i == n { 1.0 } else { 0.0 }).collect::<Vec<_>>())
-    }).collect::<Vec<_>>();
+    let targets = (0..10).map(|n| device.one_hot(n)).collect::<Vec<_>>();
 
     let mut optimizer = device.build_optimizer(&model, SgdConfig {
         lr: 0.001
@@ -40,10 +45,15 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut sma = SumTreeSMA::<_, f32, 1000>::new();
 
+    let mut order = (0..training_data.rows().len()).collect::<Vec<_>>();
+
     for i in 0..epochs {
         let mut loss = 0.0;
 
-        for (index, row) in tqdm!(training_data.rows().iter().enumerate()) {
+        device.shuffle(&mut order);
+
+        for index in tqdm!(order.iter()) {
+            let row = &training_data.rows()[*index];
             let x_tensor: Tensor<Rank1<784>> = device.constant(&row.pixels);
             let y_tensor = targets[row.label].clone();
 
@@ -74,7 +84,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         for row in tqdm!(test_data.rows().iter()) {
             let x_tensor: Tensor<Rank1<784>> = device.constant(&row.pixels);
 
-            let output = model.forward(x_tensor);
+            let output = device.no_grad(|| model.forward(x_tensor));
             let output_buffer = device.get_tensor_buffer(&output);
 
             let mut max = 0.0;
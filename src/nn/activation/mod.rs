@@ -1,4 +1,4 @@
-use crate::{tensor::{Shape, Tensor}, tensor_ops::{relu, sigmoid, softmax, tanh}};
+use crate::{tensor::{AnyShape, Tensor}, tensor_ops::{relu, sigmoid, softmax, tanh}};
 
 #[derive(Debug)]
 pub enum Activation {
@@ -42,7 +42,7 @@ pub enum Activation {
 }
 
 impl Activation {
-    pub fn apply<S: Shape>(&self, input: Tensor<S>) -> Tensor<S> {
+    pub fn apply<S: AnyShape>(&self, input: Tensor<S>) -> Tensor<S> {
         match self {
             Activation::ReLU => relu(input),
             Activation::Softmax => softmax(input),
@@ -0,0 +1,176 @@
+use rand_distr::{Normal, Uniform};
+
+use crate::{device::Device, tensor::{Dyn, DynShape, Float, Shape, Tensor}};
+
+///
+/// A scheme for choosing the initial values of a layer's parameters
+///
+/// Fan-in and fan-out are the number of inputs feeding into, and outputs fed by, each element of the layer. Layers
+/// work these out from their own shape.
+///
+#[derive(Debug, Clone, Copy)]
+pub enum Init {
+    ///
+    /// Xavier (or Glorot) uniform initialization. Draws from U(-a, a), where a = sqrt(6 / (fan_in + fan_out)).
+    ///
+    /// Keeps the variance of activations roughly constant through layers with symmetric activations, like Tanh.
+    ///
+    XavierUniform,
+
+    ///
+    /// Xavier (or Glorot) normal initialization. Draws from N(0, 2 / (fan_in + fan_out)).
+    ///
+    XavierNormal,
+
+    ///
+    /// Kaiming (or He) uniform initialization. Draws from U(-a, a), where a = sqrt(6 / fan_in).
+    ///
+    /// Accounts for ReLU zeroing half of its inputs, so is the better choice for deep ReLU stacks.
+    ///
+    KaimingUniform,
+
+    ///
+    /// Kaiming (or He) normal initialization. Draws from N(0, 2 / fan_in).
+    ///
+    KaimingNormal,
+
+    ///
+    /// Orthogonal initialization. Treats the parameter as a matrix of its first axis by the rest, and fills it with
+    /// orthonormal rows or columns, whichever there are fewer of.
+    ///
+    /// Only valid for parameters of rank two or more.
+    ///
+    Orthogonal,
+
+    ///
+    /// Sets every value to zero. Usually used for biases.
+    ///
+    Zeros,
+
+    ///
+    /// Sets every value to the given constant
+    ///
+    Constant(f64),
+}
+
+impl Init {
+    ///
+    /// Creates a parameter tensor using this scheme
+    ///
+    /// Parameters require gradients, so they are trained until frozen.
+    ///
+    pub fn build<S: Shape, E: Float>(&self, device: &Device, fan_in: usize, fan_out: usize) -> Tensor<S, E> {
+        let tensor = device.constant(&self.values(device, &S::dims(), fan_in, fan_out));
+        tensor.set_requires_grad(true);
+
+        tensor
+    }
+
+    ///
+    /// Creates a dynamically-shaped parameter tensor using this scheme
+    ///
+    pub fn build_dyn<E: Float>(&self, device: &Device, shape: &DynShape, fan_in: usize, fan_out: usize) -> Tensor<Dyn, E> {
+        let tensor = device.constant_dyn(shape, &self.values(device, shape.dims(), fan_in, fan_out)).unwrap();
+        tensor.set_requires_grad(true);
+
+        tensor
+    }
+
+    fn values<E: Float>(&self, device: &Device, dims: &[usize], fan_in: usize, fan_out: usize) -> Vec<E> {
+        let size = dims.iter().product();
+
+        let fan_in = fan_in as f64;
+        let fan_out = fan_out as f64;
+
+        match self {
+            Init::XavierUniform => {
+                let a = (6.0 / (fan_in + fan_out)).sqrt();
+
+                device.random_values(size, Uniform::new_inclusive(-a, a))
+            },
+            Init::XavierNormal => {
+                let std = (2.0 / (fan_in + fan_out)).sqrt();
+
+                device.random_values(size, Normal::new(0.0, std).unwrap())
+            },
+            Init::KaimingUniform => {
+                let a = (6.0 / fan_in).sqrt();
+
+                device.random_values(size, Uniform::new_inclusive(-a, a))
+            },
+            Init::KaimingNormal => {
+                let std = (2.0 / fan_in).sqrt();
+
+                device.random_values(size, Normal::new(0.0, std).unwrap())
+            },
+            Init::Orthogonal => {
+                assert!(dims.len() >= 2, "Orthogonal initialization needs a parameter of rank two or more");
+
+                orthogonal(device, dims[0], size / dims[0])
+            },
+            Init::Zeros => vec![E::zero(); size],
+            Init::Constant(value) => vec![E::from_f64(*value); size],
+        }
+    }
+}
+
+///
+/// Creates a row-major `rows` x `cols` matrix with orthonormal rows or columns, using Gram-Schmidt on a random
+/// normal matrix
+///
+fn orthogonal<E: Float>(device: &Device, rows: usize, cols: usize) -> Vec<E> {
+    // Orthonormalize the shorter side, working on it as the rows of `vectors`
+    let (count, len) = (rows.min(cols), rows.max(cols));
+    let normal = Normal::new(0.0, 1.0).unwrap();
+
+    let mut vectors: Vec<f64> = device.random_values(count * len, normal);
+
+    orthonormalize(&mut vectors, len, |v| v.copy_from_slice(&device.random_values::<f64>(len, normal)));
+
+    let mut output = vec![E::zero(); rows * cols];
+
+    for r in 0..rows {
+        for c in 0..cols {
+            let value = if rows <= cols { vectors[r * len + c] } else { vectors[c * len + r] };
+
+            output[r * cols + c] = E::from_f64(value);
+        }
+    }
+
+    output
+}
+
+///
+/// Makes the rows of `vectors`, each `len` long, orthonormal with Gram-Schmidt
+///
+/// A row that is left with (almost) nothing once the rows before it are taken out can't be normalized, so it is
+/// replaced using `redraw` and tried again. Random rows only need redrawing with vanishingly small probability.
+///
+pub (super) fn orthonormalize(vectors: &mut [f64], len: usize, mut redraw: impl FnMut(&mut [f64])) {
+    for i in 0..vectors.len() / len {
+        let (done, rest) = vectors.split_at_mut(i * len);
+        let v = &mut rest[..len];
+
+        loop {
+            for u in done.chunks(len) {
+                let dot = u.iter().zip(v.iter()).map(|(a, b)| a * b).sum::<f64>();
+
+                for (x, y) in v.iter_mut().zip(u) {
+                    *x -= dot * y;
+                }
+            }
+
+            let norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+            if norm > 1e-6 {
+                for x in v.iter_mut() {
+                    *x /= norm;
+                }
+
+                break;
+            }
+
+            redraw(v);
+        }
+    }
+}
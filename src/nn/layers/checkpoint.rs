@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{Tensor, TensorRef}, tensor_ops::checkpoint};
+
+use super::{Layer, LayerBuilder};
+
+///
+/// Builds a layer that drops its activations after the forward pass, and recomputes them during backpropagation
+/// 
+/// Wrapping a segment of a model, such as a tuple of layers, saves the memory its intermediate tensors would take
+/// until backpropagation, at the cost of running its forward pass twice. Forward hooks inside the segment run on
+/// both passes. Nothing is saved while anomaly detection is on, as it keeps every tensor's op even under `no_grad`.
+/// 
+pub struct Checkpoint<L: LayerBuilder>(pub L);
+
+pub struct CheckpointLayer<L: Layer> {
+    layer: Arc<L>,
+}
+
+impl<L: LayerBuilder> LayerBuilder for Checkpoint<L> where L::Layer: Send + Sync + 'static {
+    type InputShape = L::InputShape;
+    type OutputShape = L::OutputShape;
+    type Layer = CheckpointLayer<L::Layer>;
+
+    fn build_layer(self, device: &Device) -> Self::Layer {
+        CheckpointLayer {
+            layer: Arc::new(self.0.build_layer(device))
+        }
+    }
+}
+
+impl<L: Layer + Send + Sync + 'static> Layer for CheckpointLayer<L> {
+    type InputShape = L::InputShape;
+    type OutputShape = L::OutputShape;
+
+    fn forward(&self, input: Tensor<Self::InputShape>) -> Tensor<Self::OutputShape> {
+        let layer = self.layer.clone();
+
+        checkpoint(move |input| layer.forward(input), input, self.layer.get_tensors())
+    }
+
+    fn get_tensors(&self) -> Vec<TensorRef> {
+        self.layer.get_tensors()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.layer.name()
+    }
+}
@@ -14,8 +14,10 @@ impl<L1: Layer, L2: Layer<InputShape = L1::OutputShape>> Layer for CombinedLayer
     type OutputShape = L2::OutputShape;
 
     fn forward(&self, input: crate::tensor::Tensor<Self::InputShape>) -> crate::tensor::Tensor<Self::OutputShape> {
-        let intermediate = self.layer1.forward(input);
-        return self.layer2.forward(intermediate);
+        let device = input.device.clone();
+
+        let intermediate = device.in_layer(self.layer1.name(), || self.layer1.forward(input));
+        return device.in_layer(self.layer2.name(), || self.layer2.forward(intermediate));
     }
     
     fn get_tensors(&self) -> Vec<TensorRef> {
@@ -23,6 +25,10 @@ impl<L1: Layer, L2: Layer<InputShape = L1::OutputShape>> Layer for CombinedLayer
             .chain(self.layer2.get_tensors())
             .collect()
     }
+
+    fn name(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 impl<L1: LayerBuilder, L2: LayerBuilder<InputShape = L1::OutputShape>> LayerBuilder for (L1, L2) {
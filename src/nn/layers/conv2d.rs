@@ -1,4 +1,4 @@
-use crate::{device::Device, nn::Activation, tensor::{Rank1, Rank2, Tensor}, tensor_ops::{conv2d, matmul}};
+use crate::{device::Device, nn::{Activation, Init}, tensor::{Rank1, Rank2, Tensor}, tensor_ops::{conv2d, matmul}};
 
 use super::{Layer, LayerBuilder};
 
@@ -7,9 +7,27 @@ use super::{Layer, LayerBuilder};
 /// 
 /// This layer applies a convolutional transformation to the input tensor, relating each element of the output tensor to a local region of the input tensor.
 /// 
-/// The layer is parameterized by the kernel shape.
+/// The layer is parameterized by the kernel shape. The kernel starts with Xavier uniform initialization, unless
+/// another scheme is chosen.
 /// 
-pub struct Convolution2d<const I1: usize, const I2: usize, const K1: usize, const K2: usize>(pub Activation);
+pub struct Convolution2d<const I1: usize, const I2: usize, const K1: usize, const K2: usize> {
+    pub activation: Activation,
+    pub init: Init,
+}
+
+impl<const I1: usize, const I2: usize, const K1: usize, const K2: usize> Convolution2d<I1, I2, K1, K2> {
+    pub fn new(activation: Activation) -> Self {
+        Self {
+            activation,
+            init: Init::XavierUniform
+        }
+    }
+
+    pub fn with_init(mut self, init: Init) -> Self {
+        self.init = init;
+        self
+    }
+}
 
 pub struct Convolution2dLayer<const I1: usize, const I2: usize, const K1: usize, const K2: usize> {
     kernel: Tensor<Rank2<K1, K2>>,
@@ -22,11 +40,12 @@ impl<const I1: usize, const I2: usize, const K1: usize, const K2: usize> LayerBu
     type Layer = Convolution2dLayer<I1, I2, K1, K2>;
 
     fn build_layer(self, device: &Device) -> Self::Layer {
-        let kernel = device.sample();
+        // Every output element sees K1 * K2 inputs, and every input element feeds K1 * K2 outputs
+        let kernel = self.init.build(device, K1 * K2, K1 * K2);
 
         Self::Layer {
             kernel,
-            activation: self.0,
+            activation: self.activation,
         }
     }
 }
@@ -0,0 +1,94 @@
+use crate::{device::Device, nn::{Activation, Init}, tensor::{Dyn, DynShape, ShapeError, Tensor, TensorRef}, tensor_ops::{try_add, try_matmul}};
+
+use super::{Layer, LayerBuilder};
+
+///
+/// A linear layer whose size is only known at runtime, such as when it is read from a config file.
+///
+/// This behaves like `Linear`, but takes and returns dynamically-shaped tensors.
+///
+pub struct DynLinear {
+    pub inputs: usize,
+    pub outputs: usize,
+    pub activation: Activation,
+    pub weight_init: Init,
+    pub bias_init: Init,
+}
+
+impl DynLinear {
+    pub fn new(inputs: usize, outputs: usize, activation: Activation) -> Self {
+        Self {
+            inputs,
+            outputs,
+            activation,
+            weight_init: Init::XavierUniform,
+            bias_init: Init::Zeros
+        }
+    }
+
+    pub fn with_weight_init(mut self, init: Init) -> Self {
+        self.weight_init = init;
+        self
+    }
+
+    pub fn with_bias_init(mut self, init: Init) -> Self {
+        self.bias_init = init;
+        self
+    }
+}
+
+pub struct DynLinearLayer {
+    weights: Tensor<Dyn>,
+    bias: Tensor<Dyn>,
+    activation: Activation
+}
+
+impl LayerBuilder for DynLinear {
+    type InputShape = Dyn;
+    type OutputShape = Dyn;
+    type Layer = DynLinearLayer;
+
+    fn build_layer(self, device: &Device) -> Self::Layer {
+        let weights = self.weight_init.build_dyn(device, &DynShape::new([self.inputs, self.outputs]), self.inputs, self.outputs);
+        let bias = self.bias_init.build_dyn(device, &DynShape::new([self.outputs]), self.inputs, self.outputs);
+
+        Self::Layer {
+            weights,
+            bias,
+            activation: self.activation
+        }
+    }
+}
+
+impl DynLinearLayer {
+    ///
+    /// Applies the layer, failing if the input is not a vector of the layer's input size
+    ///
+    pub fn try_forward(&self, input: Tensor<Dyn>) -> Result<Tensor<Dyn>, ShapeError> {
+        let expected = DynShape::new([self.weights.dims()[0]]);
+
+        if input.shape() != expected {
+            return Err(ShapeError::Mismatch { expected, found: input.shape() });
+        }
+
+        let a = try_add(try_matmul(input, self.weights.clone())?, self.bias.clone())?;
+
+        Ok(self.activation.apply(a))
+    }
+}
+
+impl Layer for DynLinearLayer {
+    type InputShape = Dyn;
+    type OutputShape = Dyn;
+
+    fn forward(&self, input: Tensor<Dyn>) -> Tensor<Dyn> {
+        match self.try_forward(input) {
+            Ok(output) => output,
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    fn get_tensors(&self) -> Vec<TensorRef> {
+        vec![ self.weights.as_ref(), self.bias.as_ref() ]
+    }
+}
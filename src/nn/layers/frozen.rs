@@ -0,0 +1,23 @@
+use crate::device::Device;
+
+use super::{Layer, LayerBuilder};
+
+///
+/// Builds a layer with its parameters frozen, so they are not trained.
+/// 
+/// This is useful for fine-tuning, where pretrained layers are kept fixed while the layers after them learn.
+/// 
+pub struct Frozen<L: LayerBuilder>(pub L);
+
+impl<L: LayerBuilder> LayerBuilder for Frozen<L> {
+    type InputShape = L::InputShape;
+    type OutputShape = L::OutputShape;
+    type Layer = L::Layer;
+
+    fn build_layer(self, device: &Device) -> Self::Layer {
+        let layer = self.0.build_layer(device);
+        layer.freeze();
+
+        layer
+    }
+}
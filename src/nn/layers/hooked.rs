@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{Tensor, TensorRef}};
+
+use super::{Layer, LayerBuilder};
+
+pub type ForwardHook<I, O> = Box<dyn Fn(&Tensor<I>, &Tensor<O>) + Send + Sync>;
+pub type BackwardHook = Arc<dyn Fn(&mut [f32]) + Send + Sync>;
+
+///
+/// Wraps a layer with hooks that observe it as data flows through
+///
+/// Forward hooks are called with the input and output of every forward pass. Backward hooks are called with the
+/// gradient of the layer's output during backpropagation, before it reaches the layer, and may change it.
+///
+/// This is useful for logging activations and gradients, clipping gradients per layer, or tracking down exploding
+/// activations.
+///
+pub struct Hooked<L: LayerBuilder> {
+    layer: L,
+    forward_hooks: Vec<ForwardHook<L::InputShape, L::OutputShape>>,
+    backward_hooks: Vec<BackwardHook>,
+}
+
+pub struct HookedLayer<L: Layer> {
+    layer: L,
+    forward_hooks: Vec<ForwardHook<L::InputShape, L::OutputShape>>,
+    backward_hooks: Vec<BackwardHook>,
+}
+
+impl<L: LayerBuilder> Hooked<L> {
+    pub fn new(layer: L) -> Self {
+        Self {
+            layer,
+            forward_hooks: vec![],
+            backward_hooks: vec![]
+        }
+    }
+
+    pub fn on_forward(mut self, hook: impl Fn(&Tensor<L::InputShape>, &Tensor<L::OutputShape>) + Send + Sync + 'static) -> Self {
+        self.forward_hooks.push(Box::new(hook));
+        self
+    }
+
+    pub fn on_backward(mut self, hook: impl Fn(&mut [f32]) + Send + Sync + 'static) -> Self {
+        self.backward_hooks.push(Arc::new(hook));
+        self
+    }
+}
+
+impl<L: LayerBuilder> LayerBuilder for Hooked<L> {
+    type InputShape = L::InputShape;
+    type OutputShape = L::OutputShape;
+    type Layer = HookedLayer<L::Layer>;
+
+    fn build_layer(self, device: &Device) -> Self::Layer {
+        HookedLayer {
+            layer: self.layer.build_layer(device),
+            forward_hooks: self.forward_hooks,
+            backward_hooks: self.backward_hooks
+        }
+    }
+}
+
+impl<L: Layer> Layer for HookedLayer<L> {
+    type InputShape = L::InputShape;
+    type OutputShape = L::OutputShape;
+
+    fn forward(&self, input: Tensor<Self::InputShape>) -> Tensor<Self::OutputShape> {
+        let output = self.layer.forward(input.clone());
+
+        for hook in &self.forward_hooks {
+            hook(&input, &output);
+        }
+
+        for hook in &self.backward_hooks {
+            let hook = hook.clone();
+
+            output.register_hook(move |gradient| hook(gradient));
+        }
+
+        output
+    }
+
+    fn get_tensors(&self) -> Vec<TensorRef> {
+        self.layer.get_tensors()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.layer.name()
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{device::Device, nn::Activation, tensor::{inner::TensorInner, Rank1, Rank2, Tensor, TensorRef}, tensor_ops::matmul};
+use crate::{device::Device, nn::{Activation, Init}, tensor::{inner::TensorInner, Rank1, Rank2, Tensor, TensorRef}, tensor_ops::matmul};
 
 use super::{Layer, LayerBuilder};
 
@@ -7,9 +7,34 @@ use super::{Layer, LayerBuilder};
 /// 
 /// This layer applies a linear transformation to the input tensor, followed by an activation function.
 /// 
-/// The layer is parameterized by the input and output shapes, and the activation function.
+/// The layer is parameterized by the input and output shapes, and the activation function. Weights start with
+/// Xavier uniform initialization and biases start at zero, unless other schemes are chosen.
 /// 
-pub struct Linear<const I: usize, const O: usize>(pub Activation);
+pub struct Linear<const I: usize, const O: usize> {
+    pub activation: Activation,
+    pub weight_init: Init,
+    pub bias_init: Init,
+}
+
+impl<const I: usize, const O: usize> Linear<I, O> {
+    pub fn new(activation: Activation) -> Self {
+        Self {
+            activation,
+            weight_init: Init::XavierUniform,
+            bias_init: Init::Zeros
+        }
+    }
+
+    pub fn with_weight_init(mut self, init: Init) -> Self {
+        self.weight_init = init;
+        self
+    }
+
+    pub fn with_bias_init(mut self, init: Init) -> Self {
+        self.bias_init = init;
+        self
+    }
+}
 
 pub struct LinearLayer<const I: usize, const O: usize> {
     weights: Tensor<Rank2<I, O>>,
@@ -23,13 +48,13 @@ impl<const I: usize, const O: usize> LayerBuilder for Linear<I, O> {
     type Layer = LinearLayer<I, O>;
 
     fn build_layer(self, device: &Device) -> Self::Layer {
-        let weights = device.sample();
-        let bias = device.sample();
+        let weights = self.weight_init.build(device, I, O);
+        let bias = self.bias_init.build(device, I, O);
 
         Self::Layer {
             weights,
             bias,
-            activation: self.0
+            activation: self.activation
         }
     }
 }
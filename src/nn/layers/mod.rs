@@ -2,24 +2,59 @@ mod linear;
 mod combined;
 mod conv2d;
 mod reshape;
+mod dyn_linear;
+mod frozen;
+mod hooked;
+mod checkpoint;
 
 pub use linear::*;
 pub use conv2d::*;
 pub use reshape::*;
+pub use dyn_linear::*;
+pub use frozen::*;
+pub use hooked::*;
+pub use checkpoint::*;
 
-use crate::{device::Device, tensor::{inner::TensorInner, Shape, Tensor, TensorRef}};
+use crate::{device::Device, tensor::{inner::TensorInner, AnyShape, Tensor, TensorRef}, tensor_ops::short_type_name};
 
 pub trait Layer {
-    type InputShape: Shape;
-    type OutputShape: Shape;
+    type InputShape: AnyShape;
+    type OutputShape: AnyShape;
 
     fn forward(&self, input: Tensor<Self::InputShape>) -> Tensor<Self::OutputShape>;
     fn get_tensors(&self) -> Vec<TensorRef>;
+
+    ///
+    /// The name the layer is reported under when anomaly detection finds a problem in it
+    ///
+    /// Layers that only group or wrap other layers return `None`, so the layers inside are reported instead.
+    ///
+    fn name(&self) -> Option<&'static str> {
+        Some(short_type_name::<Self>())
+    }
+
+    ///
+    /// Stops training the layer's parameters. Backward skips them and optimizers leave them unchanged.
+    ///
+    fn freeze(&self) {
+        for tensor in self.get_tensors() {
+            tensor.set_requires_grad(false);
+        }
+    }
+
+    ///
+    /// Resumes training the layer's parameters
+    ///
+    fn unfreeze(&self) {
+        for tensor in self.get_tensors() {
+            tensor.set_requires_grad(true);
+        }
+    }
 }
 
 pub trait LayerBuilder {
-    type InputShape: Shape;
-    type OutputShape: Shape;
+    type InputShape: AnyShape;
+    type OutputShape: AnyShape;
     type Layer: Layer<InputShape = Self::InputShape, OutputShape = Self::OutputShape>;
 
     fn build_layer(self, device: &Device) -> Self::Layer;
@@ -1,4 +1,4 @@
-use crate::{tensor::{Rank1, Shape, Tensor}, tensor_ops::{cross_entropy_loss, mse}};
+use crate::{tensor::{AnyShape, Rank1, Tensor}, tensor_ops::{cross_entropy_loss, mse}};
 
 pub enum Loss {
     MSE,
@@ -6,7 +6,7 @@ pub enum Loss {
 }
 
 impl Loss {
-    pub fn apply<S: Shape>(&self, values: Tensor<S>, targets: Tensor<S>) -> Tensor<Rank1<1>> {
+    pub fn apply<S: AnyShape>(&self, values: Tensor<S>, targets: Tensor<S>) -> Tensor<Rank1<1>> {
         match self {
             Loss::MSE => {
                 mse(values, targets)
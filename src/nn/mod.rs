@@ -3,9 +3,14 @@ pub mod optimizer;
 
 
 mod activation;
+mod init;
 mod loss;
 mod model;
 
+#[cfg(test)]
+mod tests;
+
 pub use activation::*;
+pub use init::*;
 pub use model::*;
 pub use loss::*;
\ No newline at end of file
@@ -8,6 +8,9 @@ pub struct Model<L: Layer> {
 
 impl<L: Layer> Model<L> {
     pub fn forward(&self, input: Tensor<L::InputShape>) -> Tensor<L::OutputShape> {
-        self.layer.forward(input)
+        let device = input.device.clone();
+
+        let _forward = device.begin_forward();
+        device.in_layer(self.layer.name(), || self.layer.forward(input))
     }
 }
\ No newline at end of file
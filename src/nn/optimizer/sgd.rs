@@ -1,4 +1,4 @@
-use crate::{device::Device, tensor::{inner::TensorInner, TensorRef}};
+use crate::{device::Device, tensor::{inner::TensorInner, AnyTensor, TensorRef}};
 
 use super::OptimizerConfig;
 
@@ -12,7 +12,6 @@ pub struct SgdConfig {
 
 pub struct Sgd {
     cfg:     SgdConfig,
-    device:  Device,
     tensors: Vec<TensorRef>,
 }
 
@@ -27,10 +26,9 @@ impl Default for SgdConfig {
 impl OptimizerConfig for SgdConfig {
     type Optimizer = Sgd;
 
-    fn build_optimizer(&self, tensors: Vec<TensorRef>, device: Device) -> Sgd {
+    fn build_optimizer(&self, tensors: Vec<TensorRef>, _device: Device) -> Sgd {
         Sgd {
             tensors,
-            device,
             cfg: self.clone()
         }
     }
@@ -38,18 +36,23 @@ impl OptimizerConfig for SgdConfig {
 
 impl Sgd {
     ///
-    /// Resets all gradients
+    /// Resets the gradients of the tensors being optimized, freeing them until the next backward pass writes to them
     /// 
     pub fn zero_grad(&mut self) {
-        self.device.zero_grad();
+        for tensor in self.tensors.iter().filter(|t| t.requires_grad()) {
+            tensor.clear_gradient();
+        }
     }
 
+    ///
+    /// Updates every tensor that requires gradients, skipping frozen ones
+    /// 
     pub fn step(&mut self) {
-        for tensor in &self.tensors {
-            let buffer = tensor.buffer_mut();
+        for tensor in self.tensors.iter().filter(|t| t.requires_grad()) {
             let gradient = tensor.gradient();
+            let mut buffer = tensor.buffer_mut();
 
-            for (param, gradient) in buffer.iter_mut().zip(gradient) {
+            for (param, gradient) in buffer.iter_mut().zip(gradient.iter()) {
                 *param -= gradient * self.cfg.lr;
             }
         }
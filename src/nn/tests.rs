@@ -0,0 +1,212 @@
+use crate::{device::Device, nn::{init::orthonormalize, layers::{Checkpoint, Convolution2d, Frozen, Layer, LayerBuilder, Linear}, optimizer::SgdConfig, Activation, Init, Model}, tensor::{DynShape, Rank1, Rank2, Rank3, Tensor}, tensor_ops::mse};
+
+fn device() -> Device {
+    let device = Device::new();
+    device.seed(0);
+
+    device
+}
+
+fn mean_and_std(values: &[f32]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().map(|v| *v as f64).sum::<f64>() / n;
+    let variance = values.iter().map(|v| (*v as f64 - mean).powi(2)).sum::<f64>() / n;
+
+    (mean, variance.sqrt())
+}
+
+///
+/// Checks that a row-major `rows x cols` matrix has orthonormal rows or columns, whichever there are fewer of
+///
+fn assert_orthonormal(matrix: &[f64], rows: usize, cols: usize) {
+    let at = |r: usize, c: usize| if rows <= cols { matrix[r * cols + c] } else { matrix[c * cols + r] };
+    let (count, len) = (rows.min(cols), rows.max(cols));
+
+    for i in 0..count {
+        for j in 0..count {
+            let dot = (0..len).map(|k| at(i, k) * at(j, k)).sum::<f64>();
+            let expected = if i == j { 1.0 } else { 0.0 };
+
+            assert!((dot - expected).abs() < 1e-9, "Vectors {i} and {j} have a dot product of {dot}");
+        }
+    }
+}
+
+#[test]
+fn default_layer_init() {
+    let device = device();
+    let layer = Linear::<100, 50>::new(Activation::ReLU).build_layer(&device);
+    let [weights, bias] = <[_; 2]>::try_from(layer.get_tensors()).ok().unwrap();
+
+    // Xavier uniform draws from U(-a, a) with a = sqrt(6 / (fan_in + fan_out))
+    let a = (6.0f32 / 150.0).sqrt();
+    let weights = weights.buffer();
+
+    assert!(weights.iter().all(|w| w.abs() <= a));
+    assert!(weights.iter().any(|w| w.abs() > 0.9 * a));
+    assert!(bias.buffer().iter().all(|b| *b == 0.0));
+    assert!(bias.requires_grad());
+}
+
+#[test]
+fn chosen_layer_init() {
+    let device = device();
+
+    let layer = Linear::<400, 300>::new(Activation::ReLU)
+        .with_weight_init(Init::KaimingNormal)
+        .with_bias_init(Init::Constant(0.1))
+        .build_layer(&device);
+
+    let tensors = layer.get_tensors();
+
+    // Kaiming normal draws from N(0, 2 / fan_in)
+    let (mean, std) = mean_and_std(&tensors[0].buffer());
+    assert!(mean.abs() < 0.01 && (std - (2.0f64 / 400.0).sqrt()).abs() < 0.002, "Kaiming normal gave a mean of {mean} and std of {std}");
+    assert!(tensors[1].buffer().iter().all(|b| *b == 0.1));
+
+    let convolution = Convolution2d::<8, 8, 3, 3>::new(Activation::ReLU).with_init(Init::KaimingUniform).build_layer(&device);
+    let kernel = convolution.get_tensors()[0].buffer();
+
+    // Each output of a 3 x 3 kernel has 9 inputs, so a = sqrt(6 / 9)
+    assert!(kernel.iter().all(|k| k.abs() <= (6.0f32 / 9.0).sqrt()));
+
+    let zeros = Convolution2d::<8, 8, 3, 3>::new(Activation::ReLU).with_init(Init::Zeros).build_layer(&device);
+    assert!(zeros.get_tensors()[0].buffer().iter().all(|k| *k == 0.0));
+}
+
+#[test]
+fn xavier_normal_init() {
+    let device = device();
+    let tensor: Tensor<Rank2<300, 200>> = Init::XavierNormal.build(&device, 300, 200);
+
+    let (mean, std) = mean_and_std(&device.get_tensor_buffer(&tensor));
+    assert!(mean.abs() < 0.01 && (std - (2.0f64 / 500.0).sqrt()).abs() < 0.002, "Xavier normal gave a mean of {mean} and std of {std}");
+}
+
+#[test]
+fn orthogonal_init() {
+    let device = device();
+
+    let wide: Tensor<Rank2<3, 5>, f64> = Init::Orthogonal.build(&device, 3, 5);
+    assert_orthonormal(&device.get_tensor_buffer(&wide), 3, 5);
+
+    let tall: Tensor<Rank2<6, 2>, f64> = Init::Orthogonal.build(&device, 6, 2);
+    assert_orthonormal(&device.get_tensor_buffer(&tall), 6, 2);
+
+    // Higher ranks are flattened into their first axis by the rest
+    let kernel: Tensor<Rank3<2, 2, 3>, f64> = Init::Orthogonal.build(&device, 6, 4);
+    assert_orthonormal(&device.get_tensor_buffer(&kernel), 2, 6);
+
+    let square = Init::Orthogonal.build_dyn::<f64>(&device, &DynShape::new([4, 4]), 4, 4);
+    assert_orthonormal(&device.get_tensor_buffer(&square), 4, 4);
+}
+
+#[test]
+#[should_panic(expected = "Orthogonal initialization needs a parameter of rank two or more")]
+fn orthogonal_init_needs_a_matrix() {
+    Init::Orthogonal.build_dyn::<f32>(&device(), &DynShape::new([4]), 4, 4);
+}
+
+#[test]
+fn orthonormalize_redraws_dependent_vectors() {
+    // The second and third rows are multiples of the first, leaving nothing once it is taken out
+    let mut vectors = vec![1.0, 2.0, 2.0, -2.0, -4.0, -4.0, 0.5, 1.0, 1.0];
+    let mut redraws = 0;
+
+    orthonormalize(&mut vectors, 3, |v| {
+        redraws += 1;
+        v.copy_from_slice(&[[0.0, 1.0, 0.0], [0.0, 0.0, 1.0]][(redraws - 1) % 2]);
+    });
+
+    assert_eq!(redraws, 2);
+    assert_orthonormal(&vectors, 3, 3);
+    assert!(vectors.iter().all(|v| v.is_finite()));
+}
+
+#[test]
+fn frozen_layers_are_not_trained() {
+    let device = device();
+    let model = device.build_model((Frozen(Linear::<3, 4>::new(Activation::Tanh)), Linear::<4, 2>::new(Activation::Sigmoid)));
+    let mut optimizer = device.build_optimizer(&model, SgdConfig { lr: 0.5 });
+
+    let tensors = model.layer.get_tensors();
+    let (frozen, trained) = tensors.split_at(2);
+
+    assert!(frozen.iter().all(|t| !t.requires_grad()));
+    assert!(trained.iter().all(|t| t.requires_grad()));
+
+    let before = tensors.iter().map(|t| t.buffer().into_owned()).collect::<Vec<_>>();
+
+    let x = device.uniform::<Rank1<3>, f32>(-1.0, 1.0);
+    mse(model.forward(x), device.ones()).back();
+    optimizer.step();
+
+    let after = tensors.iter().map(|t| t.buffer().into_owned()).collect::<Vec<_>>();
+
+    // Backward skips the frozen parameters, and the optimizer leaves them alone
+    assert!(frozen.iter().all(|t| t.gradient().iter().all(|g| *g == 0.0)));
+    assert_eq!(before[..2], after[..2]);
+    assert_ne!(before[2], after[2]);
+
+    // Zeroing frees the gradients rather than filling them, and never allocates one for a frozen parameter
+    optimizer.zero_grad();
+
+    assert!(tensors.iter().all(|t| t.gradient().iter().all(|g| *g == 0.0)));
+    assert_eq!(device.memory_stats().bytes_allocated, (12 + 4 + 8 + 2) * std::mem::size_of::<f32>());
+}
+
+#[test]
+fn freeze_and_unfreeze() {
+    let device = device();
+    let layer = Linear::<3, 2>::new(Activation::ReLU).build_layer(&device);
+
+    layer.freeze();
+    assert!(layer.get_tensors().iter().all(|t| !t.requires_grad()));
+
+    layer.unfreeze();
+    assert!(layer.get_tensors().iter().all(|t| t.requires_grad()));
+}
+
+#[test]
+#[should_panic(expected = "TensorMatMul in LinearLayer #2 produced a NaN or infinite value")]
+fn forward_anomaly_names_the_layer() {
+    let device = device();
+    device.set_detect_anomaly(true);
+
+    let model = device.build_model((Linear::<2, 3>::new(Activation::Tanh), Linear::<3, 1>::new(Activation::Sigmoid)));
+    let tensors = model.layer.get_tensors();
+
+    // The first layer is fine, but the second overflows when it multiplies by its weights
+    tensors[0].buffer_mut().fill(1.0);
+    tensors[2].buffer_mut().fill(f32::MAX);
+
+    model.forward(device.ones());
+}
+
+///
+/// Backpropagates a loss through the model, returning the number of tensors in its graph and every parameter's gradient
+///
+fn graph_and_gradients<L: Layer<InputShape = Rank1<3>, OutputShape = Rank1<2>>>(device: &Device, model: &Model<L>) -> (usize, Vec<Vec<f32>>) {
+    let output = model.forward(device.uniform(-1.0, 1.0));
+    let nodes = output.to_dot().matches("[label=").count();
+
+    mse(output, device.ones()).back();
+
+    (nodes, model.layer.get_tensors().iter().map(|t| t.gradient().into_owned()).collect())
+}
+
+#[test]
+fn checkpointed_model_matches_plain_model() {
+    let segment = || (Linear::<3, 4>::new(Activation::Tanh), Linear::<4, 4>::new(Activation::ReLU));
+    let head = || Linear::<4, 2>::new(Activation::Sigmoid);
+
+    // Devices seeded alike build the same parameters
+    let (first, second) = (device(), device());
+
+    let (checkpointed_nodes, checkpointed) = graph_and_gradients(&first, &first.build_model((Checkpoint(segment()), head())));
+    let (plain_nodes, plain) = graph_and_gradients(&second, &second.build_model((segment(), head())));
+
+    // The same parameters get the same gradients, but the segment's intermediate tensors aren't kept
+    assert_eq!(checkpointed, plain);
+    assert!(checkpointed_nodes < plain_nodes, "{checkpointed_nodes} nodes with checkpointing, {plain_nodes} without");
+}
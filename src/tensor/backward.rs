@@ -0,0 +1,114 @@
+use crate::device::Device;
+
+use super::{graph, AnyShape, AnyTensor, Float, Tensor};
+
+type Seed<'a> = Box<dyn FnOnce() + 'a>;
+
+///
+/// A backward pass from one or more tensors
+///
+/// Every tensor is seeded with a gradient, and the gradients from all of them are summed on the way back, so several
+/// losses that share part of their graph can be backpropagated together. The gradients of leaves, such as
+/// parameters, are added to whatever is already in their buffers, so they add up across backward passes until they
+/// are zeroed.
+///
+/// A backward pass marks the graph it went through as used, and going through a used graph again panics rather than
+/// silently counting its gradients twice. Use `retain_graph` to allow another pass. Marking the graph doesn't free it:
+/// the ops and the tensors they saved live as long as the tensors that refer to them, so drop the loss to free them.
+///
+#[derive(Default)]
+pub struct Backward<'a> {
+    device: Option<Device>,
+    roots: Vec<&'a dyn AnyTensor>,
+    seeds: Vec<Seed<'a>>,
+    retain_graph: bool,
+    stop_at_leaves: bool,
+}
+
+impl<'a> Backward<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Backpropagates from a tensor, with a gradient of one for every element
+    ///
+    pub fn root<S: AnyShape, E: Float>(self, tensor: &'a Tensor<S, E>) -> Self {
+        self.root_with(tensor, &vec![E::one(); tensor.size()])
+    }
+
+    ///
+    /// Backpropagates from a tensor, with the given gradient for its elements in row-major order
+    ///
+    pub fn root_with<S: AnyShape, E: Float>(mut self, tensor: &'a Tensor<S, E>, gradient: &[E]) -> Self {
+        assert!(tensor.requires_grad(), "Cannot backpropagate from a tensor that does not require gradients");
+        assert_eq!(gradient.len(), tensor.size(), "Gradient must have as many elements as the tensor");
+
+        let gradient = gradient.to_vec();
+
+        self.device.get_or_insert_with(|| tensor.device.clone());
+        self.roots.push(tensor);
+        self.seeds.push(Box::new(move || tensor.device.add_to_gradient(tensor, &gradient)));
+
+        self
+    }
+
+    ///
+    /// Keeps the graph after this pass, so it can be backpropagated through again
+    ///
+    pub fn retain_graph(mut self, retain_graph: bool) -> Self {
+        self.retain_graph = retain_graph;
+        self
+    }
+
+    pub (crate) fn stop_at_leaves(mut self) -> Self {
+        self.stop_at_leaves = true;
+        self
+    }
+
+    pub fn run(self) {
+        let Some(device) = self.device else {
+            return;
+        };
+
+        // Every tensor passes its gradient on only once all of its consumers have added to it
+        let order = graph::topological_order_from(&self.roots)
+            .into_iter()
+            .filter(|tensor| tensor.requires_grad() && (!self.stop_at_leaves || tensor.op_name().is_some()))
+            .collect::<Vec<_>>();
+
+        for tensor in &order {
+            assert!(
+                !tensor.graph_used(),
+                "Cannot backpropagate through {} a second time, as its graph was already used. Use Backward::retain_graph to allow it",
+                tensor.op_name().unwrap_or("a tensor")
+            );
+        }
+
+        // Only leaves accumulate gradients across passes, the rest are recalculated from scratch
+        for tensor in &order {
+            if tensor.op_name().is_some() {
+                tensor.clear_gradient();
+            }
+        }
+
+        for seed in self.seeds {
+            seed();
+        }
+
+        for tensor in &order {
+            tensor.run_hooks();
+            tensor.propagate();
+
+            device.check_backward(*tensor);
+        }
+
+        if !self.retain_graph {
+            for tensor in &order {
+                if tensor.op_name().is_some() {
+                    tensor.mark_graph_used();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::{fmt::Debug, ops::{Deref, DerefMut, Range}, sync::{Arc, RwLockWriteGuard}};
+
+use super::{inner::Storage, DType};
+
+///
+/// The elements of a tensor or its gradient, as they were when the buffer was taken
+///
+/// The buffer keeps its storage alive, so it stays valid however the tensor changes afterwards. Writes made to the
+/// tensor while the buffer is held are not seen through it.
+///
+pub struct Buffer<E: DType> {
+    storage: Arc<Storage<E>>,
+    range: Range<usize>,
+}
+
+impl<E: DType> Buffer<E> {
+    pub (crate) fn new(storage: Arc<Storage<E>>, range: Range<usize>) -> Self {
+        Self { storage, range }
+    }
+
+    pub (crate) fn owned(data: Vec<E>) -> Self {
+        let range = 0..data.len();
+
+        Self { storage: Arc::new(Storage::new(data)), range }
+    }
+
+    ///
+    /// Takes the elements out of the buffer, copying them only if the storage is shared
+    ///
+    pub fn into_owned(self) -> Vec<E> {
+        match Arc::try_unwrap(self.storage) {
+            Ok(storage) if self.range == (0..storage.data.len()) => storage.into_data(),
+            Ok(storage) => storage.data[self.range].to_vec(),
+            Err(storage) => storage.data[self.range].to_vec(),
+        }
+    }
+}
+
+impl<E: DType> Deref for Buffer<E> {
+    type Target = [E];
+
+    fn deref(&self) -> &[E] {
+        &self.storage.data[self.range.clone()]
+    }
+}
+
+impl<E: DType> AsRef<[E]> for Buffer<E> {
+    fn as_ref(&self) -> &[E] {
+        self
+    }
+}
+
+impl<E: DType> Debug for Buffer<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.deref().fmt(f)
+    }
+}
+
+impl<E: DType, T: AsRef<[E]>> PartialEq<T> for Buffer<E> {
+    fn eq(&self, other: &T) -> bool {
+        self.deref() == other.as_ref()
+    }
+}
+
+///
+/// Exclusive access to the elements of a tensor or its gradient
+///
+/// Other tensors that share the storage, such as views, keep the values they had before the write.
+///
+pub struct BufferMut<'a, E: DType> {
+    storage: RwLockWriteGuard<'a, Arc<Storage<E>>>,
+    range: Range<usize>,
+}
+
+impl<'a, E: DType> BufferMut<'a, E> {
+    pub (crate) fn new(mut storage: RwLockWriteGuard<'a, Arc<Storage<E>>>, range: Range<usize>) -> Self {
+        // Copy the storage if anything else can see it, so the write can't be observed halfway through
+        Arc::make_mut(&mut storage);
+
+        Self { storage, range }
+    }
+}
+
+impl<E: DType> Deref for BufferMut<'_, E> {
+    type Target = [E];
+
+    fn deref(&self) -> &[E] {
+        &self.storage.data[self.range.clone()]
+    }
+}
+
+impl<E: DType> DerefMut for BufferMut<'_, E> {
+    fn deref_mut(&mut self) -> &mut [E] {
+        let storage = Arc::get_mut(&mut self.storage).expect("Storage was shared while mutably borrowed");
+
+        &mut storage.data[self.range.clone()]
+    }
+}
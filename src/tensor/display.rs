@@ -0,0 +1,104 @@
+use std::fmt::{self, Debug, Display, Formatter};
+
+use super::{AnyShape, DType, Tensor};
+
+///
+/// Axes longer than twice this are elided, showing only this many entries at each end
+///
+const EDGE_ITEMS: usize = 3;
+
+///
+/// Prints the shape, element type and values of a tensor, eliding the middle of long axes
+///
+/// The alternate form (`{:#}`) also prints the op that created the tensor and its gradient. A precision
+/// (`{:.3}`) applies to every element.
+///
+impl<S: AnyShape, E: DType> Display for Tensor<S, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Tensor{} {}", self.shape(), E::NAME)?;
+
+        if f.alternate() {
+            write!(f, " ({})", self.op_name().unwrap_or("constant"))?;
+        }
+
+        writeln!(f)?;
+        write_values(f, &self.inner.buffer(), self.dims(), Some(0))?;
+
+        if f.alternate() {
+            write!(f, "\ngradient:\n")?;
+            write_values(f, &self.inner.gradient(), self.dims(), Some(0))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: AnyShape, E: DType> Debug for Tensor<S, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Tensor {{ id: {}, shape: {}, dtype: {}, op: {}, data: ", self.id.0, self.shape(), E::NAME, self.op_name().unwrap_or("constant"))?;
+        write_values(f, &self.inner.buffer(), self.dims(), None)?;
+
+        if f.alternate() {
+            write!(f, ", gradient: ")?;
+            write_values(f, &self.inner.gradient(), self.dims(), None)?;
+        }
+
+        write!(f, " }}")
+    }
+}
+
+///
+/// Writes row-major `data` as nested brackets, one level per axis
+///
+/// With a `depth`, outer axes are split across lines and indented under `depth` brackets. Without one, everything is
+/// written on a single line.
+///
+fn write_values<E: DType>(f: &mut Formatter<'_>, data: &[E], dims: &[usize], depth: Option<usize>) -> fmt::Result {
+    let Some((&len, rest)) = dims.split_first() else {
+        return match f.precision() {
+            Some(precision) => write!(f, "{:.*?}", precision, data[0]),
+            None => write!(f, "{:?}", data[0]),
+        };
+    };
+
+    let stride = rest.iter().product::<usize>();
+    let elide = len > 2 * EDGE_ITEMS;
+
+    write!(f, "[")?;
+
+    for i in 0..len {
+        if elide && i >= EDGE_ITEMS && i < len - EDGE_ITEMS {
+            continue;
+        }
+
+        if i > 0 {
+            write_separator(f, rest.len(), depth)?;
+        }
+
+        if elide && i == len - EDGE_ITEMS {
+            write!(f, "...")?;
+            write_separator(f, rest.len(), depth)?;
+        }
+
+        write_values(f, &data[i * stride..(i + 1) * stride], rest, depth.map(|d| d + 1))?;
+    }
+
+    write!(f, "]")
+}
+
+///
+/// Separates entries of an axis, putting each entry of an outer axis on its own line
+///
+fn write_separator(f: &mut Formatter<'_>, inner_rank: usize, depth: Option<usize>) -> fmt::Result {
+    let Some(depth) = depth.filter(|_| inner_rank > 0) else {
+        return write!(f, ", ");
+    };
+
+    write!(f, ",")?;
+
+    for _ in 0..inner_rank {
+        writeln!(f)?;
+    }
+
+    write!(f, "{:width$}", "", width = depth + 1)
+}
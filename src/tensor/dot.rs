@@ -0,0 +1,48 @@
+use std::{fmt::Write, path::Path};
+
+use super::{graph, AnyShape, DType, Tensor};
+
+impl<S: AnyShape, E: DType> Tensor<S, E> {
+    ///
+    /// Describes the computation graph that produced this tensor in the Graphviz DOT language
+    ///
+    /// Every tensor in the graph is a node labelled with the op that created it, its shape and its element type.
+    /// Edges run from the inputs of each op to its output. Constants are drawn as ellipses, and leaves that require
+    /// gradients (usually parameters) are filled in.
+    ///
+    pub fn to_dot(&self) -> String {
+        let mut tensors = graph::topological_order(self);
+        tensors.reverse();
+
+        let mut dot = String::from("digraph {\n    node [shape=box];\n");
+
+        for tensor in &tensors {
+            let id = tensor.id().0;
+            let shape = tensor.shape();
+            let dtype = tensor.dtype();
+
+            let _ = match tensor.op_name() {
+                Some(op) => writeln!(dot, "    t{id} [label=\"{op}\\n{shape} {dtype}\"];"),
+                None if tensor.requires_grad() => writeln!(dot, "    t{id} [label=\"parameter\\n{shape} {dtype}\", shape=ellipse, style=filled];"),
+                None => writeln!(dot, "    t{id} [label=\"constant\\n{shape} {dtype}\", shape=ellipse];"),
+            };
+        }
+
+        for tensor in &tensors {
+            for input in tensor.inputs() {
+                let _ = writeln!(dot, "    t{} -> t{};", input.id().0, tensor.id().0);
+            }
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    ///
+    /// Writes the computation graph to a DOT file, which can be rendered with `dot -Tsvg graph.dot -o graph.svg`
+    ///
+    pub fn save_dot(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_dot())
+    }
+}
@@ -0,0 +1,180 @@
+use std::{fmt::Debug, iter::Sum};
+
+use half::{bf16, f16};
+use num_traits::{Bounded, NumAssign, NumCast};
+
+use crate::backend::{Backend, DataKernels, Kernels};
+
+///
+/// A type that can be stored in a tensor
+///
+/// Every element type supports the arithmetic needed to move data and gradients around, which is enough for the
+/// layout ops (reshape, permute, slicing and concatenation).
+///
+pub trait DType: Copy + Default + Debug + PartialOrd + NumAssign + NumCast + Bounded + Send + Sync + 'static {
+    ///
+    /// The name of the type, as shown when printing tensors
+    ///
+    const NAME: &'static str;
+
+    ///
+    /// Whether the type is a floating point one, which gradients can flow through
+    ///
+    const IS_FLOAT: bool = false;
+
+    ///
+    /// Converts from an `f64`, with the semantics of `as`: integers round toward zero and saturate at their bounds,
+    /// with NaN becoming zero, so that no value fails to convert
+    ///
+    fn from_f64(value: f64) -> Self {
+        <Self as NumCast>::from(value).unwrap()
+    }
+
+    fn as_f64(self) -> f64 {
+        <f64 as NumCast>::from(self).unwrap()
+    }
+
+    ///
+    /// Picks the backend's kernels for moving values of this type around
+    ///
+    fn data_kernels(backend: &dyn Backend) -> &dyn DataKernels<Self>;
+}
+
+///
+/// A floating point element type, which every differentiable op is implemented for
+///
+pub trait Float: DType + num_traits::Float + Sum {
+    ///
+    /// Picks the backend's kernels for this type
+    ///
+    fn kernels(backend: &dyn Backend) -> &dyn Kernels<Self>;
+}
+
+///
+/// An integer element type that can be used to index into other tensors, such as class labels or token ids
+///
+pub trait Index: DType {
+    fn to_index(self) -> usize {
+        <usize as NumCast>::from(self).expect("Index must be a non-negative integer")
+    }
+}
+
+impl DType for f32 {
+    const NAME: &'static str = "f32";
+    const IS_FLOAT: bool = true;
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn data_kernels(backend: &dyn Backend) -> &dyn DataKernels<Self> {
+        backend.f32()
+    }
+}
+
+impl DType for f64 {
+    const NAME: &'static str = "f64";
+    const IS_FLOAT: bool = true;
+
+    fn data_kernels(backend: &dyn Backend) -> &dyn DataKernels<Self> {
+        backend.f64()
+    }
+}
+
+impl DType for f16 {
+    const NAME: &'static str = "f16";
+    const IS_FLOAT: bool = true;
+
+    fn from_f64(value: f64) -> Self {
+        f16::from_f64(value)
+    }
+
+    fn as_f64(self) -> f64 {
+        f16::to_f64(self)
+    }
+
+    fn data_kernels(backend: &dyn Backend) -> &dyn DataKernels<Self> {
+        backend.f16()
+    }
+}
+
+impl DType for bf16 {
+    const NAME: &'static str = "bf16";
+    const IS_FLOAT: bool = true;
+
+    fn from_f64(value: f64) -> Self {
+        bf16::from_f64(value)
+    }
+
+    fn as_f64(self) -> f64 {
+        bf16::to_f64(self)
+    }
+
+    fn data_kernels(backend: &dyn Backend) -> &dyn DataKernels<Self> {
+        backend.bf16()
+    }
+}
+
+impl DType for i64 {
+    const NAME: &'static str = "i64";
+
+    fn from_f64(value: f64) -> Self {
+        value as i64
+    }
+
+    fn data_kernels(backend: &dyn Backend) -> &dyn DataKernels<Self> {
+        backend.i64()
+    }
+}
+
+impl DType for i32 {
+    const NAME: &'static str = "i32";
+
+    fn from_f64(value: f64) -> Self {
+        value as i32
+    }
+
+    fn data_kernels(backend: &dyn Backend) -> &dyn DataKernels<Self> {
+        backend.i32()
+    }
+}
+
+impl DType for u8 {
+    const NAME: &'static str = "u8";
+
+    fn from_f64(value: f64) -> Self {
+        value as u8
+    }
+
+    fn data_kernels(backend: &dyn Backend) -> &dyn DataKernels<Self> {
+        backend.u8()
+    }
+}
+
+impl Float for f32 {
+    fn kernels(backend: &dyn Backend) -> &dyn Kernels<Self> {
+        backend.f32()
+    }
+}
+
+impl Float for f64 {
+    fn kernels(backend: &dyn Backend) -> &dyn Kernels<Self> {
+        backend.f64()
+    }
+}
+
+impl Float for f16 {
+    fn kernels(backend: &dyn Backend) -> &dyn Kernels<Self> {
+        backend.f16()
+    }
+}
+
+impl Float for bf16 {
+    fn kernels(backend: &dyn Backend) -> &dyn Kernels<Self> {
+        backend.bf16()
+    }
+}
+
+impl Index for i64 {}
+impl Index for i32 {}
+impl Index for u8 {}
@@ -0,0 +1,143 @@
+use std::{error::Error, fmt::{self, Display}};
+
+use super::{Rank1, Rank2, Rank3, Shape};
+
+///
+/// The dimensions of a tensor, known at runtime
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DynShape {
+    dims: Vec<usize>,
+}
+
+impl DynShape {
+    pub fn new(dims: impl Into<Vec<usize>>) -> Self {
+        Self { dims: dims.into() }
+    }
+
+    ///
+    /// The runtime equivalent of a static shape
+    ///
+    pub fn of<S: Shape>() -> Self {
+        Self::new(S::dims())
+    }
+
+    pub fn dims(&self) -> &[usize] {
+        &self.dims
+    }
+
+    pub fn rank(&self) -> usize {
+        self.dims.len()
+    }
+
+    ///
+    /// The number of elements in a tensor of this shape
+    ///
+    pub fn size(&self) -> usize {
+        self.dims.iter().product()
+    }
+
+    pub fn last_dim(&self) -> usize {
+        self.dims.last().copied().unwrap_or(1)
+    }
+
+    ///
+    /// Checks that this shape is the same as a static shape
+    ///
+    pub fn check<S: Shape>(&self) -> Result<(), ShapeError> {
+        let expected = Self::of::<S>();
+
+        if *self != expected {
+            return Err(ShapeError::Mismatch { expected, found: self.clone() });
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for DynShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.dims)
+    }
+}
+
+impl<const A: usize> From<Rank1<A>> for DynShape {
+    fn from(_: Rank1<A>) -> Self {
+        Self::of::<Rank1<A>>()
+    }
+}
+
+impl<const A: usize, const B: usize> From<Rank2<A, B>> for DynShape {
+    fn from(_: Rank2<A, B>) -> Self {
+        Self::of::<Rank2<A, B>>()
+    }
+}
+
+impl<const A: usize, const B: usize, const C: usize> From<Rank3<A, B, C>> for DynShape {
+    fn from(_: Rank3<A, B, C>) -> Self {
+        Self::of::<Rank3<A, B, C>>()
+    }
+}
+
+impl<const A: usize> TryFrom<&DynShape> for Rank1<A> {
+    type Error = ShapeError;
+
+    fn try_from(shape: &DynShape) -> Result<Self, ShapeError> {
+        shape.check::<Self>().map(|_| Rank1)
+    }
+}
+
+impl<const A: usize, const B: usize> TryFrom<&DynShape> for Rank2<A, B> {
+    type Error = ShapeError;
+
+    fn try_from(shape: &DynShape) -> Result<Self, ShapeError> {
+        shape.check::<Self>().map(|_| Rank2)
+    }
+}
+
+impl<const A: usize, const B: usize, const C: usize> TryFrom<&DynShape> for Rank3<A, B, C> {
+    type Error = ShapeError;
+
+    fn try_from(shape: &DynShape) -> Result<Self, ShapeError> {
+        shape.check::<Self>().map(|_| Rank3)
+    }
+}
+
+///
+/// An error caused by tensors whose runtime shapes don't fit together
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShapeError {
+    ///
+    /// A tensor did not have the shape it was required to have
+    ///
+    Mismatch { expected: DynShape, found: DynShape },
+
+    ///
+    /// A tensor was given a shape with a different number of elements
+    ///
+    Size { expected: usize, found: usize },
+
+    ///
+    /// The shapes of two operands cannot be combined by an op
+    ///
+    Incompatible { op: &'static str, lhs: DynShape, rhs: DynShape },
+}
+
+impl Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShapeError::Mismatch { expected, found } => {
+                write!(f, "expected a tensor of shape {expected}, found {found}")
+            }
+            ShapeError::Size { expected, found } => {
+                write!(f, "expected {expected} elements, found {found}")
+            }
+            ShapeError::Incompatible { op, lhs, rhs } => {
+                write!(f, "cannot {op} tensors of shapes {lhs} and {rhs}")
+            }
+        }
+    }
+}
+
+impl Error for ShapeError {}
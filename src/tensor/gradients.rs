@@ -0,0 +1,81 @@
+use std::{any::Any, collections::HashMap};
+
+use crate::tensor_ops::add;
+
+use super::{AnyShape, DType, Tensor, TensorId};
+
+///
+/// Gradients calculated as tensors by `Tensor::gradients`
+///
+/// Each gradient is part of the computation graph, so it can be used in further computation and backpropagated
+/// through again, for example to penalise the size of a gradient or to calculate Hessian-vector products.
+///
+#[derive(Default)]
+pub struct Gradients {
+    tensors: HashMap<TensorId, Box<dyn Gradient>>,
+}
+
+impl Gradients {
+    ///
+    /// The gradient of a tensor, or `None` if it doesn't require gradients or the output wasn't computed from it
+    ///
+    pub fn get<S: AnyShape, E: DType>(&self, tensor: &Tensor<S, E>) -> Option<Tensor<S, E>> {
+        self.get_by_id(tensor.id)
+    }
+
+    ///
+    /// Adds to the gradient of a tensor, if it requires gradients
+    ///
+    pub (crate) fn add<S: AnyShape, E: DType>(&mut self, tensor: &Tensor<S, E>, gradient: Tensor<S, E>) {
+        if !tensor.requires_grad() {
+            return;
+        }
+
+        self.add_by_id(tensor.id, gradient);
+    }
+
+    ///
+    /// Adds the gradients that `other` has for the given tensors to these ones
+    ///
+    /// This is for ops that calculate gradients of a graph of their own, whose leaves are tensors of the outer graph.
+    ///
+    pub (crate) fn merge(&mut self, other: &Gradients, ids: impl IntoIterator<Item = TensorId>) {
+        for id in ids {
+            if let Some(gradient) = other.tensors.get(&id) {
+                gradient.add_to(id, self);
+            }
+        }
+    }
+
+    fn get_by_id<S: AnyShape, E: DType>(&self, id: TensorId) -> Option<Tensor<S, E>> {
+        self.tensors.get(&id)?.as_any().downcast_ref::<Tensor<S, E>>().cloned()
+    }
+
+    fn add_by_id<S: AnyShape, E: DType>(&mut self, id: TensorId, gradient: Tensor<S, E>) {
+        let gradient = match self.get_by_id(id) {
+            Some(existing) => add(existing, gradient),
+            None => gradient,
+        };
+
+        self.tensors.insert(id, Box::new(gradient));
+    }
+}
+
+///
+/// A gradient tensor of any shape and type, which remembers its type so it can be added to another gradient
+///
+trait Gradient: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn add_to(&self, id: TensorId, gradients: &mut Gradients);
+}
+
+impl<S: AnyShape, E: DType> Gradient for Tensor<S, E> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn add_to(&self, id: TensorId, gradients: &mut Gradients) {
+        gradients.add_by_id(id, self.clone());
+    }
+}
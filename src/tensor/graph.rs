@@ -0,0 +1,35 @@
+use std::{cmp::Reverse, collections::HashSet};
+
+use super::AnyTensor;
+
+///
+/// Collects every tensor that `root` was computed from, including `root` itself
+/// 
+/// Tensors are ordered so that each one comes before all of its inputs, which is the order backpropagation needs.
+/// Since ids increase in creation order, this is just descending id order.
+/// 
+pub (crate) fn topological_order(root: &dyn AnyTensor) -> Vec<&dyn AnyTensor> {
+    topological_order_from(&[root])
+}
+
+///
+/// Collects every tensor that any of `roots` were computed from, in the same order as `topological_order`
+/// 
+pub (crate) fn topological_order_from<'a>(roots: &[&'a dyn AnyTensor]) -> Vec<&'a dyn AnyTensor> {
+    let mut seen = HashSet::new();
+    let mut order = vec![];
+    let mut stack = roots.to_vec();
+
+    while let Some(tensor) = stack.pop() {
+        if !seen.insert(tensor.id()) {
+            continue;
+        }
+
+        stack.extend(tensor.inputs());
+        order.push(tensor);
+    }
+
+    order.sort_by_key(|tensor| Reverse(tensor.id()));
+
+    order
+}
@@ -1,36 +1,267 @@
-use std::cell::UnsafeCell;
+use std::{cell::RefCell, collections::HashSet, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, PoisonError, RwLock}};
 
-pub struct TensorInner {
-    buffer:     UnsafeCell<Vec<f32>>,
-    gradient:   UnsafeCell<Vec<f32>>,
+use crate::device::{BufferPool, Device};
+
+use super::{Buffer, BufferMut, DType, Layout, TensorId};
+
+///
+/// A flat buffer of elements that can be shared between several tensors
+///
+pub struct Storage<E: DType> {
+    pub (crate) data: Vec<E>,
+
+    // The device's pool, for storage that belongs to a tensor. It counts the storage's bytes, and gets the data back
+    // once the storage is dropped
+    pool: Option<Arc<BufferPool>>,
+}
+
+impl<E: DType> Storage<E> {
+    ///
+    /// Creates storage that isn't counted in the device's memory stats or recycled, for temporary copies
+    ///
+    pub fn new(data: Vec<E>) -> Self {
+        Self { data, pool: None }
+    }
+
+    pub fn pooled(data: Vec<E>, pool: &Arc<BufferPool>) -> Self {
+        let storage = Self { data, pool: Some(pool.clone()) };
+        pool.memory.allocate(storage.bytes());
+
+        storage
+    }
+
+    ///
+    /// Takes the elements out, which stops them being counted
+    ///
+    pub fn into_data(mut self) -> Vec<E> {
+        if let Some(pool) = self.pool.take() {
+            pool.memory.free(self.bytes());
+        }
+
+        std::mem::take(&mut self.data)
+    }
+
+    fn bytes(&self) -> usize {
+        self.data.len() * std::mem::size_of::<E>()
+    }
+}
+
+impl<E: DType> Clone for Storage<E> {
+    fn clone(&self) -> Self {
+        match &self.pool {
+            Some(pool) => {
+                let mut data = pool.take(self.data.len());
+                data.extend_from_slice(&self.data);
+
+                Self::pooled(data, pool)
+            },
+            None => Self::new(self.data.clone()),
+        }
+    }
 }
 
-impl TensorInner {
-    pub fn new(buffer: Vec<f32>) -> Self {
-        let len = buffer.len();
+impl<E: DType> Drop for Storage<E> {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.memory.free(self.bytes());
+            pool.recycle(std::mem::take(&mut self.data));
+        }
+    }
+}
+
+thread_local! {
+    // Tensors that don't require gradients on this thread for now, whatever their flag says
+    static CONSTANTS: RefCell<HashSet<TensorId>> = RefCell::new(HashSet::new());
+}
+
+///
+/// Runs `f` with the given tensors treated as constants on this thread, leaving them as they were for other threads
+///
+pub (crate) fn with_constants<T>(ids: HashSet<TensorId>, f: impl FnOnce() -> T) -> T {
+    struct Restore(Vec<TensorId>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CONSTANTS.with_borrow_mut(|constants| self.0.iter().for_each(|id| { constants.remove(id); }));
+        }
+    }
+
+    // Only the ids that weren't constants already are taken out again, so calls can nest
+    let added = CONSTANTS.with_borrow_mut(|constants| ids.into_iter().filter(|id| constants.insert(*id)).collect());
+    let _restore = Restore(added);
 
+    f()
+}
+
+///
+/// A function called with a tensor's gradient once backpropagation has finished calculating it
+///
+/// Hooks are shared so they can be called without holding any of the tensor's locks.
+///
+pub type GradientHook<E> = Arc<dyn Fn(&mut [E]) + Send + Sync>;
+
+pub struct TensorInner<E: DType> {
+    id:             TensorId,
+    device:         Device,
+
+    // Swapped for a copy on write if it is shared, so readers holding the old storage are unaffected
+    storage:        RwLock<Arc<Storage<E>>>,
+    layout:         Layout,
+    requires_grad:  AtomicBool,
+    hooks:          Mutex<Vec<GradientHook<E>>>,
+
+    // Set once backpropagation has passed through the op that created the tensor, unless the graph was retained
+    graph_used:     AtomicBool,
+
+    // Empty until the gradient is first written to
+    gradient:       RwLock<Arc<Storage<E>>>,
+}
+
+impl<E: DType> TensorInner<E> {
+    ///
+    /// Creates a tensor that reads its elements from existing storage
+    ///
+    pub fn view(id: TensorId, device: Device, storage: Arc<Storage<E>>, layout: Layout, requires_grad: bool) -> Self {
         Self {
-            buffer: UnsafeCell::new(buffer),
-            gradient: UnsafeCell::new(vec![0.0; len])
+            id,
+            device,
+            storage: RwLock::new(storage),
+            layout,
+            requires_grad: AtomicBool::new(requires_grad),
+            hooks: Mutex::new(vec![]),
+            graph_used: AtomicBool::new(false),
+            gradient: RwLock::new(Arc::new(Storage::new(vec![])))
         }
     }
 
-    pub fn buffer(&self) -> &[f32] {
-        unsafe { &*self.buffer.get() }
+    pub fn storage(&self) -> Arc<Storage<E>> {
+        self.storage.read().unwrap_or_else(PoisonError::into_inner).clone()
     }
 
-    pub fn buffer_mut(&self) -> &mut [f32] {
-        unsafe { &mut *self.buffer.get() }
+    pub fn layout(&self) -> &Layout {
+        &self.layout
     }
 
-    pub fn gradient(&self) -> &[f32] {
-        unsafe { &*self.gradient.get() }
+    ///
+    /// Gets the elements of the tensor in row-major order
+    ///
+    /// This shares the storage if the tensor is contiguous, and gathers a copy otherwise
+    ///
+    pub fn buffer(&self) -> Buffer<E> {
+        let storage = self.storage();
+
+        if self.layout.is_contiguous() {
+            let start = self.layout.offset();
+
+            Buffer::new(storage, start..start + self.layout.size())
+        } else {
+            Buffer::owned(self.layout.offsets().map(|i| storage.data[i]).collect())
+        }
+    }
+
+    pub fn buffer_mut(&self) -> BufferMut<'_, E> {
+        assert!(self.layout.is_contiguous(), "Cannot mutably borrow a non-contiguous tensor");
+
+        let start = self.layout.offset();
+        let storage = self.storage.write().unwrap_or_else(PoisonError::into_inner);
+
+        BufferMut::new(storage, start..start + self.layout.size())
+    }
+
+    pub fn requires_grad(&self) -> bool {
+        self.requires_grad.load(Ordering::Relaxed) && !CONSTANTS.with_borrow(|constants| constants.contains(&self.id))
+    }
+
+    pub fn set_requires_grad(&self, requires_grad: bool) {
+        self.requires_grad.store(requires_grad, Ordering::Relaxed);
+    }
+
+    pub fn is_graph_used(&self) -> bool {
+        self.graph_used.load(Ordering::Relaxed)
+    }
+
+    pub fn mark_graph_used(&self) {
+        self.graph_used.store(true, Ordering::Relaxed);
+    }
+
+    pub fn register_hook(&self, hook: GradientHook<E>) {
+        self.hooks.lock().unwrap_or_else(PoisonError::into_inner).push(hook);
     }
 
-    pub fn gradient_mut(&self) -> &mut [f32] {
-        unsafe { &mut *self.gradient.get() }
+    ///
+    /// Calls the gradient hooks in the order they were registered, letting each one see the changes of the last
+    ///
+    /// Each hook works on a copy of the gradient that is written back afterwards, and no locks are held while it runs,
+    /// so a hook can read the tensor's gradient or register more hooks. Those only run from the next pass onwards.
+    ///
+    pub fn run_hooks(&self) {
+        let hooks = self.hooks.lock().unwrap_or_else(PoisonError::into_inner).clone();
+
+        for hook in hooks {
+            let mut gradient = self.gradient().to_vec();
+            hook(&mut gradient);
+
+            self.gradient_mut().copy_from_slice(&gradient);
+        }
+    }
+
+    ///
+    /// Gets the gradient, which is all zeros if it has not been written to yet
+    ///
+    pub fn gradient(&self) -> Buffer<E> {
+        let gradient = self.gradient.read().unwrap_or_else(PoisonError::into_inner).clone();
+
+        if gradient.data.is_empty() {
+            return Buffer::owned(vec![E::zero(); self.layout.size()]);
+        }
+
+        Buffer::new(gradient, 0..self.layout.size())
+    }
+
+    ///
+    /// Frees the gradient, which reads as zeros again
+    ///
+    pub fn clear_gradient(&self) {
+        *self.gradient.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(Storage::new(vec![]));
+    }
+
+    ///
+    /// Gets the gradient for writing, allocating it as zeros if it has not been used yet
+    ///
+    pub fn gradient_mut(&self) -> BufferMut<'_, E> {
+        let mut gradient = self.gradient.write().unwrap_or_else(PoisonError::into_inner);
+
+        if gradient.data.is_empty() {
+            *gradient = Arc::new(Storage::pooled(self.device.filled_buffer(self.layout.size(), E::zero()), self.device.pool()));
+        }
+
+        BufferMut::new(gradient, 0..self.layout.size())
     }
 }
 
-unsafe impl Sync for TensorInner {}
-unsafe impl Send for TensorInner {}
\ No newline at end of file
+///
+/// Type-erased access to a tensor, letting the device manage tensors of every element type together
+///
+pub trait AnyTensorInner: Send + Sync {
+    fn zero_gradient(&self);
+}
+
+///
+/// The device stops tracking a tensor once nothing refers to it any more, whether that was a `Tensor`, a
+/// `TensorRef`, or the graph of another tensor
+///
+impl<E: DType> Drop for TensorInner<E> {
+    fn drop(&mut self) {
+        self.device.drop_tensor(self.id);
+    }
+}
+
+impl<E: DType> AnyTensorInner for TensorInner<E> {
+    fn zero_gradient(&self) {
+        let mut gradient = self.gradient.write().unwrap_or_else(PoisonError::into_inner);
+
+        if !gradient.data.is_empty() {
+            Arc::make_mut(&mut gradient).data.fill(E::zero());
+        }
+    }
+}
@@ -0,0 +1,143 @@
+///
+/// Describes how the elements of a tensor are laid out in its storage
+///
+/// Element `[i0, i1, ...]` of the tensor lives at `offset + i0 * strides[0] + i1 * strides[1] + ...`
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    dims:    Vec<usize>,
+    strides: Vec<usize>,
+    offset:  usize,
+}
+
+impl Layout {
+    ///
+    /// A row-major layout starting at the beginning of the storage
+    ///
+    pub fn contiguous(dims: Vec<usize>) -> Self {
+        Self {
+            strides: strides(&dims),
+            dims,
+            offset: 0,
+        }
+    }
+
+    pub fn dims(&self) -> &[usize] {
+        &self.dims
+    }
+
+    pub fn strides(&self) -> &[usize] {
+        &self.strides
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    ///
+    /// The number of elements described by the layout
+    ///
+    pub fn size(&self) -> usize {
+        self.dims.iter().product()
+    }
+
+    ///
+    /// Returns true if the elements are stored in row-major order without gaps
+    ///
+    pub fn is_contiguous(&self) -> bool {
+        // Axes of length one can have any stride
+        self.dims.iter()
+            .zip(&self.strides)
+            .zip(strides(&self.dims))
+            .all(|((dim, stride), expected)| *dim == 1 || *stride == expected)
+    }
+
+    ///
+    /// Reinterprets the layout with new dimensions, if it can be done without moving any data
+    ///
+    pub fn reshape(&self, dims: Vec<usize>) -> Option<Layout> {
+        if !self.is_contiguous() {
+            return None;
+        }
+
+        Some(Layout {
+            strides: strides(&dims),
+            dims,
+            offset: self.offset,
+        })
+    }
+
+    ///
+    /// Reorders the axes, so that axis `i` of the result is axis `axes[i]` of this layout
+    ///
+    pub fn permute(&self, axes: &[usize]) -> Layout {
+        Layout {
+            dims: axes.iter().map(|&axis| self.dims[axis]).collect(),
+            strides: axes.iter().map(|&axis| self.strides[axis]).collect(),
+            offset: self.offset,
+        }
+    }
+
+    ///
+    /// Restricts an axis to the range `start..start + len`
+    ///
+    pub fn narrow(&self, axis: usize, start: usize, len: usize) -> Layout {
+        let mut dims = self.dims.clone();
+        dims[axis] = len;
+
+        Layout {
+            dims,
+            strides: self.strides.clone(),
+            offset: self.offset + start * self.strides[axis],
+        }
+    }
+
+    ///
+    /// Iterates over the storage offsets of every element, in row-major order
+    ///
+    pub fn offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        strided_offsets(&self.dims, &self.strides, self.offset)
+    }
+}
+
+///
+/// Computes the row-major strides for a list of dimensions
+///
+pub(crate) fn strides(dims: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; dims.len()];
+
+    for i in (0..dims.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * dims[i + 1];
+    }
+
+    strides
+}
+
+///
+/// Iterates over the buffer offsets of every element in a strided region, in row-major order
+///
+/// Element `[i0, i1, ...]` of the region lives at `offset + i0 * strides[0] + i1 * strides[1] + ...`
+///
+pub(crate) fn strided_offsets<'a>(dims: &'a [usize], strides: &'a [usize], offset: usize) -> impl Iterator<Item = usize> + 'a {
+    let count = dims.iter().product::<usize>();
+    let mut index = vec![0; dims.len()];
+    let mut current = offset;
+
+    (0..count).map(move |_| {
+        let result = current;
+
+        for axis in (0..dims.len()).rev() {
+            index[axis] += 1;
+            current += strides[axis];
+
+            if index[axis] < dims[axis] {
+                break;
+            }
+
+            current -= strides[axis] * dims[axis];
+            index[axis] = 0;
+        }
+
+        result
+    })
+}
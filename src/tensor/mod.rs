@@ -1,56 +1,242 @@
 use std::{marker::PhantomData, sync::Arc};
 
-use crate::{device::Device, tensor_ops};
+use crate::{device::Device, tensor_ops::{self, Transpose}};
 
 use self::{inner::TensorInner, source::TensorSource};
 pub use self::shape::*;
+pub use self::layout::*;
+pub use self::dtype::*;
+pub use self::dyn_shape::*;
 pub use tensor_ref::TensorRef;
+pub use gradients::Gradients;
+pub use backward::Backward;
+pub use buffer::{Buffer, BufferMut};
+pub (crate) use graph::topological_order;
 
 mod shape;
+mod layout;
+mod dtype;
+mod dyn_shape;
 mod tensor_ref;
+mod display;
+mod graph;
+mod gradients;
+mod backward;
+mod buffer;
+mod dot;
 pub (crate) mod inner;
 pub (crate) mod source;
 
+#[cfg(test)]
+mod tests;
+
 ///
 /// A unique identifier for a tensor
 /// 
 /// This is used to link tensors with their gradients
 /// 
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+/// Ids are handed out in creation order, so a tensor's id is always greater than the ids of the tensors it was
+/// computed from.
+/// 
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TensorId (pub (crate) usize);
 
+///
+/// A tensor of any shape and element type, as seen by the ops that consume it
+/// 
+pub trait AnyTensor: Send + Sync {
+    fn id(&self) -> TensorId;
+    fn requires_grad(&self) -> bool;
+    fn shape(&self) -> DynShape;
+    fn dtype(&self) -> &'static str;
+
+    ///
+    /// The name of the op that created the tensor, or `None` for constants
+    /// 
+    fn op_name(&self) -> Option<&'static str>;
+
+    ///
+    /// The tensors this tensor was computed from, or nothing for constants
+    /// 
+    fn inputs(&self) -> Vec<&dyn AnyTensor>;
+
+    ///
+    /// Whether every element is a finite number
+    ///
+    fn values_are_finite(&self) -> bool;
+
+    ///
+    /// Whether every element of the gradient is a finite number
+    ///
+    fn gradient_is_finite(&self) -> bool;
+
+    ///
+    /// Whether a backward pass has already gone through the op that created the tensor
+    /// 
+    fn graph_used(&self) -> bool;
+
+    ///
+    /// Stops the graph being backpropagated through again, without freeing anything
+    /// 
+    fn mark_graph_used(&self);
+
+    ///
+    /// Resets the gradient to zeros
+    /// 
+    fn clear_gradient(&self);
+
+    ///
+    /// Calls the tensor's gradient hooks, once its gradient is final
+    /// 
+    fn run_hooks(&self);
+
+    ///
+    /// Passes this tensor's gradient back to its inputs, without going any further
+    /// 
+    fn propagate(&self);
+
+    ///
+    /// Passes this tensor's gradient back to its inputs as tensors, like `propagate`
+    /// 
+    fn propagate_graph(&self, gradients: &mut Gradients);
+}
+
 ///
 /// Represents a tensor
 /// 
-pub struct Tensor<S: Shape> {
+/// Tensors hold `f32` elements unless another element type is given
+/// 
+/// Views made by `reshape`, `transpose`, `permute`, `narrow` and `detach` share their storage with the tensor they
+/// came from until either of them is written to. Writing through `buffer_mut` copies the storage first if anything
+/// else refers to it, so a write is never seen through a view, or through a `Buffer` taken earlier. Only a tensor
+/// that has its storage to itself is written in place.
+/// 
+pub struct Tensor<S: AnyShape, E: DType = f32> {
     pub (crate) id:     TensorId,
-    pub (crate) inner:  Arc<TensorInner>,
+    pub (crate) inner:  Arc<TensorInner<E>>,
     pub (crate) device: Device,
-    pub (crate) source: TensorSource<S>,
+    pub (crate) source: TensorSource<S, E>,
 
     pub (crate) _shape: PhantomData<S>
 }
 
-impl<S: Shape> Tensor<S> {
+impl<S: Shape, E: DType> Tensor<S, E> {
     ///
     /// Reshapes a tensor into a new shape without changing the data
     /// 
     /// The new shape must have the same number of elements as the original shape
     /// 
-    pub fn reshape<Output: Shape>(&self) -> Tensor<Output> {
+    pub fn reshape<Output: Shape>(&self) -> Tensor<Output, E> {
         assert_eq!(S::SIZE, Output::SIZE);
 
         tensor_ops::reshape(self.clone())
     }
 
+    ///
+    /// Swaps the last two axes of the tensor
+    ///
+    pub fn transpose(&self) -> Tensor<S::Transposed, E> where S: Transpose {
+        tensor_ops::transpose(self.clone())
+    }
+
+    ///
+    /// Reorders the axes of the tensor, so that axis `i` of the output is axis `axes[i]` of this tensor
+    ///
+    pub fn permute<Output: Shape>(&self, axes: &[usize]) -> Tensor<Output, E> {
+        tensor_ops::permute(self.clone(), axes)
+    }
+
+    ///
+    /// Slices the tensor along an axis, starting at `start`
+    ///
+    /// The length of the slice is taken from the output shape
+    ///
+    pub fn narrow<Output: Shape>(&self, axis: usize, start: usize) -> Tensor<Output, E> {
+        tensor_ops::narrow(self.clone(), axis, start)
+    }
+
+    ///
+    /// Gathers the entries at `indices` along an axis
+    ///
+    pub fn index_select<Output: Shape>(&self, axis: usize, indices: &[usize]) -> Tensor<Output, E> {
+        tensor_ops::index_select(self.clone(), axis, indices)
+    }
+
+    ///
+    /// Converts the tensor into a dynamically-shaped tensor with the same data
+    /// 
+    pub fn to_dyn(&self) -> Tensor<Dyn, E> {
+        tensor_ops::reshape_to(self.clone(), S::dims())
+    }
+}
+
+impl<S: AnyShape, E: DType> Tensor<S, E> {
+    ///
+    /// The number of elements in the tensor
+    /// 
     pub fn size(&self) -> usize {
-        S::SIZE
+        self.inner.layout().size()
+    }
+
+    ///
+    /// The length of each axis of the tensor
+    /// 
+    pub fn dims(&self) -> &[usize] {
+        self.inner.layout().dims()
+    }
+
+    ///
+    /// The shape of the tensor, as a runtime value
+    /// 
+    pub fn shape(&self) -> DynShape {
+        DynShape::new(self.dims())
+    }
+
+    ///
+    /// Returns true if the tensor's elements are stored in row-major order without gaps
+    ///
+    pub fn is_contiguous(&self) -> bool {
+        self.inner.layout().is_contiguous()
+    }
+
+    ///
+    /// The name of the op that created the tensor, or `None` for constants
+    ///
+    pub fn op_name(&self) -> Option<&'static str> {
+        match &self.source {
+            TensorSource::Constant => None,
+            TensorSource::Operation(op) => Some(op.name()),
+        }
+    }
+
+    ///
+    /// Returns a tensor with the same data laid out contiguously
+    ///
+    /// Views created by `transpose`, `permute` and `narrow` share storage with the tensor they were created from,
+    /// and may not be contiguous. This copies them into fresh storage, and is free for tensors that already are.
+    ///
+    pub fn contiguous(&self) -> Tensor<S, E> {
+        if self.is_contiguous() {
+            return self.clone();
+        }
+
+        tensor_ops::contiguous(self.clone())
+    }
+
+    ///
+    /// Converts the elements of the tensor to another type
+    /// 
+    /// Gradients flow back through the conversion, so this can be used to compute in a wider type than the
+    /// tensor is stored in
+    /// 
+    pub fn cast<To: DType>(&self) -> Tensor<S, To> {
+        tensor_ops::cast(self.clone())
     }
 
     ///
     /// Converts the tensor into a reference without it's shape data
     /// 
-    pub fn as_ref(&self) -> TensorRef {
+    pub fn as_ref(&self) -> TensorRef<E> {
         TensorRef {
             id: self.id,
             inner: self.inner.clone(),
@@ -58,40 +244,208 @@ impl<S: Shape> Tensor<S> {
     }
 }
 
-impl<S: Shape> Tensor<S> {
+impl<E: DType> Tensor<Dyn, E> {
     ///
-    /// Runs the backpropagation algorithm on the tensor
+    /// Converts a dynamically-shaped tensor into a statically-shaped one
     /// 
-    /// This will calculate the gradients of the tensor with respect to the output of the operation that created it
+    /// Fails if the tensor's runtime shape is not `S`
     /// 
-    pub fn back(&self) {
-        // Fill the gradient buffer with ones
-        self.device.add_to_gradient(self, &vec![1.0; S::SIZE]);
+    pub fn try_to_shape<S: Shape>(&self) -> Result<Tensor<S, E>, ShapeError> {
+        self.shape().check::<S>()?;
+
+        Ok(tensor_ops::reshape_to(self.clone(), S::dims()))
+    }
+
+    ///
+    /// Reshapes a dynamically-shaped tensor without changing the data
+    /// 
+    /// Fails if the new shape does not have the same number of elements
+    /// 
+    pub fn try_reshape(&self, shape: &DynShape) -> Result<Tensor<Dyn, E>, ShapeError> {
+        if shape.size() != self.size() {
+            return Err(ShapeError::Size { expected: self.size(), found: shape.size() });
+        }
+
+        Ok(tensor_ops::reshape_to(self.clone(), shape.dims().to_vec()))
+    }
+}
+
+impl<S: AnyShape, E: DType> Tensor<S, E> {
+    ///
+    /// Whether gradients are calculated for this tensor
+    /// 
+    /// Constants don't require gradients unless asked to, while the output of an op requires gradients if any of
+    /// its inputs do. Layer parameters require gradients until they are frozen.
+    /// 
+    pub fn requires_grad(&self) -> bool {
+        self.inner.requires_grad()
+    }
+
+    pub fn set_requires_grad(&self, requires_grad: bool) {
+        self.inner.set_requires_grad(requires_grad);
+    }
+
+    ///
+    /// Creates a view of the tensor that is cut off from the computation graph
+    /// 
+    /// The view shares the tensor's data, but is a constant that doesn't require gradients, so nothing is
+    /// backpropagated through it. Shared data is copied before it is written to, so later writes to either tensor
+    /// are not seen by the other.
+    /// 
+    pub fn detach(&self) -> Tensor<S, E> {
+        self.device.allocate_view(self.inner.storage(), self.inner.layout().clone(), TensorSource::Constant)
+    }
+
+    ///
+    /// Registers a function to call with the tensor's gradient during backpropagation
+    /// 
+    /// The hook runs once every op that used the tensor has contributed to the gradient, and before the gradient
+    /// is passed any further back, so changes it makes (such as clipping) affect everything upstream.
+    /// 
+    pub fn register_hook(&self, hook: impl Fn(&mut [E]) + Send + Sync + 'static) {
+        self.inner.register_hook(Arc::new(hook));
+    }
+}
+
+impl<S: AnyShape, E: DType> AnyTensor for Tensor<S, E> {
+    fn id(&self) -> TensorId {
+        self.id
+    }
 
-        self.move_backward();
+    fn requires_grad(&self) -> bool {
+        self.inner.requires_grad()
     }
 
-    pub (crate) fn move_backward(&self) {
+    fn shape(&self) -> DynShape {
+        Tensor::shape(self)
+    }
+
+    fn dtype(&self) -> &'static str {
+        E::NAME
+    }
+
+    fn op_name(&self) -> Option<&'static str> {
+        Tensor::op_name(self)
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
         match &self.source {
-            TensorSource::Constant => {
-                // Constants have no gradients
-            }
-            TensorSource::Operation(operation) => {
-                operation.backprop(&self.device, &self);
-            }
+            TensorSource::Constant => vec![],
+            TensorSource::Operation(op) => op.inputs(),
+        }
+    }
+
+    fn values_are_finite(&self) -> bool {
+        self.inner.buffer().iter().all(|v| v.as_f64().is_finite())
+    }
+
+    fn gradient_is_finite(&self) -> bool {
+        self.inner.gradient().iter().all(|g| g.as_f64().is_finite())
+    }
+
+    fn graph_used(&self) -> bool {
+        self.inner.is_graph_used()
+    }
+
+    fn mark_graph_used(&self) {
+        self.inner.mark_graph_used();
+    }
+
+    fn clear_gradient(&self) {
+        self.inner.clear_gradient();
+    }
+
+    fn run_hooks(&self) {
+        self.inner.run_hooks();
+    }
+
+    fn propagate(&self) {
+        if let TensorSource::Operation(op) = &self.source {
+            op.backprop(&self.device, self);
+        }
+    }
+
+    fn propagate_graph(&self, gradients: &mut Gradients) {
+        if let (TensorSource::Operation(op), Some(gradient)) = (&self.source, gradients.get(self)) {
+            op.backprop_graph(self, gradient, gradients);
         }
     }
 }
 
-impl<S: Shape> Drop for Tensor<S> {
-    fn drop(&mut self) {
-        if Arc::strong_count(&self.inner) == 2 {
-            self.device.drop_tensor(self.id);
+impl<S: AnyShape, E: Float> Tensor<S, E> {
+    ///
+    /// Runs the backpropagation algorithm on the tensor
+    /// 
+    /// This will calculate the gradients of the tensor with respect to the output of the operation that created it.
+    /// The graph can't be backpropagated through again afterwards, see `Backward` for how to allow that or
+    /// backpropagate from several tensors.
+    /// 
+    pub fn back(&self) {
+        Backward::new().root(self).run();
+    }
+
+    ///
+    /// Runs the backpropagation algorithm, starting from the given gradient rather than ones
+    /// 
+    /// This is how tensors with more than one element are backpropagated from when they aren't summed into a loss.
+    /// `gradient` holds the gradient of every element in row-major order.
+    /// 
+    pub fn backward_with(&self, gradient: &[E]) {
+        Backward::new().root_with(self, gradient).run();
+    }
+
+    ///
+    /// Backpropagates like `backward_with`, but stops at the leaves of the graph without running their hooks
+    /// 
+    /// This is for ops that build a graph of their own during backpropagation, whose leaves are also inputs of the
+    /// op, and so have their hooks run by the outer backward pass.
+    /// 
+    pub (crate) fn backward_to_leaves(&self, gradient: &[E]) {
+        Backward::new().root_with(self, gradient).stop_at_leaves().run();
+    }
+
+    ///
+    /// Calculates the gradients of the tensor as tensors, instead of into gradient buffers
+    /// 
+    /// Every op's backward pass is built out of differentiable ops, so the gradients are part of the computation
+    /// graph themselves, and can be backpropagated through again to get higher-order gradients. Gradient buffers
+    /// are left untouched and hooks are not run. The gradients of a `custom_op` are the exception, as
+    /// `CustomOp::backward` works on plain elements: they can be calculated, but differentiating them again panics.
+    /// 
+    pub fn gradients(&self) -> Gradients {
+        let ones = self.device.allocate_with_dims(vec![E::one(); self.size()], self.dims().to_vec(), TensorSource::Constant);
+
+        self.gradients_with(ones)
+    }
+
+    ///
+    /// Calculates gradients as tensors like `gradients`, starting from the given gradient rather than ones
+    /// 
+    pub (crate) fn gradients_with(&self, gradient: Tensor<S, E>) -> Gradients {
+        self.gradients_to(gradient, &[])
+    }
+
+    ///
+    /// Calculates gradients as tensors like `gradients_with`, without going past `leaves` into the graph before them
+    /// 
+    pub (crate) fn gradients_to(&self, gradient: Tensor<S, E>, leaves: &[TensorId]) -> Gradients {
+        assert!(self.requires_grad(), "Cannot backpropagate from a tensor that does not require gradients");
+        assert_eq!(gradient.dims(), self.dims(), "Gradient must have the same shape as the tensor");
+
+        let mut gradients = Gradients::default();
+        gradients.add(self, gradient);
+
+        for tensor in graph::topological_order(self) {
+            if tensor.requires_grad() && !leaves.contains(&tensor.id()) {
+                tensor.propagate_graph(&mut gradients);
+            }
         }
+
+        gradients
     }
 }
 
-impl<S: Shape> Clone for Tensor<S> {
+impl<S: AnyShape, E: DType> Clone for Tensor<S, E> {
     fn clone(&self) -> Self {
         Self { id: self.id.clone(), inner: self.inner.clone(), device: self.device.clone(), source: self.source.clone(), _shape: self._shape.clone() }
     }
@@ -2,6 +2,11 @@ pub trait Shape: Sync + Send + 'static {
     const SIZE: usize;
 
     fn last_dim() -> usize;
+
+    ///
+    /// The length of each axis, outermost first
+    ///
+    fn dims() -> Vec<usize>;
 }
 
 #[derive(Clone)]
@@ -13,6 +18,10 @@ impl<const A: usize> Shape for Rank1<A> {
     fn last_dim() -> usize {
         A
     }
+
+    fn dims() -> Vec<usize> {
+        vec![A]
+    }
 }
 
 #[derive(Clone)]
@@ -24,4 +33,54 @@ impl<const A: usize, const B: usize> Shape for Rank2<A, B> {
     fn last_dim() -> usize {
         B
     }
-}
\ No newline at end of file
+
+    fn dims() -> Vec<usize> {
+        vec![A, B]
+    }
+}
+
+#[derive(Clone)]
+pub struct Rank3<const A: usize, const B: usize, const C: usize>;
+
+impl<const A: usize, const B: usize, const C: usize> Shape for Rank3<A, B, C> {
+    const SIZE: usize = A * B * C;
+
+    fn last_dim() -> usize {
+        C
+    }
+
+    fn dims() -> Vec<usize> {
+        vec![A, B, C]
+    }
+}
+
+///
+/// Implemented by every shape a tensor can have, whether its dimensions are known at compile time or not
+///
+pub trait AnyShape: Sync + Send + 'static {
+    ///
+    /// The dimensions of the shape, if they are known at compile time
+    ///
+    fn static_dims() -> Option<Vec<usize>>;
+}
+
+impl<S: Shape> AnyShape for S {
+    fn static_dims() -> Option<Vec<usize>> {
+        Some(S::dims())
+    }
+}
+
+///
+/// A shape that is only known at runtime
+///
+/// Tensors of this shape carry their dimensions with them, and can be checked against a static shape with
+/// `Tensor::try_to_shape`.
+///
+#[derive(Clone)]
+pub struct Dyn;
+
+impl AnyShape for Dyn {
+    fn static_dims() -> Option<Vec<usize>> {
+        None
+    }
+}
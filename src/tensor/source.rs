@@ -2,14 +2,14 @@ use std::sync::Arc;
 
 use crate::tensor_ops::TensorOp;
 
-use super::Shape;
+use super::{AnyShape, DType};
 
-pub enum TensorSource<S: Shape> {
+pub enum TensorSource<S: AnyShape, E: DType> {
     Constant,
-    Operation(Arc<dyn TensorOp<OutputShape = S>>),
+    Operation(Arc<dyn TensorOp<OutputShape = S, Elem = E>>),
 }
 
-impl<S: Shape> Clone for TensorSource<S> {
+impl<S: AnyShape, E: DType> Clone for TensorSource<S, E> {
     fn clone(&self) -> Self {
         match self {
             Self::Constant => Self::Constant,
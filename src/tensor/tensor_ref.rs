@@ -1,16 +1,16 @@
 use std::sync::Arc;
 
-use crate::tensor::{inner::TensorInner, TensorId};
+use crate::tensor::{inner::TensorInner, AnyTensor, Buffer, BufferMut, DType, DynShape, Gradients, TensorId};
 
 ///
 /// A shape-independent reference to a tensor
 /// 
-pub struct TensorRef {
+pub struct TensorRef<E: DType = f32> {
     pub (crate) id:    TensorId,
-    pub (crate) inner: Arc<TensorInner>,
+    pub (crate) inner: Arc<TensorInner<E>>,
 }
 
-impl TensorRef {
+impl<E: DType> TensorRef<E> {
     ///
     /// Returns an identifier for the tensor
     /// 
@@ -19,30 +19,104 @@ impl TensorRef {
     }
 
     ///
-    /// Gets an immutable reference to the tensor buffer
+    /// Whether gradients are calculated for the tensor
     /// 
-    pub fn buffer(&self) -> &[f32] {
+    pub fn requires_grad(&self) -> bool {
+        self.inner.requires_grad()
+    }
+
+    pub fn set_requires_grad(&self, requires_grad: bool) {
+        self.inner.set_requires_grad(requires_grad);
+    }
+
+    ///
+    /// Registers a function to call with the tensor's gradient during backpropagation
+    /// 
+    pub fn register_hook(&self, hook: impl Fn(&mut [E]) + Send + Sync + 'static) {
+        self.inner.register_hook(Arc::new(hook));
+    }
+
+    ///
+    /// Gets the tensor buffer
+    /// 
+    pub fn buffer(&self) -> Buffer<E> {
         self.inner.buffer()
     }
 
     ///
-    /// Gets a mutable reference to the tensor buffer
+    /// Gets mutable access to the tensor buffer, which is held until the returned buffer is dropped
     /// 
-    pub fn buffer_mut(&self) -> &mut [f32] {
+    /// Panics if the tensor is a non-contiguous view. Views that share the tensor's storage keep their old values
+    /// 
+    pub fn buffer_mut(&self) -> BufferMut<'_, E> {
         self.inner.buffer_mut()
     }
 
     ///
-    /// Gets an immutable reference to the tensor gradient buffer
+    /// Gets the tensor gradient buffer
     /// 
-    pub fn gradient(&self) -> &[f32] {
+    pub fn gradient(&self) -> Buffer<E> {
         self.inner.gradient()
     }
 
     ///
-    /// Gets a mutable reference to the tensor gradient buffer
+    /// Gets mutable access to the tensor gradient buffer, which is held until the returned buffer is dropped
     /// 
-    pub fn gradient_mut(&self) -> &mut [f32] {
+    pub fn gradient_mut(&self) -> BufferMut<'_, E> {
         self.inner.gradient_mut()
     }
-}
\ No newline at end of file
+}
+///
+/// A reference is seen by ops as a leaf, as it doesn't know how the tensor was made
+///
+impl<E: DType> AnyTensor for TensorRef<E> {
+    fn id(&self) -> TensorId {
+        self.id
+    }
+
+    fn requires_grad(&self) -> bool {
+        self.inner.requires_grad()
+    }
+
+    fn shape(&self) -> DynShape {
+        DynShape::new(self.inner.layout().dims())
+    }
+
+    fn dtype(&self) -> &'static str {
+        E::NAME
+    }
+
+    fn op_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![]
+    }
+
+    fn values_are_finite(&self) -> bool {
+        self.inner.buffer().iter().all(|v| v.as_f64().is_finite())
+    }
+
+    fn gradient_is_finite(&self) -> bool {
+        self.inner.gradient().iter().all(|g| g.as_f64().is_finite())
+    }
+
+    fn graph_used(&self) -> bool {
+        false
+    }
+
+    fn mark_graph_used(&self) {}
+
+    fn clear_gradient(&self) {
+        self.inner.clear_gradient();
+    }
+
+    fn run_hooks(&self) {
+        self.inner.run_hooks();
+    }
+
+    fn propagate(&self) {}
+
+    fn propagate_graph(&self, _gradients: &mut Gradients) {}
+}
@@ -0,0 +1,219 @@
+use crate::{device::Device, tensor_ops::{matmul, mul, relu, sum, tanh}};
+
+use super::*;
+
+fn device() -> Device {
+    let device = Device::new();
+    device.seed(0);
+
+    device
+}
+
+#[test]
+fn display_nests_axes() {
+    let device = device();
+
+    let matrix = device.arange::<Rank2<2, 3>, f64>(0.0, 0.5);
+    assert_eq!(format!("{matrix}"), "Tensor[2, 3] f64\n[[0.0, 0.5, 1.0],\n [1.5, 2.0, 2.5]]");
+    assert_eq!(format!("{matrix:.2}"), "Tensor[2, 3] f64\n[[0.00, 0.50, 1.00],\n [1.50, 2.00, 2.50]]");
+
+    // Outer axes are separated by a blank line for every axis inside them
+    let cube = device.arange::<Rank3<2, 2, 2>, i64>(0, 1);
+    assert_eq!(format!("{cube}"), "Tensor[2, 2, 2] i64\n[[[0, 1],\n  [2, 3]],\n\n [[4, 5],\n  [6, 7]]]");
+
+    assert_eq!(format!("{}", device.full::<Rank1<1>, f32>(0.5)), "Tensor[1] f32\n[0.5]");
+}
+
+#[test]
+fn display_elides_long_axes() {
+    let device = device();
+
+    // Six entries are shown in full, and anything longer keeps three at each end
+    assert_eq!(format!("{}", device.arange::<Rank1<6>, u8>(0, 1)), "Tensor[6] u8\n[0, 1, 2, 3, 4, 5]");
+    assert_eq!(format!("{}", device.arange::<Rank1<10>, u8>(0, 1)), "Tensor[10] u8\n[0, 1, 2, ..., 7, 8, 9]");
+
+    let matrix = device.arange::<Rank2<8, 7>, i32>(0, 1);
+    let expected = [
+        "Tensor[8, 7] i32",
+        "[[0, 1, 2, ..., 4, 5, 6],",
+        " [7, 8, 9, ..., 11, 12, 13],",
+        " [14, 15, 16, ..., 18, 19, 20],",
+        " ...,",
+        " [35, 36, 37, ..., 39, 40, 41],",
+        " [42, 43, 44, ..., 46, 47, 48],",
+        " [49, 50, 51, ..., 53, 54, 55]]",
+    ];
+
+    assert_eq!(format!("{matrix}"), expected.join("\n"));
+
+    // Views are printed in their own order, not their storage's
+    let transposed = device.arange::<Rank2<2, 3>, i32>(0, 1).transpose();
+    assert_eq!(format!("{transposed}"), "Tensor[3, 2] i32\n[[0, 3],\n [1, 4],\n [2, 5]]");
+}
+
+#[test]
+fn alternate_display_shows_op_and_gradient() {
+    let device = device();
+    let x = device.constant::<Rank1<2>, f32>(&[1.0, -2.0]);
+    x.set_requires_grad(true);
+
+    let y = relu(x.clone());
+    y.back();
+
+    assert_eq!(format!("{y:#}"), "Tensor[2] f32 (TensorRelu)\n[1.0, 0.0]\ngradient:\n[1.0, 1.0]");
+    assert_eq!(format!("{x:#}"), "Tensor[2] f32 (constant)\n[1.0, -2.0]\ngradient:\n[1.0, 0.0]");
+}
+
+#[test]
+fn debug_is_one_line() {
+    let device = device();
+    let x = device.constant::<Rank1<2>, f32>(&[1.0, -2.0]);
+    x.set_requires_grad(true);
+
+    let y = relu(x.clone());
+    y.back();
+
+    assert_eq!(format!("{y:?}"), format!("Tensor {{ id: {}, shape: [2], dtype: f32, op: TensorRelu, data: [1.0, 0.0] }}", y.id.0));
+    assert_eq!(format!("{x:#?}"), format!("Tensor {{ id: {}, shape: [2], dtype: f32, op: constant, data: [1.0, -2.0], gradient: [1.0, 0.0] }}", x.id.0));
+
+    let long = device.arange::<Rank2<7, 2>, u8>(0, 1);
+    assert_eq!(format!("{long:?}"), format!("Tensor {{ id: {}, shape: [7, 2], dtype: u8, op: constant, data: [[0, 1], [2, 3], [4, 5], ..., [8, 9], [10, 11], [12, 13]] }}", long.id.0));
+}
+
+#[test]
+fn requires_grad_propagates() {
+    let device = device();
+    let a = device.ones::<Rank1<3>, f64>();
+    let b = device.ones::<Rank1<3>, f64>();
+
+    // Constants don't require gradients, and nor does anything computed only from them
+    assert!(!a.requires_grad());
+
+    let constant = mul(a.clone(), b.clone());
+    assert!(!constant.requires_grad());
+    assert_eq!(constant.op_name(), None);
+
+    // One input that requires gradients is enough
+    b.set_requires_grad(true);
+
+    let tracked = mul(a.clone(), b.clone());
+    assert!(tracked.requires_grad());
+    assert_eq!(tracked.op_name(), Some("TensorMul"));
+
+    sum(tracked).back();
+
+    assert_eq!(device.get_gradient_buffer(&b), &[1.0; 3]);
+    assert_eq!(device.get_gradient_buffer(&a), &[0.0; 3]);
+}
+
+#[test]
+fn detach_cuts_the_graph() {
+    let device = device();
+    let x = device.constant::<Rank1<3>, f64>(&[1.0, 2.0, 3.0]);
+    x.set_requires_grad(true);
+
+    let hidden = tanh(x.clone());
+    let detached = hidden.detach();
+
+    assert!(!detached.requires_grad());
+    assert_eq!(detached.op_name(), None);
+    assert_eq!(device.get_tensor_buffer(&detached), device.get_tensor_buffer(&hidden));
+
+    // Only the path through `x` itself carries a gradient, so d/dx (c * x) = c
+    sum(mul(detached.clone(), x.clone())).back();
+
+    assert_eq!(device.get_gradient_buffer(&x), device.get_tensor_buffer(&detached));
+}
+
+#[test]
+fn dot_draws_the_graph() {
+    let device = device();
+    let x = device.ones::<Rank2<2, 3>, f32>();
+    let w = device.ones::<Rank2<3, 4>, f32>();
+    w.set_requires_grad(true);
+
+    let product = matmul(x.clone(), w.clone());
+    let y = relu(product.clone());
+    let dot = y.to_dot();
+    let (x, w, product, y) = (x.id().0, w.id().0, product.id().0, y.id().0);
+
+    // One node per tensor, with the op that made it, its shape and its element type
+    assert!(dot.contains(&format!("t{x} [label=\"constant\\n[2, 3] f32\", shape=ellipse];")));
+    assert!(dot.contains(&format!("t{w} [label=\"parameter\\n[3, 4] f32\", shape=ellipse, style=filled];")));
+    assert!(dot.contains(&format!("t{product} [label=\"TensorMatMul\\n[2, 4] f32\"];")));
+    assert!(dot.contains(&format!("t{y} [label=\"TensorRelu\\n[2, 4] f32\"];")));
+    assert_eq!(dot.matches("[label=").count(), 4);
+
+    // And one edge from each input to the op that used it
+    let mut edges = dot.lines().filter(|line| line.contains("->")).map(str::trim).collect::<Vec<_>>();
+    edges.sort();
+
+    let mut expected = [format!("t{x} -> t{product};"), format!("t{w} -> t{product};"), format!("t{product} -> t{y};")];
+    expected.sort();
+
+    assert_eq!(edges, expected);
+    assert!(dot.starts_with("digraph {") && dot.ends_with("}\n"));
+}
+
+#[test]
+fn save_dot_writes_the_graph() {
+    let device = device();
+    let x = device.ones::<Rank1<3>, f64>();
+    x.set_requires_grad(true);
+
+    let y = tanh(x);
+    let path = std::env::temp_dir().join(format!("backprop-graph-{}.dot", std::process::id()));
+
+    y.save_dot(&path).unwrap();
+    let saved = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(saved, y.to_dot());
+}
+
+#[test]
+fn views_keep_their_values_when_written_to() {
+    let device = device();
+    let x = device.constant::<Rank2<2, 3>, f64>(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+    let reshaped = x.reshape::<Rank1<6>>();
+    let transposed = x.transpose();
+    let detached = x.detach();
+    let before = device.get_tensor_buffer(&x);
+
+    x.as_ref().buffer_mut()[0] = 10.0;
+
+    assert_eq!(device.get_tensor_buffer(&x), &[10.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+    // Every view, and the buffer taken before the write, still sees the old values
+    assert_eq!(before, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    assert_eq!(device.get_tensor_buffer(&reshaped), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    assert_eq!(device.get_tensor_buffer(&transposed), &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+    assert_eq!(device.get_tensor_buffer(&detached), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+    // And the same holds the other way round
+    reshaped.as_ref().buffer_mut()[5] = 60.0;
+
+    assert_eq!(device.get_tensor_buffer(&x), &[10.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    assert_eq!(device.get_tensor_buffer(&reshaped), &[1.0, 2.0, 3.0, 4.0, 5.0, 60.0]);
+}
+
+#[test]
+fn unshared_storage_is_written_in_place() {
+    let device = device();
+    let x = device.zeros::<Rank1<4>, f32>();
+    let address = device.get_tensor_buffer(&x).as_ptr();
+    let allocated = device.memory_stats().bytes_allocated;
+
+    x.as_ref().buffer_mut().fill(1.0);
+
+    assert_eq!(device.get_tensor_buffer(&x).as_ptr(), address);
+    assert_eq!(device.memory_stats().bytes_allocated, allocated);
+
+    // Holding a buffer shares the storage, so the next write has to copy it
+    let held = device.get_tensor_buffer(&x);
+    x.as_ref().buffer_mut().fill(2.0);
+
+    assert_ne!(device.get_tensor_buffer(&x).as_ptr(), address);
+    assert_eq!(held, &[1.0; 4]);
+}
@@ -1,6 +1,6 @@
 use std::{ops::Add, sync::Arc};
 
-use crate::{device::Device, tensor::{source::TensorSource, Rank1, Shape, Tensor}};
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, DType, Dyn, Float, Gradients, Rank1, ShapeError, Tensor}};
 
 use super::{DispatchTensorOp, TensorOp};
 
@@ -10,21 +10,43 @@ use super::{DispatchTensorOp, TensorOp};
 /// Each element in the output tensor is the sum of the corresponding elements in the input tensors
 /// 
 #[derive(Clone)]
-pub struct TensorAdd<S: Shape> {
-    pub lhs: Tensor<S>,
-    pub rhs: Tensor<S>,
+pub struct TensorAdd<S: AnyShape, E: DType> {
+    pub lhs: Tensor<S, E>,
+    pub rhs: Tensor<S, E>,
 }
 
-impl<S: Shape> TensorOp for TensorAdd<S> {
+impl<S: AnyShape, E: DType> TensorOp for TensorAdd<S, E> {
     type OutputShape = S;
+    type Elem = E;
 
-    fn backprop(&self, device: &Device, output: &Tensor<S>) {
+    fn backprop(&self, device: &Device, output: &Tensor<S, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.lhs, &self.rhs]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        gradients.add(&self.lhs, gradient.clone());
+        gradients.add(&self.rhs, gradient);
+    }
+}
+
+///
+/// Adds two tensors of the same shape, checking that their dimensions match at runtime
+///
+pub fn add<S: AnyShape, E: DType>(lhs: Tensor<S, E>, rhs: Tensor<S, E>) -> Tensor<S, E> {
+    assert_eq!(lhs.dims(), rhs.dims(), "Cannot add tensors of different shapes");
+
+    lhs.device.clone().dispatch(TensorAdd {
+        lhs,
+        rhs,
+    })
 }
 
-impl<const A: usize> Add for Tensor<Rank1<A>> {
-    type Output = Tensor<Rank1<A>>;
+impl<const A: usize, E: Float> Add for Tensor<Rank1<A>, E> {
+    type Output = Tensor<Rank1<A>, E>;
 
     fn add(self, rhs: Self) -> Self::Output {
         self.device.dispatch(TensorAdd {
@@ -34,25 +56,38 @@ impl<const A: usize> Add for Tensor<Rank1<A>> {
     }
 }
 
-impl<S: Shape> DispatchTensorOp<TensorAdd<S>> for Device {
-    fn dispatch(&self, op: TensorAdd<S>) -> Tensor<S> {
+impl<S: AnyShape, E: DType> DispatchTensorOp<TensorAdd<S, E>> for Device {
+    fn dispatch(&self, op: TensorAdd<S, E>) -> Tensor<S, E> {
         let lhs_buffer = self.get_tensor_buffer(&op.lhs);
         let rhs_buffer = self.get_tensor_buffer(&op.rhs);
 
-        let buffer = lhs_buffer.iter().zip(rhs_buffer.iter()).map(|(a, b)| a + b).collect();
+        let mut buffer = self.filled_buffer(lhs_buffer.len(), E::zero());
+        self.data_kernels().add(&lhs_buffer, &rhs_buffer, &mut buffer);
 
-        return self.allocate_tensor(buffer, TensorSource::Operation(Arc::new(op)));
+        let dims = op.lhs.dims().to_vec();
+
+        return self.allocate_with_dims(buffer, dims, TensorSource::Operation(Arc::new(op)));
     }
 
-    fn back_dispatch(&self, op: &TensorAdd<S>, output: &Tensor<S>) {
+    fn back_dispatch(&self, op: &TensorAdd<S, E>, output: &Tensor<S, E>) {
         let output_gradient = self.get_gradient_buffer(&output);
 
         // grad Ai = grad C
         // grad Bi = grad C
-        self.add_to_gradient(&op.lhs, output_gradient);
-        self.add_to_gradient(&op.rhs, output_gradient);
-
-        op.lhs.move_backward();
-        op.rhs.move_backward();
+        self.add_to_gradient(&op.lhs, &output_gradient);
+        self.add_to_gradient(&op.rhs, &output_gradient);
+    }
+}
+///
+/// Adds two dynamically-shaped tensors, failing if their shapes differ
+///
+pub fn try_add<E: Float>(lhs: Tensor<Dyn, E>, rhs: Tensor<Dyn, E>) -> Result<Tensor<Dyn, E>, ShapeError> {
+    if lhs.dims() != rhs.dims() {
+        return Err(ShapeError::Incompatible { op: "add", lhs: lhs.shape(), rhs: rhs.shape() });
     }
-}
\ No newline at end of file
+
+    Ok(lhs.device.clone().dispatch(TensorAdd {
+        lhs,
+        rhs,
+    }))
+}
@@ -0,0 +1,69 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Rank1, Shape, Tensor}};
+
+use super::{sum, DispatchTensorOp, TensorOp};
+
+///
+/// Repeats a single value into every element of a tensor
+///
+pub fn broadcast<S: Shape, E: Float>(t: Tensor<Rank1<1>, E>) -> Tensor<S, E> {
+    broadcast_to(t, S::dims())
+}
+
+///
+/// Repeats a single value into a tensor with the given dimensions
+///
+pub(crate) fn broadcast_to<S: AnyShape, E: Float>(t: Tensor<Rank1<1>, E>, dims: Vec<usize>) -> Tensor<S, E> {
+    let device = t.device.clone();
+
+    device.dispatch(TensorBroadcast {
+        input: t,
+        dims,
+        _phantom: PhantomData
+    })
+}
+
+pub struct TensorBroadcast<S: AnyShape, E: Float> {
+    pub input: Tensor<Rank1<1>, E>,
+    pub dims: Vec<usize>,
+    _phantom: PhantomData<S>
+}
+
+impl<S: AnyShape, E: Float> TensorOp for TensorBroadcast<S, E> {
+    type OutputShape = S;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<S, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        gradients.add(&self.input, sum(gradient));
+    }
+}
+
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorBroadcast<S, E>> for Device {
+    fn dispatch(&self, op: TensorBroadcast<S, E>) -> Tensor<S, E> {
+        let value = self.get_tensor_buffer(&op.input)[0];
+
+        let mut output = self.filled_buffer(op.dims.iter().product(), E::zero());
+        self.data_kernels().broadcast(value, &mut output);
+        let dims = op.dims.clone();
+
+        self.allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorBroadcast<S, E>, output: &Tensor<S, E>) {
+        // The value was used once for every element, so its gradient is the sum of theirs
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let gradient = self.kernels().sum(&output_gradient);
+
+        self.add_to_gradient(&op.input, &[gradient]);
+    }
+}
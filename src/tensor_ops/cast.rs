@@ -0,0 +1,83 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, DType, Gradients, Tensor}};
+
+use super::{DispatchTensorOp, TensorOp};
+
+///
+/// Converts every element of a tensor to another element type
+///
+/// Gradients are converted back to the input's type on the way backwards. Conversions to integers round toward
+/// zero and saturate, as `DType::from_f64` does, so NaN and out-of-range values never fail. Integers carry no
+/// gradient, so a cast to an integer type gives a constant that cuts the graph
+///
+pub fn cast<S: AnyShape, From: DType, To: DType>(t: Tensor<S, From>) -> Tensor<S, To> {
+    let device = t.device.clone();
+
+    device.dispatch(TensorCast {
+        input: t,
+        _phantom: PhantomData
+    })
+}
+
+pub struct TensorCast<S: AnyShape, From: DType, To: DType> {
+    pub input: Tensor<S, From>,
+    _phantom: PhantomData<To>
+}
+
+impl<S: AnyShape, From: DType, To: DType> TensorOp for TensorCast<S, From, To> {
+    type OutputShape = S;
+    type Elem = To;
+
+    fn backprop(&self, device: &Device, output: &Tensor<S, To>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<S, To>, gradient: Tensor<S, To>, gradients: &mut Gradients) {
+        gradients.add(&self.input, cast(gradient));
+    }
+}
+
+///
+/// Converts a buffer to another element type, by way of `f64`
+///
+fn convert<From: DType, To: DType>(device: &Device, input: &[From]) -> Vec<To> {
+    let mut values = device.filled_buffer(input.len(), 0.0);
+    device.data_kernels().cast_to_f64(input, &mut values);
+
+    let mut output = device.filled_buffer(input.len(), To::zero());
+    device.data_kernels().cast_from_f64(&values, &mut output);
+
+    device.recycle_buffer(values);
+
+    output
+}
+
+impl<S: AnyShape, From: DType, To: DType> DispatchTensorOp<TensorCast<S, From, To>> for Device {
+    fn dispatch(&self, op: TensorCast<S, From, To>) -> Tensor<S, To> {
+        let input = self.get_tensor_buffer(&op.input);
+
+        let output = convert(self, &input);
+
+        let dims = op.input.dims().to_vec();
+
+        if !To::IS_FLOAT {
+            return self.allocate_with_dims(output, dims, TensorSource::Constant);
+        }
+
+        self.allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorCast<S, From, To>, output: &Tensor<S, To>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let gradient = convert(self, &output_gradient);
+
+        self.add_to_gradient(&op.input, &gradient);
+        self.recycle_buffer(gradient);
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor, TensorRef}};
+
+use super::{DispatchTensorOp, TensorOp};
+
+pub type CheckpointFn<I, O, E> = Arc<dyn Fn(Tensor<I, E>) -> Tensor<O, E> + Send + Sync>;
+
+///
+/// Runs `f` without keeping the intermediate tensors it creates, and runs it again during backpropagation to get
+/// them back
+///
+/// This trades compute for memory. `parameters` must list every tensor `f` uses besides `input` that needs a
+/// gradient, such as the parameters of the layers it runs. The device's random number generator is restored before
+/// running `f` again, so random ops like dropout make the same choices both times.
+///
+/// The first run is under `no_grad`, which keeps every op while anomaly detection is on, so checkpointing saves no
+/// memory then.
+///
+pub fn checkpoint<I: AnyShape, O: AnyShape, E: Float>(
+    f: impl Fn(Tensor<I, E>) -> Tensor<O, E> + Send + Sync + 'static,
+    input: Tensor<I, E>,
+    parameters: Vec<TensorRef<E>>
+) -> Tensor<O, E> {
+    let device = input.device.clone();
+
+    // Without a graph there is nothing to save
+    if !device.is_grad_enabled() {
+        return f(input);
+    }
+
+    let rng = device.with_rng(|rng| rng.clone());
+
+    device.dispatch(TensorCheckpoint {
+        input,
+        parameters,
+        forward: Arc::new(f),
+        rng
+    })
+}
+
+pub struct TensorCheckpoint<I: AnyShape, O: AnyShape, E: Float> {
+    pub input: Tensor<I, E>,
+    pub parameters: Vec<TensorRef<E>>,
+    forward: CheckpointFn<I, O, E>,
+    rng: StdRng,
+}
+
+impl<I: AnyShape, O: AnyShape, E: Float> TensorCheckpoint<I, O, E> {
+    ///
+    /// Runs `f` on `input` again, with the random state it first ran with
+    ///
+    fn recompute(&self, input: Tensor<I, E>) -> Tensor<O, E> {
+        let device = &self.input.device;
+
+        assert!(device.is_grad_enabled(), "Checkpointed layers cannot be backpropagated through under no_grad");
+
+        let rng = device.with_rng(|rng| std::mem::replace(rng, self.rng.clone()));
+        let recomputed = (self.forward)(input);
+        device.with_rng(|current| *current = rng);
+
+        recomputed
+    }
+}
+
+impl<I: AnyShape, O: AnyShape, E: Float> TensorOp for TensorCheckpoint<I, O, E> {
+    type OutputShape = O;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<O, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        std::iter::once(&self.input as &dyn AnyTensor)
+            .chain(self.parameters.iter().map(|p| p as &dyn AnyTensor))
+            .collect()
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<O, E>, gradient: Tensor<O, E>, gradients: &mut Gradients) {
+        // Recomputed from the input itself, so the gradients stay connected to it and can be differentiated again
+        let recomputed = self.recompute(self.input.clone());
+
+        if !recomputed.requires_grad() {
+            return;
+        }
+
+        // The outer pass carries on from the input and parameters
+        let leaves = std::iter::once(self.input.id).chain(self.parameters.iter().map(|p| p.id())).collect::<Vec<_>>();
+        let inner = recomputed.gradients_to(gradient, &leaves);
+
+        gradients.merge(&inner, leaves);
+    }
+}
+
+impl<I: AnyShape, O: AnyShape, E: Float> DispatchTensorOp<TensorCheckpoint<I, O, E>> for Device {
+    fn dispatch(&self, op: TensorCheckpoint<I, O, E>) -> Tensor<O, E> {
+        let output = self.no_grad(|| (op.forward)(op.input.clone()));
+
+        // Only the values are kept, the graph that made them is rebuilt during backpropagation
+        let storage = output.inner.storage();
+        let layout = output.inner.layout().clone();
+
+        self.allocate_view(storage, layout, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorCheckpoint<I, O, E>, output: &Tensor<O, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        // Recompute from a copy of the input, so that its gradient can be collected separately
+        let input = op.input.detach();
+        input.set_requires_grad(op.input.requires_grad());
+
+        let recomputed = op.recompute(input.clone());
+
+        if !recomputed.requires_grad() {
+            return;
+        }
+
+        // The parameters are inputs of this op, so the outer backward pass runs their hooks
+        recomputed.backward_to_leaves(&output_gradient);
+
+        if input.requires_grad() {
+            self.add_to_gradient(&op.input, &self.get_gradient_buffer(&input));
+        }
+    }
+}
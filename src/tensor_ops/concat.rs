@@ -0,0 +1,101 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{backend::ConcatDims, device::Device, tensor::{source::TensorSource, AnyTensor, DType, Gradients, Shape, Tensor}};
+
+use super::{narrow, DispatchTensorOp, TensorOp};
+
+///
+/// Joins two tensors along an existing axis
+///
+/// Both tensors must have the same length along every other axis. The output length along `axis` is the sum of the
+/// input lengths, with `lhs` placed first.
+///
+pub fn concat<A: Shape, B: Shape, To: Shape, E: DType>(lhs: Tensor<A, E>, rhs: Tensor<B, E>, axis: usize) -> Tensor<To, E> {
+    let a = A::dims();
+    let b = B::dims();
+    let to = To::dims();
+
+    assert!(axis < a.len(), "Axis {axis} is out of range for a tensor of rank {}", a.len());
+    assert!(a.len() == b.len() && a.len() == to.len(), "Cannot concatenate tensors of different ranks");
+
+    for i in 0..to.len() {
+        if i == axis {
+            assert_eq!(a[i] + b[i], to[i], "Concatenated length does not match the output shape");
+        } else {
+            assert!(a[i] == to[i] && b[i] == to[i], "Concatenated tensors must match outside of the concatenation axis");
+        }
+    }
+
+    let device = lhs.device.clone();
+
+    device.dispatch(TensorConcat {
+        lhs,
+        rhs,
+        axis,
+        _phantom: PhantomData
+    })
+}
+
+pub struct TensorConcat<A: Shape, B: Shape, To: Shape, E: DType> {
+    pub lhs: Tensor<A, E>,
+    pub rhs: Tensor<B, E>,
+    pub axis: usize,
+    _phantom: PhantomData<To>
+}
+
+impl<A: Shape, B: Shape, To: Shape, E: DType> TensorConcat<A, B, To, E> {
+    ///
+    /// The inputs alternate in the output in blocks of all their elements from the concatenation axis on
+    ///
+    fn dims(&self) -> ConcatDims {
+        ConcatDims {
+            lhs: A::dims()[self.axis..].iter().product(),
+            rhs: B::dims()[self.axis..].iter().product(),
+        }
+    }
+}
+
+impl<A: Shape, B: Shape, To: Shape, E: DType> TensorOp for TensorConcat<A, B, To, E> {
+    type OutputShape = To;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<To, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.lhs, &self.rhs]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<To, E>, gradient: Tensor<To, E>, gradients: &mut Gradients) {
+        gradients.add(&self.lhs, narrow(gradient.clone(), self.axis, 0));
+        gradients.add(&self.rhs, narrow(gradient, self.axis, A::dims()[self.axis]));
+    }
+}
+
+impl<A: Shape, B: Shape, To: Shape, E: DType> DispatchTensorOp<TensorConcat<A, B, To, E>> for Device {
+    fn dispatch(&self, op: TensorConcat<A, B, To, E>) -> Tensor<To, E> {
+        let lhs = self.get_tensor_buffer(&op.lhs);
+        let rhs = self.get_tensor_buffer(&op.rhs);
+
+        let mut output = self.filled_buffer(To::SIZE, E::zero());
+        self.data_kernels().concat(&lhs, &rhs, &mut output, op.dims());
+
+        self.allocate_tensor(output, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorConcat<A, B, To, E>, output: &Tensor<To, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let mut lhs_gradient = self.filled_buffer(A::SIZE, E::zero());
+        let mut rhs_gradient = self.filled_buffer(B::SIZE, E::zero());
+
+        self.data_kernels().concat_gradients(&output_gradient, &mut lhs_gradient, &mut rhs_gradient, op.dims());
+
+        self.add_to_gradient(&op.lhs, &lhs_gradient);
+        self.add_to_gradient(&op.rhs, &rhs_gradient);
+
+        self.recycle_buffer(lhs_gradient);
+        self.recycle_buffer(rhs_gradient);
+    }
+}
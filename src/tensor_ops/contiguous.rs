@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, DType, Gradients, Tensor}};
+
+use super::{DispatchTensorOp, TensorOp};
+
+///
+/// Copies a tensor into fresh, contiguous storage
+///
+pub fn contiguous<S: AnyShape, E: DType>(t: Tensor<S, E>) -> Tensor<S, E> {
+    let device = t.device.clone();
+
+    device.dispatch(TensorContiguous {
+        input: t
+    })
+}
+
+pub struct TensorContiguous<S: AnyShape, E: DType> {
+    pub input: Tensor<S, E>,
+}
+
+impl<S: AnyShape, E: DType> TensorOp for TensorContiguous<S, E> {
+    type OutputShape = S;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        gradients.add(&self.input, gradient);
+    }
+}
+
+impl<S: AnyShape, E: DType> DispatchTensorOp<TensorContiguous<S, E>> for Device {
+    fn dispatch(&self, op: TensorContiguous<S, E>) -> Tensor<S, E> {
+        let layout = op.input.inner.layout();
+
+        let mut buffer = self.filled_buffer(layout.size(), E::zero());
+        self.data_kernels().gather(&op.input.inner.storage().data, layout, &mut buffer);
+
+        let dims = layout.dims().to_vec();
+
+        self.allocate_with_dims(buffer, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorContiguous<S, E>, output: &Tensor<S, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+        self.add_to_gradient(&op.input, &output_gradient);
+    }
+}
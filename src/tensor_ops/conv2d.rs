@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{device::Device, tensor::{source::TensorSource, Rank2, Tensor}};
+use crate::{backend::Conv2dDims, device::Device, tensor::{source::TensorSource, AnyTensor, Float, Gradients, Rank2, Tensor}};
 
 use super::{DispatchTensorOp, TensorOp};
 
@@ -8,11 +8,12 @@ pub fn conv2d<
     const I1: usize,
     const I2: usize,
     const K1: usize,
-    const K2: usize
+    const K2: usize,
+    E: Float
 >(
-    input: Tensor<Rank2<I1, I2>>,
-    kernel: Tensor<Rank2<K1, K2>>
-) -> Tensor<Rank2<I1, I2>> {
+    input: Tensor<Rank2<I1, I2>, E>,
+    kernel: Tensor<Rank2<K1, K2>, E>
+) -> Tensor<Rank2<I1, I2>, E> {
     input.device.clone().dispatch(TensorConvolve2D {
         input,
         kernel
@@ -23,82 +24,266 @@ struct TensorConvolve2D<
     const I1: usize,
     const I2: usize,
     const K1: usize,
-    const K2: usize>
+    const K2: usize,
+    E: Float>
 {
-    input: Tensor<Rank2<I1, I2>>,
-    kernel: Tensor<Rank2<K1, K2>>,
+    input: Tensor<Rank2<I1, I2>, E>,
+    kernel: Tensor<Rank2<K1, K2>, E>,
 }
 
 impl<
     const I1: usize,
     const I2: usize,
     const K1: usize,
-    const K2: usize
-> TensorOp for TensorConvolve2D<I1, I2, K1, K2> {
+    const K2: usize,
+    E: Float
+> TensorOp for TensorConvolve2D<I1, I2, K1, K2, E> {
     type OutputShape = Rank2<I1, I2>;
+    type Elem = E;
 
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>) {
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
         device.back_dispatch(self, output)
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input, &self.kernel]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank2<I1, I2>, E>, gradient: Tensor<Rank2<I1, I2>, E>, gradients: &mut Gradients) {
+        gradients.add(&self.input, conv2d_input_gradient(gradient.clone(), self.kernel.clone()));
+        gradients.add(&self.kernel, conv2d_kernel_gradient(self.input.clone(), gradient));
+    }
 }
 
 impl<
     const I1: usize,
     const I2: usize,
     const K1: usize,
-    const K2: usize
-> DispatchTensorOp<TensorConvolve2D<I1, I2, K1, K2>> for Device {
-    fn dispatch(&self, op: TensorConvolve2D<I1, I2, K1, K2>) -> Tensor<Rank2<I1, I2>> {
+    const K2: usize,
+    E: Float
+> DispatchTensorOp<TensorConvolve2D<I1, I2, K1, K2, E>> for Device {
+    fn dispatch(&self, op: TensorConvolve2D<I1, I2, K1, K2, E>) -> Tensor<Rank2<I1, I2>, E> {
         let input_buffer = self.get_tensor_buffer(&op.input);
         let kernel_buffer = self.get_tensor_buffer(&op.kernel);
 
-        let mut output_buffer = vec![0.0; I1 * I2];
+        let mut output_buffer = self.filled_buffer(I1 * I2, E::zero());
 
-        let x_off = K1 - 1;
-        let y_off = K2 - 1;
+        self.kernels().conv2d(&input_buffer, &kernel_buffer, &mut output_buffer, Conv2dDims { input: (I1, I2), kernel: (K1, K2) });
 
-        for i in 0..(I1 - x_off) {
-            for j in 0..(I2 - y_off) {
-                for k in 0..K1 {
-                    for l in 0..K2 {
-                        output_buffer[i * I2 + j] += input_buffer[(i + k) * I2 + (j + l)] * kernel_buffer[k * K2 + l];
-                    }
-                }
-            }
-        }
-        
         self.allocate_tensor(output_buffer, TensorSource::Operation(Arc::new(op)))
     }
 
-    fn back_dispatch(&self, op: &TensorConvolve2D<I1, I2, K1, K2>, output: &Tensor<Rank2<I1, I2>>) {
+    fn back_dispatch(&self, op: &TensorConvolve2D<I1, I2, K1, K2, E>, output: &Tensor<Rank2<I1, I2>, E>) {
         let input_buffer = self.get_tensor_buffer(&op.input);
         let kernel_buffer = self.get_tensor_buffer(&op.kernel);
         
         let output_gradient = self.get_gradient_buffer(output);
 
-        let mut kernel_gradient = vec![0.0; K1 * K2];
-        let mut input_gradient = vec![0.0; I1 * I2];
+        let mut kernel_gradient = self.filled_buffer(K1 * K2, E::zero());
+        let mut input_gradient = self.filled_buffer(I1 * I2, E::zero());
 
-        let x_off = K1 - 1;
-        let y_off = K2 - 1;
+        let dims = Conv2dDims { input: (I1, I2), kernel: (K1, K2) };
 
-        // todo: do the math for this
-        for i in 0..K1 {
-            for j in 0..K2 {
-                for k in 0..(I1 - x_off) {
-                    for l in 0..(I2 - y_off) {
-                        kernel_gradient[i * K2 + j] += input_buffer[(k + i) * I2 + (l + j)] * output_gradient[k * I2 + l];
-                        input_gradient[(k + i) * I2 + (l + j)] += kernel_buffer[i * K2 + j] * output_gradient[k * I2 + l];
-                    }
-                }
-            }
-        }
+        self.kernels().conv2d_kernel_gradient(&input_buffer, &output_gradient, &mut kernel_gradient, dims);
+        self.kernels().conv2d_input_gradient(&kernel_buffer, &output_gradient, &mut input_gradient, dims);
 
         self.add_to_gradient(&op.kernel, &kernel_gradient);
         self.add_to_gradient(&op.input, &input_gradient);
-        
-        op.input.move_backward();
-        op.kernel.move_backward();
+
+        self.recycle_buffer(kernel_gradient);
+        self.recycle_buffer(input_gradient);
+    }
+}
+
+///
+/// The gradient a convolution passes back to its input, given the gradient of its output
+///
+/// The convolution is linear in its input and in its kernel, so this and `conv2d_kernel_gradient` are linear in
+/// both of their arguments too. Their own gradients are convolutions of one another, which is what lets `conv2d` be
+/// differentiated any number of times.
+///
+fn conv2d_input_gradient<
+    const I1: usize,
+    const I2: usize,
+    const K1: usize,
+    const K2: usize,
+    E: Float
+>(
+    output_gradient: Tensor<Rank2<I1, I2>, E>,
+    kernel: Tensor<Rank2<K1, K2>, E>
+) -> Tensor<Rank2<I1, I2>, E> {
+    output_gradient.device.clone().dispatch(TensorConv2dInputGradient {
+        output_gradient,
+        kernel
+    })
+}
+
+///
+/// The gradient a convolution passes back to its kernel, given its input and the gradient of its output
+///
+fn conv2d_kernel_gradient<
+    const I1: usize,
+    const I2: usize,
+    const K1: usize,
+    const K2: usize,
+    E: Float
+>(
+    input: Tensor<Rank2<I1, I2>, E>,
+    output_gradient: Tensor<Rank2<I1, I2>, E>
+) -> Tensor<Rank2<K1, K2>, E> {
+    input.device.clone().dispatch(TensorConv2dKernelGradient {
+        input,
+        output_gradient
+    })
+}
+
+struct TensorConv2dInputGradient<
+    const I1: usize,
+    const I2: usize,
+    const K1: usize,
+    const K2: usize,
+    E: Float>
+{
+    output_gradient: Tensor<Rank2<I1, I2>, E>,
+    kernel: Tensor<Rank2<K1, K2>, E>,
+}
+
+impl<
+    const I1: usize,
+    const I2: usize,
+    const K1: usize,
+    const K2: usize,
+    E: Float
+> TensorOp for TensorConv2dInputGradient<I1, I2, K1, K2, E> {
+    type OutputShape = Rank2<I1, I2>;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output)
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.output_gradient, &self.kernel]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank2<I1, I2>, E>, gradient: Tensor<Rank2<I1, I2>, E>, gradients: &mut Gradients) {
+        gradients.add(&self.output_gradient, conv2d(gradient.clone(), self.kernel.clone()));
+        gradients.add(&self.kernel, conv2d_kernel_gradient(gradient, self.output_gradient.clone()));
+    }
+}
+
+impl<
+    const I1: usize,
+    const I2: usize,
+    const K1: usize,
+    const K2: usize,
+    E: Float
+> DispatchTensorOp<TensorConv2dInputGradient<I1, I2, K1, K2, E>> for Device {
+    fn dispatch(&self, op: TensorConv2dInputGradient<I1, I2, K1, K2, E>) -> Tensor<Rank2<I1, I2>, E> {
+        let output_gradient = self.get_tensor_buffer(&op.output_gradient);
+        let kernel = self.get_tensor_buffer(&op.kernel);
+
+        let mut input_gradient = self.filled_buffer(I1 * I2, E::zero());
+        self.kernels().conv2d_input_gradient(&kernel, &output_gradient, &mut input_gradient, Conv2dDims { input: (I1, I2), kernel: (K1, K2) });
+
+        self.allocate_tensor(input_gradient, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorConv2dInputGradient<I1, I2, K1, K2, E>, output: &Tensor<Rank2<I1, I2>, E>) {
+        let output_gradient = self.get_tensor_buffer(&op.output_gradient);
+        let kernel = self.get_tensor_buffer(&op.kernel);
+        let gradient = self.get_gradient_buffer(output);
+
+        let dims = Conv2dDims { input: (I1, I2), kernel: (K1, K2) };
+
+        // grad G = conv2d(grad X, K)
+        let mut convolved = self.filled_buffer(I1 * I2, E::zero());
+        self.kernels().conv2d(&gradient, &kernel, &mut convolved, dims);
+
+        // grad K is the kernel gradient of a convolution of grad X, with G as the gradient of its output
+        let mut kernel_gradient = self.filled_buffer(K1 * K2, E::zero());
+        self.kernels().conv2d_kernel_gradient(&gradient, &output_gradient, &mut kernel_gradient, dims);
+
+        self.add_to_gradient(&op.output_gradient, &convolved);
+        self.add_to_gradient(&op.kernel, &kernel_gradient);
+
+        self.recycle_buffer(convolved);
+        self.recycle_buffer(kernel_gradient);
+    }
+}
+
+struct TensorConv2dKernelGradient<
+    const I1: usize,
+    const I2: usize,
+    const K1: usize,
+    const K2: usize,
+    E: Float>
+{
+    input: Tensor<Rank2<I1, I2>, E>,
+    output_gradient: Tensor<Rank2<I1, I2>, E>,
+}
+
+impl<
+    const I1: usize,
+    const I2: usize,
+    const K1: usize,
+    const K2: usize,
+    E: Float
+> TensorOp for TensorConv2dKernelGradient<I1, I2, K1, K2, E> {
+    type OutputShape = Rank2<K1, K2>;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output)
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input, &self.output_gradient]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank2<K1, K2>, E>, gradient: Tensor<Rank2<K1, K2>, E>, gradients: &mut Gradients) {
+        gradients.add(&self.input, conv2d_input_gradient(self.output_gradient.clone(), gradient.clone()));
+        gradients.add(&self.output_gradient, conv2d(self.input.clone(), gradient));
+    }
+}
+
+impl<
+    const I1: usize,
+    const I2: usize,
+    const K1: usize,
+    const K2: usize,
+    E: Float
+> DispatchTensorOp<TensorConv2dKernelGradient<I1, I2, K1, K2, E>> for Device {
+    fn dispatch(&self, op: TensorConv2dKernelGradient<I1, I2, K1, K2, E>) -> Tensor<Rank2<K1, K2>, E> {
+        let input = self.get_tensor_buffer(&op.input);
+        let output_gradient = self.get_tensor_buffer(&op.output_gradient);
+
+        let mut kernel_gradient = self.filled_buffer(K1 * K2, E::zero());
+        self.kernels().conv2d_kernel_gradient(&input, &output_gradient, &mut kernel_gradient, Conv2dDims { input: (I1, I2), kernel: (K1, K2) });
+
+        self.allocate_tensor(kernel_gradient, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorConv2dKernelGradient<I1, I2, K1, K2, E>, output: &Tensor<Rank2<K1, K2>, E>) {
+        let input = self.get_tensor_buffer(&op.input);
+        let output_gradient = self.get_tensor_buffer(&op.output_gradient);
+        let gradient = self.get_gradient_buffer(output);
+
+        let dims = Conv2dDims { input: (I1, I2), kernel: (K1, K2) };
+
+        // grad X is the input gradient of a convolution by grad K, with G as the gradient of its output
+        let mut input_gradient = self.filled_buffer(I1 * I2, E::zero());
+        self.kernels().conv2d_input_gradient(&gradient, &output_gradient, &mut input_gradient, dims);
+
+        // grad G = conv2d(X, grad K)
+        let mut convolved = self.filled_buffer(I1 * I2, E::zero());
+        self.kernels().conv2d(&input, &gradient, &mut convolved, dims);
+
+        self.add_to_gradient(&op.input, &input_gradient);
+        self.add_to_gradient(&op.output_gradient, &convolved);
+
+        self.recycle_buffer(input_gradient);
+        self.recycle_buffer(convolved);
     }
 }
 
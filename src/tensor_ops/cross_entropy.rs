@@ -1,59 +1,170 @@
 use std::sync::Arc;
 
-use crate::{device::Device, tensor::{source::TensorSource, Rank1, Shape, Tensor}};
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Dyn, Float, Gradients, Index, Rank1, ShapeError, Tensor}};
 
-use super::{DispatchTensorOp, TensorOp};
+use super::{broadcast_to, ln, mul, recip, scale, sum, DispatchTensorOp, TensorOp};
+
+///
+/// Cross entropy loss of probabilities against a target distribution of the same shape, checking that their
+/// dimensions match at runtime
+///
+pub fn cross_entropy_loss<S: AnyShape, E: Float>(a: Tensor<S, E>, targets: Tensor<S, E>) -> Tensor<Rank1<1>, E> {
+    assert_eq!(a.dims(), targets.dims(), "Cannot take the cross entropy of tensors of different shapes");
 
-pub fn cross_entropy_loss<S: Shape>(a: Tensor<S>, targets: Tensor<S>) -> Tensor<Rank1<1>> {
     a.device.clone().dispatch(TensorCrossEntropyLoss {
         a,
         targets
     })
 }
 
+///
+/// Cross entropy loss of two dynamically-shaped tensors, failing if their shapes differ
+///
+pub fn try_cross_entropy_loss<E: Float>(a: Tensor<Dyn, E>, targets: Tensor<Dyn, E>) -> Result<Tensor<Rank1<1>, E>, ShapeError> {
+    if a.dims() != targets.dims() {
+        return Err(ShapeError::Incompatible { op: "take the cross entropy of", lhs: a.shape(), rhs: targets.shape() });
+    }
+
+    Ok(a.device.clone().dispatch(TensorCrossEntropyLoss {
+        a,
+        targets
+    }))
+}
+
 #[derive(Clone)]
-pub struct TensorCrossEntropyLoss<S: Shape> {
-    pub a: Tensor<S>,
-    pub targets: Tensor<S>,
+pub struct TensorCrossEntropyLoss<S: AnyShape, E: Float> {
+    pub a: Tensor<S, E>,
+    pub targets: Tensor<S, E>,
 }
 
-impl<S: Shape> TensorOp for TensorCrossEntropyLoss<S> {
+impl<S: AnyShape, E: Float> TensorOp for TensorCrossEntropyLoss<S, E> {
     type OutputShape = Rank1<1>;
+    type Elem = E;
     
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>) {
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.a, &self.targets]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank1<1>, E>, gradient: Tensor<Rank1<1>, E>, gradients: &mut Gradients) {
+        // grad A = -(grad z) * target / A and grad target = -(grad z) * ln(A). Unlike `back`, a probability of zero
+        // gives an infinite or NaN gradient rather than being skipped
+        let gradient = broadcast_to::<S, E>(scale(gradient, -E::one()), self.a.dims().to_vec());
+
+        gradients.add(&self.targets, mul(gradient.clone(), ln(self.a.clone())));
+        gradients.add(&self.a, mul(gradient, mul(self.targets.clone(), recip(self.a.clone()))));
+    }
 }
 
-impl<S: Shape> DispatchTensorOp<TensorCrossEntropyLoss<S>> for Device {
-    fn dispatch(&self, op: TensorCrossEntropyLoss<S>) -> Tensor<Rank1<1>> {
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorCrossEntropyLoss<S, E>> for Device {
+    fn dispatch(&self, op: TensorCrossEntropyLoss<S, E>) -> Tensor<Rank1<1>, E> {
         let a = self.get_tensor_buffer(&op.a);
         let targets = self.get_tensor_buffer(&op.targets);
 
-        //println!("a: {:?}", a);
-
-        let buffer = -a.iter().zip(targets.iter()).map(|(a, t)| t * a.ln()).sum::<f32>();
+        let buffer = self.kernels().cross_entropy(&a, &targets);
 
-        return self.allocate_tensor(vec![buffer], TensorSource::Operation(Arc::new(op)));
+        return self.allocate_tensor(self.filled_buffer(1, buffer), TensorSource::Operation(Arc::new(op)));
     }
 
-    fn back_dispatch(&self, op: &TensorCrossEntropyLoss<S>, output: &Tensor<Rank1<1>>) {
+    fn back_dispatch(&self, op: &TensorCrossEntropyLoss<S, E>, output: &Tensor<Rank1<1>, E>) {
         /*
             z = -sum (target * ln(a))
-            dz/da = -sum (target / a)
+            dz/da = -(target / a)
+            dz/dtarget = -ln(a)
          */
-        let output_gradient = self.get_gradient_buffer(&output);
+        let output_gradient = self.get_gradient_buffer(output);
 
         let a = self.get_tensor_buffer(&op.a);
         let targets = self.get_tensor_buffer(&op.targets);
 
-        let a_gradient = a.iter()
-                          .zip(targets)
-                          .map(|(a, target)| if a == &0.0 { 0.0 } else { -output_gradient[0] * target / a })
-                          .collect::<Vec<f32>>();
+        let mut a_gradient = self.filled_buffer(a.len(), E::zero());
+        let mut target_gradient = self.filled_buffer(a.len(), E::zero());
+
+        self.kernels().cross_entropy_gradients(&a, &targets, output_gradient[0], &mut a_gradient, &mut target_gradient);
 
         self.add_to_gradient(&op.a, &a_gradient);
+        self.add_to_gradient(&op.targets, &target_gradient);
+
+        self.recycle_buffer(a_gradient);
+        self.recycle_buffer(target_gradient);
+    }
+}
+///
+/// Cross entropy loss against an integer class label, rather than a one-hot target
+///
+pub fn sparse_cross_entropy_loss<S: AnyShape, E: Float, I: Index>(a: Tensor<S, E>, label: Tensor<Rank1<1>, I>) -> Tensor<Rank1<1>, E> {
+    a.device.clone().dispatch(TensorSparseCrossEntropyLoss {
+        a,
+        label
+    })
+}
+
+pub struct TensorSparseCrossEntropyLoss<S: AnyShape, E: Float, I: Index> {
+    pub a: Tensor<S, E>,
+    pub label: Tensor<Rank1<1>, I>,
+}
+
+impl<S: AnyShape, E: Float, I: Index> TensorSparseCrossEntropyLoss<S, E, I> {
+    fn class(&self) -> usize {
+        let class = self.label.device.get_tensor_buffer(&self.label)[0].to_index();
+
+        assert!(class < self.a.size(), "Label {class} is out of bounds for {} classes", self.a.size());
+
+        class
+    }
+}
+
+impl<S: AnyShape, E: Float, I: Index> TensorOp for TensorSparseCrossEntropyLoss<S, E, I> {
+    type OutputShape = Rank1<1>;
+    type Elem = E;
+    
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.a, &self.label]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank1<1>, E>, gradient: Tensor<Rank1<1>, E>, gradients: &mut Gradients) {
+        // grad a[label] = -(grad z) / a[label], picked out with a one-hot mask so the other elements can be zero
+        let device = &self.a.device;
+
+        let mut mask = device.filled_buffer(self.a.size(), E::zero());
+        mask[self.class()] = E::one();
+
+        let mask = device.allocate_with_dims(mask, self.a.dims().to_vec(), TensorSource::Constant);
+        let probability = sum(mul(self.a.clone(), mask.clone()));
+
+        let scaled = scale(mul(gradient, recip(probability)), -E::one());
+
+        gradients.add(&self.a, mul(broadcast_to(scaled, self.a.dims().to_vec()), mask));
+    }
+}
+
+impl<S: AnyShape, E: Float, I: Index> DispatchTensorOp<TensorSparseCrossEntropyLoss<S, E, I>> for Device {
+    fn dispatch(&self, op: TensorSparseCrossEntropyLoss<S, E, I>) -> Tensor<Rank1<1>, E> {
+        let a = self.get_tensor_buffer(&op.a);
 
-        op.a.move_backward();
+        // z = -ln(a[label])
+        let buffer = self.kernels().sparse_cross_entropy(&a, op.class());
+
+        self.allocate_tensor(self.filled_buffer(1, buffer), TensorSource::Operation(Arc::new(op)))
     }
-}
\ No newline at end of file
+
+    fn back_dispatch(&self, op: &TensorSparseCrossEntropyLoss<S, E, I>, output: &Tensor<Rank1<1>, E>) {
+        // dz/da[label] = -1 / a[label], and zero everywhere else
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let a = self.get_tensor_buffer(&op.a);
+
+        let mut a_gradient = self.filled_buffer(a.len(), E::zero());
+        self.kernels().sparse_cross_entropy_gradient(&a, op.class(), output_gradient[0], &mut a_gradient);
+
+        self.add_to_gradient(&op.a, &a_gradient);
+        self.recycle_buffer(a_gradient);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Dyn, DynShape, Float, Gradients, Tensor}};
+
+use super::{short_type_name, DispatchTensorOp, TensorOp};
+
+///
+/// A differentiable op defined outside the crate
+///
+/// The op works on the elements of its inputs in row-major order, and the crate takes care of the graph, gradient
+/// buffers and backpropagation. Apply it with `custom_op`.
+///
+pub trait CustomOp<E: Float = f32>: Send + Sync + 'static {
+    ///
+    /// Calculates the output elements from the input elements
+    ///
+    fn forward(&self, inputs: &[&[E]]) -> Vec<E>;
+
+    ///
+    /// Calculates the gradient of every input from the gradient of the output
+    ///
+    /// Must return one gradient per input, each with as many elements as that input. As this works on plain elements
+    /// rather than tensors, `Tensor::gradients` can calculate the gradients of a custom op but not differentiate them
+    /// again.
+    ///
+    fn backward(&self, grad_output: &[E], inputs: &[&[E]]) -> Vec<Vec<E>>;
+
+    ///
+    /// The shape of the output, given the shapes of the inputs
+    ///
+    /// Defaults to the shape of the first input, as for elementwise ops.
+    ///
+    fn output_shape(&self, inputs: &[DynShape]) -> DynShape {
+        inputs[0].clone()
+    }
+
+    ///
+    /// A short name for the op, used when printing tensors
+    ///
+    fn name(&self) -> &'static str {
+        short_type_name::<Self>()
+    }
+}
+
+///
+/// Applies a custom op to some tensors
+///
+/// Inputs of any shape can be passed in with `to_dyn()`. The output shape comes from `CustomOp::output_shape`, and
+/// must match `O` if it is a static shape.
+///
+pub fn custom_op<O: AnyShape, E: Float, C: CustomOp<E>>(op: C, inputs: &[Tensor<Dyn, E>]) -> Tensor<O, E> {
+    assert!(!inputs.is_empty(), "Custom ops need at least one input");
+
+    let device = inputs[0].device.clone();
+
+    device.dispatch(TensorCustom {
+        op,
+        inputs: inputs.to_vec(),
+        _phantom: PhantomData
+    })
+}
+
+pub struct TensorCustom<O: AnyShape, E: Float, C: CustomOp<E>> {
+    pub op: C,
+    pub inputs: Vec<Tensor<Dyn, E>>,
+    _phantom: PhantomData<O>
+}
+
+impl<O: AnyShape, E: Float, C: CustomOp<E>> TensorCustom<O, E, C> {
+    ///
+    /// Runs `CustomOp::backward`, checking that it returned a gradient of the right size for every input
+    ///
+    fn input_gradients(&self, device: &Device, output_gradient: &[E]) -> Vec<Vec<E>> {
+        let buffers = self.inputs.iter().map(|t| device.get_tensor_buffer(t)).collect::<Vec<_>>();
+        let slices = buffers.iter().map(|b| b.as_ref()).collect::<Vec<_>>();
+
+        let gradients = self.op.backward(output_gradient, &slices);
+
+        assert_eq!(gradients.len(), self.inputs.len(), "{} returned {} gradients for {} inputs", self.op.name(), gradients.len(), self.inputs.len());
+
+        for (input, gradient) in self.inputs.iter().zip(&gradients) {
+            assert_eq!(gradient.len(), input.size(), "{} returned a gradient of the wrong size", self.op.name());
+        }
+
+        gradients
+    }
+}
+
+impl<O: AnyShape, E: Float, C: CustomOp<E>> TensorOp for TensorCustom<O, E, C> {
+    type OutputShape = O;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<O, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        self.inputs.iter().map(|t| t as &dyn AnyTensor).collect()
+    }
+
+    fn name(&self) -> &'static str {
+        self.op.name()
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<O, E>, gradient: Tensor<O, E>, gradients: &mut Gradients) {
+        let device = gradient.device.clone();
+        let input_gradients = self.input_gradients(&device, &device.get_tensor_buffer(&gradient));
+
+        for (input, input_gradient) in self.inputs.iter().zip(input_gradients) {
+            let source = TensorSource::Operation(Arc::new(TensorCustomGradient {
+                output_gradient: gradient.clone(),
+                inputs: self.inputs.clone(),
+                name: self.op.name()
+            }));
+
+            gradients.add(input, device.allocate_with_dims(input_gradient, input.dims().to_vec(), source));
+        }
+    }
+}
+
+impl<O: AnyShape, E: Float, C: CustomOp<E>> DispatchTensorOp<TensorCustom<O, E, C>> for Device {
+    fn dispatch(&self, op: TensorCustom<O, E, C>) -> Tensor<O, E> {
+        let buffers = op.inputs.iter().map(|t| self.get_tensor_buffer(t)).collect::<Vec<_>>();
+        let slices = buffers.iter().map(|b| b.as_ref()).collect::<Vec<_>>();
+
+        let output = op.op.forward(&slices);
+
+        let shapes = op.inputs.iter().map(|t| t.shape()).collect::<Vec<_>>();
+        let shape = op.op.output_shape(&shapes);
+
+        assert_eq!(output.len(), shape.size(), "{} returned {} elements for an output of shape {shape}", op.op.name(), output.len());
+
+        drop(buffers);
+
+        self.allocate_with_dims(output, shape.dims().to_vec(), TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorCustom<O, E, C>, output: &Tensor<O, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        for (input, gradient) in op.inputs.iter().zip(op.input_gradients(self, &output_gradient)) {
+            self.add_to_gradient(input, &gradient);
+        }
+    }
+}
+
+///
+/// A gradient that `Tensor::gradients` calculated with `CustomOp::backward`
+///
+/// The gradient stays connected to the graph, so that differentiating it again fails loudly instead of treating it
+/// as a constant.
+///
+struct TensorCustomGradient<O: AnyShape, E: Float> {
+    output_gradient: Tensor<O, E>,
+    inputs: Vec<Tensor<Dyn, E>>,
+    name: &'static str,
+}
+
+impl<O: AnyShape, E: Float> TensorOp for TensorCustomGradient<O, E> {
+    type OutputShape = Dyn;
+    type Elem = E;
+
+    fn backprop(&self, _device: &Device, _output: &Tensor<Dyn, E>) {
+        panic!("Gradients of {} cannot be backpropagated through, as CustomOp::backward does not build a graph", self.name);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        std::iter::once(&self.output_gradient as &dyn AnyTensor)
+            .chain(self.inputs.iter().map(|t| t as &dyn AnyTensor))
+            .collect()
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Dyn, E>, _gradient: Tensor<Dyn, E>, _gradients: &mut Gradients) {
+        panic!("Gradients of {} cannot be differentiated, as CustomOp::backward does not build a graph", self.name);
+    }
+}
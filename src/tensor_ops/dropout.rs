@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
+
+use super::{mul, DispatchTensorOp, TensorOp};
+
+///
+/// Randomly zeroes each element with probability `p`, scaling the rest by `1 / (1 - p)` so the expected value is
+/// unchanged
+///
+/// The elements to drop are drawn from the device's random number generator. Dropout should only be applied while
+/// training.
+///
+pub fn dropout<S: AnyShape, E: Float>(t: Tensor<S, E>, p: f64) -> Tensor<S, E> {
+    assert!((0.0..1.0).contains(&p), "Dropout probability must be in [0, 1), got {p}");
+
+    let device = t.device.clone();
+
+    let scale = E::from_f64(1.0 / (1.0 - p));
+    let mask = device.with_rng(|rng| {
+        (0..t.size()).map(|_| if rng.gen_bool(p) { E::zero() } else { scale }).collect()
+    });
+
+    device.dispatch(TensorDropout {
+        input: t,
+        mask
+    })
+}
+
+pub struct TensorDropout<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
+    pub mask: Vec<E>,
+}
+
+impl<S: AnyShape, E: Float> TensorOp for TensorDropout<S, E> {
+    type OutputShape = S;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        let mask = output.device.allocate_with_dims(self.mask.clone(), output.dims().to_vec(), TensorSource::Constant);
+
+        gradients.add(&self.input, mul(gradient, mask));
+    }
+}
+
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorDropout<S, E>> for Device {
+    fn dispatch(&self, op: TensorDropout<S, E>) -> Tensor<S, E> {
+        let input = self.get_tensor_buffer(&op.input);
+
+        let mut output = self.filled_buffer(input.len(), E::zero());
+        self.kernels().zip(&input, &op.mask, &mut output, &|i, m| i * m);
+
+        let dims = op.input.dims().to_vec();
+
+        self.allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorDropout<S, E>, output: &Tensor<S, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let mut nudge = self.filled_buffer(output_gradient.len(), E::zero());
+        self.kernels().zip(&output_gradient, &op.mask, &mut nudge, &|d, m| d * m);
+
+        self.add_to_gradient(&op.input, &nudge);
+        self.recycle_buffer(nudge);
+    }
+}
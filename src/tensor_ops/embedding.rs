@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyTensor, Float, Gradients, Index, Rank1, Rank2, Tensor}};
+
+use super::{index_select_gradient, DispatchTensorOp, TensorOp};
+
+///
+/// Looks up rows of an embedding table
+///
+/// Row `i` of the output is row `indices[i]` of `weights`. Gradients only flow into the table, as the indices
+/// are not differentiable.
+///
+pub fn embedding<
+    const V: usize,
+    const D: usize,
+    const N: usize,
+    E: Float,
+    I: Index
+>(
+    weights: Tensor<Rank2<V, D>, E>,
+    indices: Tensor<Rank1<N>, I>
+) -> Tensor<Rank2<N, D>, E> {
+    let device = weights.device.clone();
+
+    device.dispatch(TensorEmbedding {
+        weights,
+        indices
+    })
+}
+
+pub struct TensorEmbedding<
+    const V: usize,
+    const D: usize,
+    const N: usize,
+    E: Float,
+    I: Index
+> {
+    pub weights: Tensor<Rank2<V, D>, E>,
+    pub indices: Tensor<Rank1<N>, I>,
+}
+
+impl<const V: usize, const D: usize, const N: usize, E: Float, I: Index> TensorEmbedding<V, D, N, E, I> {
+    fn rows(&self) -> Vec<usize> {
+        self.indices.device.get_tensor_buffer(&self.indices)
+            .iter()
+            .map(|i| {
+                let row = i.to_index();
+                assert!(row < V, "Index {row} is out of bounds for an embedding table with {V} rows");
+                row
+            })
+            .collect()
+    }
+}
+
+impl<const V: usize, const D: usize, const N: usize, E: Float, I: Index> TensorOp for TensorEmbedding<V, D, N, E, I> {
+    type OutputShape = Rank2<N, D>;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.weights, &self.indices]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank2<N, D>, E>, gradient: Tensor<Rank2<N, D>, E>, gradients: &mut Gradients) {
+        // A lookup is a selection of rows, so the table's gradient is scattered back the same way
+        gradients.add(&self.weights, index_select_gradient(gradient, 0, &self.rows()));
+    }
+}
+
+impl<const V: usize, const D: usize, const N: usize, E: Float, I: Index> DispatchTensorOp<TensorEmbedding<V, D, N, E, I>> for Device {
+    fn dispatch(&self, op: TensorEmbedding<V, D, N, E, I>) -> Tensor<Rank2<N, D>, E> {
+        let weights = self.get_tensor_buffer(&op.weights);
+
+        let mut output = self.filled_buffer(N * D, E::zero());
+        self.data_kernels().embedding(&weights, &op.rows(), &mut output, D);
+
+        self.allocate_tensor(output, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorEmbedding<V, D, N, E, I>, output: &Tensor<Rank2<N, D>, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let mut gradient = self.filled_buffer(V * D, E::zero());
+        self.data_kernels().embedding_gradient(&output_gradient, &op.rows(), &mut gradient, D);
+
+        self.add_to_gradient(&op.weights, &gradient);
+        self.recycle_buffer(gradient);
+    }
+}
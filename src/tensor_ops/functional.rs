@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use crate::tensor::{inner::with_constants, source::TensorSource, topological_order, AnyShape, Backward, Dyn, Float, Shape, Tensor};
+
+///
+/// Calculates the gradient of a function with a single output, at `x`
+///
+/// `f` is called with a copy of `x` that the gradient is taken with respect to, so `x` itself needn't require
+/// gradients. Gradient buffers are left untouched, including those of any parameters `f` uses, and the returned
+/// gradient is a constant.
+///
+/// The gradient is calculated with an ordinary backward pass, so every op is supported, and hooks on tensors that
+/// `f` computes from `x` are run.
+///
+pub fn grad<I: Shape, O: AnyShape, E: Float>(f: impl Fn(Tensor<I, E>) -> Tensor<O, E>, x: &Tensor<I, E>) -> Tensor<I, E> {
+    let x = leaf(x);
+    let output = f(x.clone());
+
+    assert_eq!(output.size(), 1, "grad needs a function with a single output, use jacobian instead");
+
+    backward_to(&output, &x, None)
+}
+
+///
+/// Calculates the Jacobian of a function at `x`
+///
+/// The Jacobian has the output's dimensions followed by the input's, so that element `[j.., i..]` is the derivative
+/// of output `j` with respect to input `i`. It takes one backward pass per output element, which supports every op
+/// like `grad`. Gradient buffers are left untouched.
+///
+pub fn jacobian<I: Shape, O: AnyShape, E: Float>(f: impl Fn(Tensor<I, E>) -> Tensor<O, E>, x: &Tensor<I, E>) -> Tensor<Dyn, E> {
+    let x = leaf(x);
+    let output = f(x.clone());
+
+    let rows = (0..output.size()).map(|j| backward_to(&output, &x, Some(j))).collect::<Vec<_>>();
+
+    stack_rows(&x, output.dims(), &rows)
+}
+
+///
+/// Calculates the Hessian of a function with a single output at `x`
+///
+/// The Hessian has the input's dimensions twice, so that element `[i.., k..]` is the second derivative with respect
+/// to inputs `i` and `k`. Every op in `f` must support higher-order gradients. Gradient buffers are left untouched.
+///
+pub fn hessian<I: Shape, O: AnyShape, E: Float>(f: impl Fn(Tensor<I, E>) -> Tensor<O, E>, x: &Tensor<I, E>) -> Tensor<Dyn, E> {
+    let x = leaf(x);
+    let output = f(x.clone());
+
+    assert_eq!(output.size(), 1, "hessian needs a function with a single output");
+
+    // The gradient is itself part of the graph, so each of its elements can be differentiated again
+    let gradient = gradient_of(&output, &x, None);
+
+    let rows = (0..gradient.size()).map(|i| gradient_of(&gradient, &x, Some(i))).collect::<Vec<_>>();
+
+    stack_rows(&x, x.dims(), &rows)
+}
+
+///
+/// A view of `x` to differentiate with respect to, separate from any graph `x` is part of
+///
+fn leaf<I: Shape, E: Float>(x: &Tensor<I, E>) -> Tensor<I, E> {
+    assert!(x.device.is_grad_enabled(), "Gradients cannot be calculated under no_grad");
+
+    let x = x.detach();
+    x.set_requires_grad(true);
+
+    x
+}
+
+///
+/// The gradient of `output`, or of only its element `index`, with respect to `x`
+///
+/// Outputs that don't depend on `x` have a gradient of zero.
+///
+fn gradient_of<O: AnyShape, I: Shape, E: Float>(output: &Tensor<O, E>, x: &Tensor<I, E>, index: Option<usize>) -> Tensor<I, E> {
+    let device = &output.device;
+
+    if output.requires_grad() {
+        let seed = (0..output.size()).map(|j| if index.is_none_or(|i| i == j) { E::one() } else { E::zero() }).collect();
+        let seed = device.allocate_with_dims(seed, output.dims().to_vec(), TensorSource::Constant);
+
+        if let Some(gradient) = output.gradients_with(seed).get(x) {
+            return gradient;
+        }
+    }
+
+    device.zeros()
+}
+
+///
+/// The gradient of `output`, or of only its element `index`, with respect to `x`, calculated into gradient buffers
+///
+/// Only the tensors computed from `x` are backpropagated through. Everything else in the graph, such as parameters,
+/// is treated as a constant on this thread for the pass, so their gradient buffers and graphs are left alone. The
+/// graph is kept so the Jacobian can take several passes through it.
+///
+fn backward_to<O: AnyShape, I: Shape, E: Float>(output: &Tensor<O, E>, x: &Tensor<I, E>, index: Option<usize>) -> Tensor<I, E> {
+    let device = &output.device;
+
+    if !output.requires_grad() {
+        return device.zeros();
+    }
+
+    let mut from_x = HashSet::from([x.id]);
+    let mut constants = HashSet::new();
+
+    // Inputs come before the tensors computed from them in creation order
+    for tensor in topological_order(output).into_iter().rev() {
+        if tensor.inputs().iter().any(|input| from_x.contains(&input.id())) {
+            from_x.insert(tensor.id());
+        } else if tensor.id() != x.id {
+            constants.insert(tensor.id());
+        }
+    }
+
+    let seed = (0..output.size()).map(|j| if index.is_none_or(|i| i == j) { E::one() } else { E::zero() }).collect::<Vec<_>>();
+
+    x.inner.clear_gradient();
+
+    with_constants(constants, || Backward::new().root_with(output, &seed).retain_graph(true).run());
+
+    device.allocate_with_dims(device.get_gradient_buffer(x).to_vec(), x.dims().to_vec(), TensorSource::Constant)
+}
+
+///
+/// Lays out one gradient of `x` per output element, in a tensor with the output's dimensions followed by `x`'s
+///
+fn stack_rows<I: Shape, E: Float>(x: &Tensor<I, E>, output_dims: &[usize], rows: &[Tensor<I, E>]) -> Tensor<Dyn, E> {
+    let device = &x.device;
+
+    let data = rows.iter().flat_map(|row| device.get_tensor_buffer(row).into_owned()).collect();
+    let dims = output_dims.iter().chain(x.dims()).cloned().collect();
+
+    device.allocate_with_dims(data, dims, TensorSource::Constant)
+}
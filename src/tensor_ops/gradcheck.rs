@@ -0,0 +1,125 @@
+use std::{error::Error, fmt::Display};
+
+use rand_distr::Uniform;
+
+use crate::tensor::{AnyShape, Float, Tensor, TensorRef};
+
+///
+/// Step size and tolerances for `gradcheck_with`
+///
+/// An analytic gradient `a` matches a numerical gradient `n` if `|a - n| <= atol + rtol * |n|`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradcheckConfig {
+    pub eps: f64,
+    pub atol: f64,
+    pub rtol: f64,
+}
+
+impl Default for GradcheckConfig {
+    fn default() -> Self {
+        Self {
+            eps: 1e-6,
+            atol: 1e-5,
+            rtol: 1e-3
+        }
+    }
+}
+
+///
+/// The first gradient element that did not match its numerical estimate
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradcheckError {
+    pub input: usize,
+    pub index: usize,
+    pub analytic: f64,
+    pub numeric: f64,
+}
+
+impl Display for GradcheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Gradient of input {} at index {} is {}, but finite differences give {}", self.input, self.index, self.analytic, self.numeric)
+    }
+}
+
+impl Error for GradcheckError {}
+
+///
+/// Checks the gradients that `back()` calculates for `inputs` against central finite differences
+///
+/// `f` builds the computation being checked from the inputs, and is called once for the analytic gradients and twice
+/// more for every input element. The inputs are nudged in place between calls, so `f` should capture them rather
+/// than copies of their data, and must be deterministic (seed the device inside `f` if it uses randomness).
+///
+/// The output is reduced to a scalar with random weights, so every output element contributes. Use `f64` elements
+/// for reliable results. `f` may differentiate with `Tensor::gradients`, which checks higher-order gradients.
+///
+/// The inputs require gradients while they are checked, and are left requiring them only if they already did.
+///
+pub fn gradcheck<O: AnyShape, E: Float, F: Float>(inputs: &[TensorRef<E>], f: impl Fn() -> Tensor<O, F>) -> Result<(), GradcheckError> {
+    gradcheck_with(&GradcheckConfig::default(), inputs, f)
+}
+
+///
+/// Checks gradients like `gradcheck`, with a custom step size and tolerances
+///
+pub fn gradcheck_with<O: AnyShape, E: Float, F: Float>(cfg: &GradcheckConfig, inputs: &[TensorRef<E>], f: impl Fn() -> Tensor<O, F>) -> Result<(), GradcheckError> {
+    let required = inputs.iter().map(|input| input.requires_grad()).collect::<Vec<_>>();
+
+    for input in inputs {
+        input.set_requires_grad(true);
+        input.gradient_mut().fill(E::zero());
+    }
+
+    let result = check_gradients(cfg, inputs, f);
+
+    // The inputs only need gradients for the check, so constants go back to being constants
+    for (input, required) in inputs.iter().zip(required) {
+        input.set_requires_grad(required);
+    }
+
+    result
+}
+
+fn check_gradients<O: AnyShape, E: Float, F: Float>(cfg: &GradcheckConfig, inputs: &[TensorRef<E>], f: impl Fn() -> Tensor<O, F>) -> Result<(), GradcheckError> {
+    let output = f();
+    let device = output.device.clone();
+
+    let weights: Vec<F> = device.random_values(output.size(), Uniform::new(-1.0, 1.0));
+
+    output.backward_with(&weights);
+
+    // The graph is still built here, so that `f` can itself call `Tensor::gradients`
+    let weighted_sum = || {
+        let output = f();
+        let buffer = device.get_tensor_buffer(&output);
+
+        buffer.iter().zip(&weights).map(|(o, w)| o.as_f64() * w.as_f64()).sum::<f64>()
+    };
+
+    for (i, input) in inputs.iter().enumerate() {
+        let analytic = input.gradient().to_vec();
+
+        for (index, analytic) in analytic.into_iter().enumerate() {
+            let original = input.buffer_mut()[index];
+
+            input.buffer_mut()[index] = E::from_f64(original.as_f64() + cfg.eps);
+            let plus = weighted_sum();
+
+            input.buffer_mut()[index] = E::from_f64(original.as_f64() - cfg.eps);
+            let minus = weighted_sum();
+
+            input.buffer_mut()[index] = original;
+
+            let analytic = analytic.as_f64();
+            let numeric = (plus - minus) / (2.0 * cfg.eps);
+
+            if (analytic - numeric).abs() > cfg.atol + cfg.rtol * numeric.abs() {
+                return Err(GradcheckError { input: i, index, analytic, numeric });
+            }
+        }
+    }
+
+    Ok(())
+}
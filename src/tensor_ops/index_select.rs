@@ -0,0 +1,164 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{backend::IndexSelectDims, device::Device, tensor::{source::TensorSource, AnyTensor, DType, Gradients, Shape, Tensor}};
+
+use super::{DispatchTensorOp, TensorOp};
+
+///
+/// Gathers entries of a tensor along one axis
+///
+/// Entry `i` of the output along `axis` is entry `indices[i]` of the input. Indices may repeat, in which case
+/// their gradients are summed.
+///
+pub fn index_select<From: Shape, To: Shape, E: DType>(t: Tensor<From, E>, axis: usize, indices: &[usize]) -> Tensor<To, E> {
+    let from = From::dims();
+    let to = To::dims();
+
+    assert!(axis < from.len(), "Axis {axis} is out of range for a tensor of rank {}", from.len());
+    assert_eq!(from.len(), to.len(), "Cannot select into a different rank");
+
+    for (i, (f, t)) in from.iter().zip(&to).enumerate() {
+        if i == axis {
+            assert_eq!(indices.len(), *t, "Number of indices does not match the output shape");
+        } else {
+            assert_eq!(f, t, "Selection can only change the length of the selected axis");
+        }
+    }
+
+    for &index in indices {
+        assert!(index < from[axis], "Index {index} is out of bounds for axis of length {}", from[axis]);
+    }
+
+    let device = t.device.clone();
+
+    device.dispatch(TensorIndexSelect {
+        input: t,
+        axis,
+        indices: indices.to_vec(),
+        _phantom: PhantomData
+    })
+}
+
+pub struct TensorIndexSelect<From: Shape, To: Shape, E: DType> {
+    pub input: Tensor<From, E>,
+    pub axis: usize,
+    pub indices: Vec<usize>,
+    _phantom: PhantomData<To>
+}
+
+impl<From: Shape, To: Shape, E: DType> TensorIndexSelect<From, To, E> {
+    fn dims(&self) -> IndexSelectDims {
+        selected_dims::<From>(self.axis)
+    }
+}
+
+impl<From: Shape, To: Shape, E: DType> TensorOp for TensorIndexSelect<From, To, E> {
+    type OutputShape = To;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<To, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<To, E>, gradient: Tensor<To, E>, gradients: &mut Gradients) {
+        gradients.add(&self.input, index_select_gradient(gradient, self.axis, &self.indices));
+    }
+}
+
+impl<From: Shape, To: Shape, E: DType> DispatchTensorOp<TensorIndexSelect<From, To, E>> for Device {
+    fn dispatch(&self, op: TensorIndexSelect<From, To, E>) -> Tensor<To, E> {
+        let input = self.get_tensor_buffer(&op.input);
+
+        let mut output = self.filled_buffer(To::SIZE, E::zero());
+        self.data_kernels().index_select(&input, &op.indices, &mut output, op.dims());
+
+        self.allocate_tensor(output, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorIndexSelect<From, To, E>, output: &Tensor<To, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let mut gradient = self.filled_buffer(From::SIZE, E::zero());
+        self.data_kernels().index_select_gradient(&output_gradient, &op.indices, &mut gradient, op.dims());
+
+        self.add_to_gradient(&op.input, &gradient);
+        self.recycle_buffer(gradient);
+    }
+}
+
+///
+/// The length of the selected axis of an input of shape `S`, and of the entries along it
+///
+fn selected_dims<S: Shape>(axis: usize) -> IndexSelectDims {
+    let dims = S::dims();
+
+    IndexSelectDims {
+        len: dims[axis],
+        inner: dims[axis + 1..].iter().product(),
+    }
+}
+
+///
+/// The gradient an index selection passes back to its input, which adds entry `i` of the output's gradient to entry
+/// `indices[i]`
+///
+/// Scattering is linear, and its own gradient is the selection it undoes. Embeddings are selections along the first
+/// axis, and share it.
+///
+pub(crate) fn index_select_gradient<From: Shape, To: Shape, E: DType>(output_gradient: Tensor<To, E>, axis: usize, indices: &[usize]) -> Tensor<From, E> {
+    output_gradient.device.clone().dispatch(TensorIndexSelectGradient {
+        output_gradient,
+        axis,
+        indices: indices.to_vec(),
+        _phantom: PhantomData
+    })
+}
+
+struct TensorIndexSelectGradient<From: Shape, To: Shape, E: DType> {
+    output_gradient: Tensor<To, E>,
+    axis: usize,
+    indices: Vec<usize>,
+    _phantom: PhantomData<From>
+}
+
+impl<From: Shape, To: Shape, E: DType> TensorOp for TensorIndexSelectGradient<From, To, E> {
+    type OutputShape = From;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<From, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.output_gradient]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<From, E>, gradient: Tensor<From, E>, gradients: &mut Gradients) {
+        gradients.add(&self.output_gradient, index_select(gradient, self.axis, &self.indices));
+    }
+}
+
+impl<From: Shape, To: Shape, E: DType> DispatchTensorOp<TensorIndexSelectGradient<From, To, E>> for Device {
+    fn dispatch(&self, op: TensorIndexSelectGradient<From, To, E>) -> Tensor<From, E> {
+        let output_gradient = self.get_tensor_buffer(&op.output_gradient);
+
+        let mut scattered = self.filled_buffer(From::SIZE, E::zero());
+        self.data_kernels().index_select_gradient(&output_gradient, &op.indices, &mut scattered, selected_dims::<From>(op.axis));
+
+        self.allocate_tensor(scattered, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorIndexSelectGradient<From, To, E>, output: &Tensor<From, E>) {
+        let gradient = self.get_gradient_buffer(output);
+
+        let mut selected = self.filled_buffer(To::SIZE, E::zero());
+        self.data_kernels().index_select(&gradient, &op.indices, &mut selected, selected_dims::<From>(op.axis));
+
+        self.add_to_gradient(&op.output_gradient, &selected);
+        self.recycle_buffer(selected);
+    }
+}
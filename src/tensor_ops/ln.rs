@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
+
+use super::{mul, recip, DispatchTensorOp, TensorOp};
+
+///
+/// The natural logarithm of every element
+///
+pub fn ln<S: AnyShape, E: Float>(t: Tensor<S, E>) -> Tensor<S, E> {
+    let device = t.device.clone();
+
+    device.dispatch(TensorLn {
+        input: t
+    })
+}
+
+pub struct TensorLn<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
+}
+
+impl<S: AnyShape, E: Float> TensorOp for TensorLn<S, E> {
+    type OutputShape = S;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        // grad A = grad X / A
+        gradients.add(&self.input, mul(gradient, recip(self.input.clone())));
+    }
+}
+
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorLn<S, E>> for Device {
+    fn dispatch(&self, op: TensorLn<S, E>) -> Tensor<S, E> {
+        let input = op.input.device.get_tensor_buffer(&op.input);
+
+        let mut output = self.filled_buffer(input.len(), E::zero());
+        self.kernels().map(&input, &mut output, &|i| i.ln());
+
+        let dims = op.input.dims().to_vec();
+
+        op.input.device.clone().allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorLn<S, E>, output: &Tensor<S, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+        let input = self.get_tensor_buffer(&op.input);
+
+        let mut nudge = self.filled_buffer(input.len(), E::zero());
+        self.kernels().zip(&input, &output_gradient, &mut nudge, &|v, d| d / v);
+
+        op.input.device.add_to_gradient(&op.input, &nudge);
+        self.recycle_buffer(nudge);
+    }
+}
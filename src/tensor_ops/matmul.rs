@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
-use crate::{device::Device, tensor::{source::TensorSource, Rank1, Rank2, Shape, Tensor}};
+use crate::{backend::MatMulDims, device::Device, tensor::{source::TensorSource, AnyTensor, Dyn, Float, Gradients, Rank1, Rank2, Shape, ShapeError, Tensor}};
 
-use super::{DispatchTensorOp, TensorOp};
+use super::{transpose, DispatchTensorOp, TensorOp};
 
-pub fn matmul<A: Shape, B: Shape>(a: Tensor<A>, b: Tensor<B>) -> Tensor<A::MulOutput>
+pub fn matmul<A: Shape, B: Shape, E: Float>(a: Tensor<A, E>, b: Tensor<B, E>) -> Tensor<A::MulOutput, E>
     where A: MatMul<B>
 {
     A::dispatch(a, b)
@@ -13,21 +13,24 @@ pub fn matmul<A: Shape, B: Shape>(a: Tensor<A>, b: Tensor<B>) -> Tensor<A::MulOu
 pub trait MatMul<S: Shape>: Shape + Sized {
     type MulOutput: Shape;
 
-    fn dispatch(a: Tensor<Self>, b: Tensor<S>) -> Tensor<Self::MulOutput>;
+    fn dispatch<E: Float>(a: Tensor<Self, E>, b: Tensor<S, E>) -> Tensor<Self::MulOutput, E>;
 }
 
 impl<const A: usize, const B: usize, const C: usize> MatMul<Rank2<B, C>> for Rank2<A, B> {
     type MulOutput = Rank2<A, C>;
 
-    fn dispatch(_a: Tensor<Self>, _b: Tensor<Rank2<B, C>>) -> Tensor<Self::MulOutput> {
-        todo!()
+    fn dispatch<E: Float>(a: Tensor<Self, E>, b: Tensor<Rank2<B, C>, E>) -> Tensor<Self::MulOutput, E> {
+        a.device.clone().dispatch(TensorMatMul {
+            lhs: a,
+            rhs: b
+        })
     }
 }
 
 impl<const A: usize, const B: usize> MatMul<Rank2<A, B>> for Rank1<A> {
     type MulOutput = Rank1<B>;
 
-    fn dispatch(a: Tensor<Self>, b: Tensor<Rank2<A, B>>) -> Tensor<Self::MulOutput> {
+    fn dispatch<E: Float>(a: Tensor<Self, E>, b: Tensor<Rank2<A, B>, E>) -> Tensor<Self::MulOutput, E> {
         a.device.clone().dispatch(TensorMatMul {
             lhs: a,
             rhs: b
@@ -35,76 +38,218 @@ impl<const A: usize, const B: usize> MatMul<Rank2<A, B>> for Rank1<A> {
     }
 }
 
-pub struct TensorMatMul<A: Shape, B: Shape> where A: MatMul<B> {
-    pub lhs: Tensor<A>,
-    pub rhs: Tensor<B>
+pub struct TensorMatMul<A: Shape, B: Shape, E: Float> where A: MatMul<B> {
+    pub lhs: Tensor<A, E>,
+    pub rhs: Tensor<B, E>
 }
 
-impl<const A: usize, const B: usize> TensorOp for TensorMatMul<Rank1<A>, Rank2<A, B>> {
+impl<const A: usize, const B: usize, E: Float> TensorOp for TensorMatMul<Rank1<A>, Rank2<A, B>, E> {
     type OutputShape = Rank1<B>;
+    type Elem = E;
 
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>) {
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.lhs, &self.rhs]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank1<B>, E>, gradient: Tensor<Rank1<B>, E>, gradients: &mut Gradients) {
+        // grad lhs = grad output * rhs^T
+        // grad rhs = lhs^T * grad output, the outer product of the two vectors
+        let lhs_gradient = matmul(gradient.clone(), transpose(self.rhs.clone()));
+        let rhs_gradient = matmul(self.lhs.reshape::<Rank2<A, 1>>(), gradient.reshape::<Rank2<1, B>>());
+
+        gradients.add(&self.lhs, lhs_gradient);
+        gradients.add(&self.rhs, rhs_gradient);
+    }
 }
 
-impl<const A: usize, const B: usize> DispatchTensorOp<TensorMatMul<Rank1<A>, Rank2<A, B>>> for Device  {
-    fn dispatch(&self, op: TensorMatMul<Rank1<A>, Rank2<A, B>>) -> Tensor<Rank1<B>> {
+impl<const A: usize, const B: usize, E: Float> DispatchTensorOp<TensorMatMul<Rank1<A>, Rank2<A, B>, E>> for Device  {
+    fn dispatch(&self, op: TensorMatMul<Rank1<A>, Rank2<A, B>, E>) -> Tensor<Rank1<B>, E> {
         let lhs = self.get_tensor_buffer(&op.lhs);
         let rhs = self.get_tensor_buffer(&op.rhs);
 
-        let mut buffer = vec![0.0; B];
+        let mut buffer = self.filled_buffer(B, E::zero());
 
-        for i in 0..B {
-            for j in 0..A {
-                buffer[i] += lhs[j] * rhs[(j * B) + i];
-            }
-        }
+        // A vector is a matrix with a single row
+        self.kernels().matmul(&lhs, &rhs, &mut buffer, MatMulDims { m: 1, k: A, n: B });
 
         return op.lhs.device.clone().allocate_tensor(buffer, TensorSource::Operation(Arc::new(op)));
     }
 
-    fn back_dispatch(&self, op: &TensorMatMul<Rank1<A>, Rank2<A, B>>, output: &Tensor<Rank1<B>>) {
+    fn back_dispatch(&self, op: &TensorMatMul<Rank1<A>, Rank2<A, B>, E>, output: &Tensor<Rank1<B>, E>) {
         let output_gradient = self.get_gradient_buffer(&output);
 
         let lhs = self.get_tensor_buffer(&op.lhs);
         let rhs = self.get_tensor_buffer(&op.rhs);
 
-        let mut activation_gradient = vec![0.0f32; lhs.len()];
+        let dims = MatMulDims { m: 1, k: A, n: B };
 
-        //println!("{output_gradient:?}");
+        // grad lhs = grad output * rhs^T
+        // grad rhs = lhs^T * grad output, the outer product of the two vectors
+        let mut activation_gradient = self.filled_buffer(lhs.len(), E::zero());
+        let mut weights_gradient = self.filled_buffer(rhs.len(), E::zero());
 
-        //
-        //       b00 b01
-        //       b10 b11
-        // a0 a1 a0b00 + a1b10  a0b01 + a1b11
-        //
-        for i in 0..A {
-            for j in 0..B {
-                let del_output = output_gradient[j];
-                let weight = rhs[(i * B) + j];
+        self.kernels().matmul_lhs_gradient(&output_gradient, &rhs, &mut activation_gradient, dims);
+        self.kernels().matmul_rhs_gradient(&lhs, &output_gradient, &mut weights_gradient, dims);
 
-                activation_gradient[i] += del_output * weight;
-            }
-        }
+        self.add_to_gradient(&op.lhs, &activation_gradient);
+        self.add_to_gradient(&op.rhs, &weights_gradient);
 
-        //println!("activation: {activation_gradient:?}");
+        self.recycle_buffer(activation_gradient);
+        self.recycle_buffer(weights_gradient);
+    }
+}
+impl<const M: usize, const K: usize, const N: usize, E: Float> TensorOp for TensorMatMul<Rank2<M, K>, Rank2<K, N>, E> {
+    type OutputShape = Rank2<M, N>;
+    type Elem = E;
 
-        let mut weights_gradient = vec![0.0f32; rhs.len()];
-        for i in 0..A {
-            for j in 0..B {
-                let del_output = output_gradient[j];
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output);
+    }
 
-                weights_gradient[(i * B) + j] = del_output * lhs[i];
-            }
-        }
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.lhs, &self.rhs]
+    }
 
-        //println!("weights: {weights_gradient:?}");
+    fn backprop_graph(&self, _output: &Tensor<Rank2<M, N>, E>, gradient: Tensor<Rank2<M, N>, E>, gradients: &mut Gradients) {
+        // grad lhs = grad output * rhs^T
+        // grad rhs = lhs^T * grad output
+        let lhs_gradient = matmul(gradient.clone(), transpose(self.rhs.clone()));
+        let rhs_gradient = matmul(transpose(self.lhs.clone()), gradient);
 
-        self.add_to_gradient(&op.lhs, &activation_gradient);
-        self.add_to_gradient(&op.rhs, &weights_gradient);
+        gradients.add(&self.lhs, lhs_gradient);
+        gradients.add(&self.rhs, rhs_gradient);
+    }
+}
 
-        op.lhs.move_backward();
-        op.rhs.move_backward();
+impl<const M: usize, const K: usize, const N: usize, E: Float> DispatchTensorOp<TensorMatMul<Rank2<M, K>, Rank2<K, N>, E>> for Device {
+    fn dispatch(&self, op: TensorMatMul<Rank2<M, K>, Rank2<K, N>, E>) -> Tensor<Rank2<M, N>, E> {
+        let lhs = self.get_tensor_buffer(&op.lhs);
+        let rhs = self.get_tensor_buffer(&op.rhs);
+
+        let mut buffer = self.filled_buffer(M * N, E::zero());
+
+        self.kernels().matmul(&lhs, &rhs, &mut buffer, MatMulDims { m: M, k: K, n: N });
+
+        self.allocate_tensor(buffer, TensorSource::Operation(Arc::new(op)))
     }
-}
\ No newline at end of file
+
+    fn back_dispatch(&self, op: &TensorMatMul<Rank2<M, K>, Rank2<K, N>, E>, output: &Tensor<Rank2<M, N>, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let lhs = self.get_tensor_buffer(&op.lhs);
+        let rhs = self.get_tensor_buffer(&op.rhs);
+
+        // grad lhs = grad output * rhs^T
+        // grad rhs = lhs^T * grad output
+        let dims = MatMulDims { m: M, k: K, n: N };
+
+        let mut lhs_gradient = self.filled_buffer(M * K, E::zero());
+        let mut rhs_gradient = self.filled_buffer(K * N, E::zero());
+
+        self.kernels().matmul_lhs_gradient(&output_gradient, &rhs, &mut lhs_gradient, dims);
+        self.kernels().matmul_rhs_gradient(&lhs, &output_gradient, &mut rhs_gradient, dims);
+
+        self.add_to_gradient(&op.lhs, &lhs_gradient);
+        self.add_to_gradient(&op.rhs, &rhs_gradient);
+
+        self.recycle_buffer(lhs_gradient);
+        self.recycle_buffer(rhs_gradient);
+    }
+}
+
+///
+/// Multiplies two dynamically-shaped tensors
+///
+/// `lhs` may be a vector of length `k` or an `m x k` matrix, and `rhs` must be a `k x n` matrix
+///
+pub fn try_matmul<E: Float>(lhs: Tensor<Dyn, E>, rhs: Tensor<Dyn, E>) -> Result<Tensor<Dyn, E>, ShapeError> {
+    let incompatible = || ShapeError::Incompatible { op: "multiply", lhs: lhs.shape(), rhs: rhs.shape() };
+
+    let (m, k) = match *lhs.dims() {
+        [k] => (1, k),
+        [m, k] => (m, k),
+        _ => return Err(incompatible()),
+    };
+
+    let n = match *rhs.dims() {
+        [rows, n] if rows == k => n,
+        _ => return Err(incompatible()),
+    };
+
+    let vector = lhs.dims().len() == 1;
+
+    Ok(lhs.device.clone().dispatch(TensorDynMatMul {
+        lhs,
+        rhs,
+        m,
+        k,
+        n,
+        vector
+    }))
+}
+
+pub struct TensorDynMatMul<E: Float> {
+    pub lhs: Tensor<Dyn, E>,
+    pub rhs: Tensor<Dyn, E>,
+    m: usize,
+    k: usize,
+    n: usize,
+    vector: bool,
+}
+
+impl<E: Float> TensorOp for TensorDynMatMul<E> {
+    type OutputShape = Dyn;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Dyn, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.lhs, &self.rhs]
+    }
+}
+
+impl<E: Float> DispatchTensorOp<TensorDynMatMul<E>> for Device {
+    fn dispatch(&self, op: TensorDynMatMul<E>) -> Tensor<Dyn, E> {
+        let lhs = self.get_tensor_buffer(&op.lhs);
+        let rhs = self.get_tensor_buffer(&op.rhs);
+
+        let (m, k, n) = (op.m, op.k, op.n);
+
+        let mut buffer = self.filled_buffer(m * n, E::zero());
+
+        self.kernels().matmul(&lhs, &rhs, &mut buffer, MatMulDims { m, k, n });
+
+        let dims = if op.vector { vec![n] } else { vec![m, n] };
+
+        self.allocate_with_dims(buffer, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorDynMatMul<E>, output: &Tensor<Dyn, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let lhs = self.get_tensor_buffer(&op.lhs);
+        let rhs = self.get_tensor_buffer(&op.rhs);
+
+        let dims = MatMulDims { m: op.m, k: op.k, n: op.n };
+
+        // grad lhs = grad output * rhs^T
+        // grad rhs = lhs^T * grad output
+        let mut lhs_gradient = self.filled_buffer(op.m * op.k, E::zero());
+        let mut rhs_gradient = self.filled_buffer(op.k * op.n, E::zero());
+
+        self.kernels().matmul_lhs_gradient(&output_gradient, &rhs, &mut lhs_gradient, dims);
+        self.kernels().matmul_rhs_gradient(&lhs, &output_gradient, &mut rhs_gradient, dims);
+
+        self.add_to_gradient(&op.lhs, &lhs_gradient);
+        self.add_to_gradient(&op.rhs, &rhs_gradient);
+
+        self.recycle_buffer(lhs_gradient);
+        self.recycle_buffer(rhs_gradient);
+    }
+}
@@ -1,38 +1,117 @@
 pub mod add;
+mod mul;
+mod scale;
+mod sum;
+mod broadcast;
 pub mod mse;
 pub mod matmul;
 mod relu;
 mod softmax;
 mod sigmoid;
 mod tanh;
+mod ln;
+mod recip;
 mod cross_entropy;
 mod conv2d;
 mod reshape;
+mod permute;
+mod narrow;
+mod index_select;
+mod concat;
+mod stack;
+mod contiguous;
+mod cast;
+mod embedding;
+mod dropout;
+mod gradcheck;
+mod functional;
+mod custom;
+mod checkpoint;
 //mod pool;
 
+#[cfg(test)]
+mod tests;
+
 use downcast_rs::{impl_downcast, DowncastSync};
-pub use mse::mse;
-pub use cross_entropy::cross_entropy_loss;
-pub use matmul::matmul;
+pub use add::{add, try_add};
+pub use mul::mul;
+pub use scale::scale;
+pub use sum::sum;
+pub use broadcast::broadcast;
+pub(crate) use broadcast::broadcast_to;
+pub use mse::{mse, try_mse};
+pub use cross_entropy::{cross_entropy_loss, sparse_cross_entropy_loss, try_cross_entropy_loss};
+pub use matmul::{matmul, try_matmul};
 pub use relu::relu;
 pub use softmax::softmax;
 pub use sigmoid::sigmoid;
 pub use tanh::tanh;
+pub use ln::ln;
+pub use recip::recip;
 pub use conv2d::conv2d;
 pub use reshape::reshape;
+pub(crate) use reshape::reshape_to;
+pub use permute::{permute, transpose, Transpose};
+pub use narrow::narrow;
+pub(crate) use narrow::narrow_to;
+pub use index_select::index_select;
+pub(crate) use index_select::index_select_gradient;
+pub use concat::concat;
+pub use stack::stack;
+pub use contiguous::contiguous;
+pub use cast::cast;
+pub use embedding::embedding;
+pub use dropout::dropout;
+pub use custom::{custom_op, CustomOp};
+pub use checkpoint::checkpoint;
+pub use gradcheck::{gradcheck, gradcheck_with, GradcheckConfig, GradcheckError};
+pub use functional::{grad, jacobian, hessian};
 //pub use pool::{maxpool, maxpool2d};
 
-use crate::{device::Device, tensor::{Shape, Tensor}};
+use crate::{device::Device, tensor::{AnyShape, AnyTensor, DType, Gradients, Tensor}};
 
 pub trait TensorOp: DowncastSync {
-    type OutputShape: Shape;
+    type OutputShape: AnyShape;
+    type Elem: DType;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, Self::Elem>);
+
+    ///
+    /// The tensors the op was applied to
+    ///
+    fn inputs(&self) -> Vec<&dyn AnyTensor>;
+
+    ///
+    /// Passes the gradient of the output back to the inputs as tensors, built from differentiable ops
+    ///
+    /// This is what `Tensor::gradients` uses, and what makes higher-order gradients possible. Ops that don't
+    /// implement it can only be backpropagated through with `back`.
+    ///
+    fn backprop_graph(&self, _output: &Tensor<Self::OutputShape, Self::Elem>, _gradient: Tensor<Self::OutputShape, Self::Elem>, _gradients: &mut Gradients) {
+        panic!("{} does not support higher-order gradients", self.name());
+    }
+
+    ///
+    /// A short name for the op, such as `TensorRelu`, used when printing tensors
+    ///
+    fn name(&self) -> &'static str {
+        short_type_name::<Self>()
+    }
+}
+
+///
+/// The name of a type without its path or generic parameters
+///
+pub (crate) fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
 
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>);
+    name.rsplit("::").next().unwrap_or(name)
 }
 
-impl_downcast!(sync TensorOp assoc OutputShape);
+impl_downcast!(sync TensorOp assoc OutputShape, Elem);
 
 pub trait DispatchTensorOp<T: TensorOp> {
-    fn dispatch(&self, op: T) -> Tensor<T::OutputShape>;
-    fn back_dispatch(&self, op: &T, output: &Tensor<T::OutputShape>);
+    fn dispatch(&self, op: T) -> Tensor<T::OutputShape, T::Elem>;
+    fn back_dispatch(&self, op: &T, output: &Tensor<T::OutputShape, T::Elem>);
 }
\ No newline at end of file
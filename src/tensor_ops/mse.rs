@@ -1,41 +1,80 @@
 use std::sync::Arc;
 
-use crate::{device::Device, tensor::{source::TensorSource, Rank1, Shape, Tensor}};
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Dyn, Float, Gradients, Rank1, ShapeError, Tensor}};
 
-use super::{DispatchTensorOp, TensorOp};
+use super::{add, broadcast_to, mul, scale, DispatchTensorOp, TensorOp};
+
+///
+/// The mean of the squared differences between two tensors of the same shape, checking that their dimensions match
+/// at runtime
+///
+pub fn mse<S: AnyShape, E: Float>(a: Tensor<S, E>, targets: Tensor<S, E>) -> Tensor<Rank1<1>, E> {
+    assert_eq!(a.dims(), targets.dims(), "Cannot take the mean squared error of tensors of different shapes");
 
-pub fn mse<S: Shape>(a: Tensor<S>, targets: Tensor<S>) -> Tensor<Rank1<1>> {
     a.device.clone().dispatch(MeanSquaredError {
         a,
         targets
     })
 }
 
+///
+/// The mean squared error of two dynamically-shaped tensors, failing if their shapes differ
+///
+pub fn try_mse<E: Float>(a: Tensor<Dyn, E>, targets: Tensor<Dyn, E>) -> Result<Tensor<Rank1<1>, E>, ShapeError> {
+    if a.dims() != targets.dims() {
+        return Err(ShapeError::Incompatible { op: "take the mean squared error of", lhs: a.shape(), rhs: targets.shape() });
+    }
+
+    Ok(a.device.clone().dispatch(MeanSquaredError {
+        a,
+        targets
+    }))
+}
+
 #[derive(Clone)]
-pub struct MeanSquaredError<S: Shape> {
-    pub a: Tensor<S>,
-    pub targets: Tensor<S>,
+pub struct MeanSquaredError<S: AnyShape, E: Float> {
+    pub a: Tensor<S, E>,
+    pub targets: Tensor<S, E>,
 }
 
-impl<S: Shape> TensorOp for MeanSquaredError<S> {
+impl<S: AnyShape, E: Float> TensorOp for MeanSquaredError<S, E> {
     type OutputShape = Rank1<1>;
+    type Elem = E;
     
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>) {
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.a, &self.targets]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank1<1>, E>, gradient: Tensor<Rank1<1>, E>, gradients: &mut Gradients) {
+        // grad Ai = (grad output) * (2/N) * (Ai - Bi), and grad Bi is its negation
+        let n = E::from_f64(self.a.size() as f64);
+        let two = E::one() + E::one();
+
+        let difference = add(self.a.clone(), scale(self.targets.clone(), -E::one()));
+        let gradient = broadcast_to(scale(gradient, two / n), self.a.dims().to_vec());
+
+        let a_gradient = mul(gradient, difference);
+
+        gradients.add(&self.targets, scale(a_gradient.clone(), -E::one()));
+        gradients.add(&self.a, a_gradient);
+    }
 }
 
-impl<S: Shape> DispatchTensorOp<MeanSquaredError<S>> for Device {
-    fn dispatch(&self, op: MeanSquaredError<S>) -> Tensor<Rank1<1>> {
+impl<S: AnyShape, E: Float> DispatchTensorOp<MeanSquaredError<S, E>> for Device {
+    fn dispatch(&self, op: MeanSquaredError<S, E>) -> Tensor<Rank1<1>, E> {
         let a = self.get_tensor_buffer(&op.a);
         let targets = self.get_tensor_buffer(&op.targets);
 
-        let buffer = a.iter().zip(targets.iter()).map(|(a, b)| (a - b).powi(2)).sum::<f32>() / (S::SIZE as f32);
+        let buffer = self.kernels().mse(&a, &targets);
 
-        return self.allocate_tensor(vec![buffer], TensorSource::Operation(Arc::new(op)));
+        return self.allocate_tensor(self.filled_buffer(1, buffer), TensorSource::Operation(Arc::new(op)));
     }
 
-    fn back_dispatch(&self, op: &MeanSquaredError<S>, output: &Tensor<Rank1<1>>) {
+    fn back_dispatch(&self, op: &MeanSquaredError<S, E>, output: &Tensor<Rank1<1>, E>) {
         // grad Ai = (grad output) * (Ai')
         // output = (1/N) * sum((Ai - Bi)^2)
         // d(output)/d(Ai) = (2/N) * (Ai - Bi)
@@ -46,24 +85,15 @@ impl<S: Shape> DispatchTensorOp<MeanSquaredError<S>> for Device {
         let a = self.get_tensor_buffer(&op.a);
         let targets = self.get_tensor_buffer(&op.targets);
 
-        let n = S::SIZE as f32;
+        let mut a_gradient = self.filled_buffer(a.len(), E::zero());
+        let mut target_gradient = self.filled_buffer(a.len(), E::zero());
 
-        let a_gradient = a.iter()
-                          .zip(targets)
-                          .map(|(a, target)| output_gradient[0] * (2.0 / n) * (a - target))
-                          .collect::<Vec<f32>>();
+        self.kernels().mse_gradients(&a, &targets, output_gradient[0], &mut a_gradient, &mut target_gradient);
 
         self.add_to_gradient(&op.a, &a_gradient);
-
-        let mut target_gradient = a_gradient;
-
-        for target_gradient in target_gradient.iter_mut() {
-            *target_gradient *= -1.0;
-        }
-
         self.add_to_gradient(&op.targets, &target_gradient);
 
-        op.a.move_backward();
-        op.targets.move_backward();
+        self.recycle_buffer(a_gradient);
+        self.recycle_buffer(target_gradient);
     }
 }
\ No newline at end of file
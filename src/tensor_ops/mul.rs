@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
+
+use super::{DispatchTensorOp, TensorOp};
+
+///
+/// Multiplies two tensors of the same shape
+///
+/// Each element in the output tensor is the product of the corresponding elements in the input tensors
+///
+pub fn mul<S: AnyShape, E: Float>(lhs: Tensor<S, E>, rhs: Tensor<S, E>) -> Tensor<S, E> {
+    assert_eq!(lhs.dims(), rhs.dims(), "Cannot multiply tensors of different shapes");
+
+    lhs.device.clone().dispatch(TensorMul {
+        lhs,
+        rhs,
+    })
+}
+
+pub struct TensorMul<S: AnyShape, E: Float> {
+    pub lhs: Tensor<S, E>,
+    pub rhs: Tensor<S, E>,
+}
+
+impl<S: AnyShape, E: Float> TensorOp for TensorMul<S, E> {
+    type OutputShape = S;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<S, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.lhs, &self.rhs]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        gradients.add(&self.lhs, mul(gradient.clone(), self.rhs.clone()));
+        gradients.add(&self.rhs, mul(gradient, self.lhs.clone()));
+    }
+}
+
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorMul<S, E>> for Device {
+    fn dispatch(&self, op: TensorMul<S, E>) -> Tensor<S, E> {
+        let lhs_buffer = self.get_tensor_buffer(&op.lhs);
+        let rhs_buffer = self.get_tensor_buffer(&op.rhs);
+
+        let mut buffer = self.filled_buffer(lhs_buffer.len(), E::zero());
+        self.kernels().zip(&lhs_buffer, &rhs_buffer, &mut buffer, &|a, b| a * b);
+
+        let dims = op.lhs.dims().to_vec();
+
+        self.allocate_with_dims(buffer, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorMul<S, E>, output: &Tensor<S, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let lhs_buffer = self.get_tensor_buffer(&op.lhs);
+        let rhs_buffer = self.get_tensor_buffer(&op.rhs);
+
+        // grad Ai = grad Ci * Bi
+        // grad Bi = grad Ci * Ai
+        let mut lhs_gradient = self.filled_buffer(output_gradient.len(), E::zero());
+        let mut rhs_gradient = self.filled_buffer(output_gradient.len(), E::zero());
+
+        self.kernels().zip(&output_gradient, &rhs_buffer, &mut lhs_gradient, &|g, b| g * b);
+        self.kernels().zip(&output_gradient, &lhs_buffer, &mut rhs_gradient, &|g, a| g * a);
+
+        self.add_to_gradient(&op.lhs, &lhs_gradient);
+        self.add_to_gradient(&op.rhs, &rhs_gradient);
+
+        self.recycle_buffer(lhs_gradient);
+        self.recycle_buffer(rhs_gradient);
+    }
+}
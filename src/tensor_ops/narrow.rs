@@ -0,0 +1,175 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, DType, Gradients, Layout, Shape, Tensor}};
+
+use super::{DispatchTensorOp, TensorOp};
+
+///
+/// Takes a contiguous slice of a tensor along one axis
+///
+/// The output keeps every other axis as is. The length of the slice along `axis` is taken from the output shape,
+/// starting at index `start` of the input. The output is a view of the input's storage.
+///
+pub fn narrow<From: Shape, To: Shape, E: DType>(t: Tensor<From, E>, axis: usize, start: usize) -> Tensor<To, E> {
+    let from = From::dims();
+    let to = To::dims();
+
+    assert!(axis < from.len(), "Axis {axis} is out of range for a tensor of rank {}", from.len());
+    assert_eq!(from.len(), to.len(), "Cannot narrow a tensor into a different rank");
+
+    for (i, (f, t)) in from.iter().zip(&to).enumerate() {
+        if i == axis {
+            assert!(start + t <= *f, "Slice {start}..{} is out of bounds for axis of length {f}", start + t);
+        } else {
+            assert_eq!(f, t, "Narrowing can only change the length of the narrowed axis");
+        }
+    }
+
+    narrow_to(t, axis, start, to[axis])
+}
+
+///
+/// Takes the slice `start..start + len` of a tensor along one axis, for shapes that are only known at runtime
+///
+pub(crate) fn narrow_to<From: AnyShape, To: AnyShape, E: DType>(t: Tensor<From, E>, axis: usize, start: usize, len: usize) -> Tensor<To, E> {
+    let dims = t.dims();
+
+    assert!(axis < dims.len(), "Axis {axis} is out of range for a tensor of rank {}", dims.len());
+    assert!(start + len <= dims[axis], "Slice {start}..{} is out of bounds for axis of length {}", start + len, dims[axis]);
+
+    let device = t.device.clone();
+
+    device.dispatch(TensorNarrow {
+        input: t,
+        axis,
+        start,
+        len,
+        _phantom: PhantomData
+    })
+}
+
+pub struct TensorNarrow<From: AnyShape, To: AnyShape, E: DType> {
+    pub input: Tensor<From, E>,
+    pub axis: usize,
+    pub start: usize,
+    pub len: usize,
+    _phantom: PhantomData<To>
+}
+
+impl<From: AnyShape, To: AnyShape, E: DType> TensorOp for TensorNarrow<From, To, E> {
+    type OutputShape = To;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<To, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<To, E>, gradient: Tensor<To, E>, gradients: &mut Gradients) {
+        gradients.add(&self.input, narrow_gradient(gradient, self.axis, self.start, self.input.dims().to_vec()));
+    }
+}
+
+impl<From: AnyShape, To: AnyShape, E: DType> DispatchTensorOp<TensorNarrow<From, To, E>> for Device {
+    fn dispatch(&self, op: TensorNarrow<From, To, E>) -> Tensor<To, E> {
+        let storage = op.input.inner.storage();
+        let layout = op.input.inner.layout().narrow(op.axis, op.start, op.len);
+
+        self.allocate_view(storage, layout, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorNarrow<From, To, E>, output: &Tensor<To, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let layout = slice_layout(op.input.dims(), op.axis, op.start, op.len);
+
+        // Elements outside of the slice did not contribute to the output
+        let mut gradient = self.filled_buffer(op.input.size(), E::zero());
+        self.data_kernels().gather_gradient(&output_gradient, &layout, &mut gradient);
+
+        self.add_to_gradient(&op.input, &gradient);
+        self.recycle_buffer(gradient);
+    }
+}
+
+///
+/// Where a slice lies in a contiguous tensor of the given dimensions
+///
+fn slice_layout(dims: &[usize], axis: usize, start: usize, len: usize) -> Layout {
+    Layout::contiguous(dims.to_vec()).narrow(axis, start, len)
+}
+
+///
+/// The gradient a slice passes back to the tensor it was taken from, which is the slice's gradient padded with zeros
+/// up to `dims`
+///
+/// Padding is linear, and is undone by taking the slice again, which is its own gradient.
+///
+fn narrow_gradient<From: AnyShape, To: AnyShape, E: DType>(output_gradient: Tensor<To, E>, axis: usize, start: usize, dims: Vec<usize>) -> Tensor<From, E> {
+    output_gradient.device.clone().dispatch(TensorNarrowGradient {
+        output_gradient,
+        axis,
+        start,
+        dims,
+        _phantom: PhantomData
+    })
+}
+
+struct TensorNarrowGradient<From: AnyShape, To: AnyShape, E: DType> {
+    output_gradient: Tensor<To, E>,
+    axis: usize,
+    start: usize,
+    dims: Vec<usize>,
+    _phantom: PhantomData<From>
+}
+
+impl<From: AnyShape, To: AnyShape, E: DType> TensorNarrowGradient<From, To, E> {
+    fn layout(&self) -> Layout {
+        slice_layout(&self.dims, self.axis, self.start, self.output_gradient.dims()[self.axis])
+    }
+}
+
+impl<From: AnyShape, To: AnyShape, E: DType> TensorOp for TensorNarrowGradient<From, To, E> {
+    type OutputShape = From;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<From, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.output_gradient]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<From, E>, gradient: Tensor<From, E>, gradients: &mut Gradients) {
+        let len = self.output_gradient.dims()[self.axis];
+
+        gradients.add(&self.output_gradient, narrow_to(gradient, self.axis, self.start, len));
+    }
+}
+
+impl<From: AnyShape, To: AnyShape, E: DType> DispatchTensorOp<TensorNarrowGradient<From, To, E>> for Device {
+    fn dispatch(&self, op: TensorNarrowGradient<From, To, E>) -> Tensor<From, E> {
+        let output_gradient = self.get_tensor_buffer(&op.output_gradient);
+
+        let mut padded = self.filled_buffer(op.dims.iter().product(), E::zero());
+        self.data_kernels().gather_gradient(&output_gradient, &op.layout(), &mut padded);
+
+        let dims = op.dims.clone();
+
+        self.allocate_with_dims(padded, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorNarrowGradient<From, To, E>, output: &Tensor<From, E>) {
+        let gradient = self.get_gradient_buffer(output);
+
+        let mut sliced = self.filled_buffer(op.output_gradient.size(), E::zero());
+        self.data_kernels().gather(&gradient, &op.layout(), &mut sliced);
+
+        self.add_to_gradient(&op.output_gradient, &sliced);
+        self.recycle_buffer(sliced);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyTensor, DType, Gradients, Layout, Rank2, Rank3, Shape, Tensor}};
+
+use super::{DispatchTensorOp, TensorOp};
+
+///
+/// Reorders the axes of a tensor
+///
+/// Axis `i` of the output is axis `axes[i]` of the input. The output is a view of the input's storage, and is
+/// generally not contiguous.
+///
+pub fn permute<From: Shape, To: Shape, E: DType>(t: Tensor<From, E>, axes: &[usize]) -> Tensor<To, E> {
+    let dims = From::dims();
+
+    assert_eq!(axes.len(), dims.len(), "Permutation must name every axis");
+
+    let mut seen = vec![false; dims.len()];
+    for &axis in axes {
+        assert!(axis < dims.len() && !seen[axis], "Invalid permutation {axes:?}");
+        seen[axis] = true;
+    }
+
+    let permuted = axes.iter().map(|&axis| dims[axis]).collect::<Vec<_>>();
+    assert_eq!(permuted, To::dims(), "Permuted shape does not match the output shape");
+
+    let device = t.device.clone();
+
+    device.dispatch(TensorPermute {
+        input: t,
+        axes: axes.to_vec(),
+        _phantom: PhantomData
+    })
+}
+
+///
+/// Swaps the last two axes of a tensor
+///
+pub fn transpose<S: Transpose, E: DType>(t: Tensor<S, E>) -> Tensor<S::Transposed, E> {
+    let rank = S::dims().len();
+
+    let mut axes = (0..rank).collect::<Vec<_>>();
+    axes.swap(rank - 2, rank - 1);
+
+    permute(t, &axes)
+}
+
+pub trait Transpose: Shape {
+    type Transposed: Shape;
+}
+
+impl<const A: usize, const B: usize> Transpose for Rank2<A, B> {
+    type Transposed = Rank2<B, A>;
+}
+
+impl<const A: usize, const B: usize, const C: usize> Transpose for Rank3<A, B, C> {
+    type Transposed = Rank3<A, C, B>;
+}
+
+pub struct TensorPermute<From: Shape, To: Shape, E: DType> {
+    pub input: Tensor<From, E>,
+    pub axes: Vec<usize>,
+    _phantom: PhantomData<To>
+}
+
+impl<From: Shape, To: Shape, E: DType> TensorPermute<From, To, E> {
+    ///
+    /// The layout of a contiguous input, reordered to walk the input in output order
+    ///
+    fn input_layout(&self) -> Layout {
+        Layout::contiguous(From::dims()).permute(&self.axes)
+    }
+}
+
+impl<From: Shape, To: Shape, E: DType> TensorOp for TensorPermute<From, To, E> {
+    type OutputShape = To;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<To, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<To, E>, gradient: Tensor<To, E>, gradients: &mut Gradients) {
+        // Axis `axes[i]` of the input became axis `i` of the output, so the inverse permutation undoes it
+        let mut inverse = vec![0; self.axes.len()];
+
+        for (i, &axis) in self.axes.iter().enumerate() {
+            inverse[axis] = i;
+        }
+
+        gradients.add(&self.input, permute(gradient, &inverse));
+    }
+}
+
+impl<From: Shape, To: Shape, E: DType> DispatchTensorOp<TensorPermute<From, To, E>> for Device {
+    fn dispatch(&self, op: TensorPermute<From, To, E>) -> Tensor<To, E> {
+        let storage = op.input.inner.storage();
+        let layout = op.input.inner.layout().permute(&op.axes);
+
+        self.allocate_view(storage, layout, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorPermute<From, To, E>, output: &Tensor<To, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let layout = op.input_layout();
+
+        let mut gradient = self.filled_buffer(From::SIZE, E::zero());
+        self.data_kernels().gather_gradient(&output_gradient, &layout, &mut gradient);
+
+        self.add_to_gradient(&op.input, &gradient);
+        self.recycle_buffer(gradient);
+    }
+}
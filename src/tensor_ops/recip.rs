@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
+
+use super::{mul, scale, DispatchTensorOp, TensorOp};
+
+///
+/// One over every element
+///
+pub fn recip<S: AnyShape, E: Float>(t: Tensor<S, E>) -> Tensor<S, E> {
+    let device = t.device.clone();
+
+    device.dispatch(TensorRecip {
+        input: t
+    })
+}
+
+pub struct TensorRecip<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
+}
+
+impl<S: AnyShape, E: Float> TensorOp for TensorRecip<S, E> {
+    type OutputShape = S;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        // grad A = -grad X / A^2 = -grad X * X^2
+        let squared = mul(output.clone(), output.clone());
+
+        gradients.add(&self.input, scale(mul(gradient, squared), -E::one()));
+    }
+}
+
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorRecip<S, E>> for Device {
+    fn dispatch(&self, op: TensorRecip<S, E>) -> Tensor<S, E> {
+        let input = op.input.device.get_tensor_buffer(&op.input);
+
+        let mut output = self.filled_buffer(input.len(), E::zero());
+        self.kernels().map(&input, &mut output, &|i| E::one() / i);
+
+        let dims = op.input.dims().to_vec();
+
+        op.input.device.clone().allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorRecip<S, E>, output: &Tensor<S, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+        let output_buffer = self.get_tensor_buffer(output);
+
+        let mut nudge = self.filled_buffer(output_buffer.len(), E::zero());
+        self.kernels().zip(&output_buffer, &output_gradient, &mut nudge, &|v, d| -d * v * v);
+
+        op.input.device.add_to_gradient(&op.input, &nudge);
+        self.recycle_buffer(nudge);
+    }
+}
@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
-use crate::{device::Device, tensor::{source::TensorSource, Shape, Tensor}};
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
 
-use super::{DispatchTensorOp, TensorOp};
+use super::{mul, DispatchTensorOp, TensorOp};
 
-pub fn relu<S: Shape>(t: Tensor<S>) -> Tensor<S> {
+pub fn relu<S: AnyShape, E: Float>(t: Tensor<S, E>) -> Tensor<S, E> {
     let device = t.device.clone();
 
     device.dispatch(TensorRelu {
@@ -12,38 +12,55 @@ pub fn relu<S: Shape>(t: Tensor<S>) -> Tensor<S> {
     })
 }
 
-pub struct TensorRelu<S: Shape> {
-    pub input: Tensor<S>,
+pub struct TensorRelu<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
 }
 
-impl<S: Shape> TensorOp for TensorRelu<S> {
+impl<S: AnyShape, E: Float> TensorOp for TensorRelu<S, E> {
     type OutputShape = S;
+    type Elem = E;
 
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>) {
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        // The derivative is a step, which is constant wherever it is defined
+        let mask = output.device.get_tensor_buffer(output)
+                                .iter()
+                                .map(|v| if *v > E::zero() { E::one() } else { E::zero() })
+                                .collect();
+
+        let mask = output.device.allocate_with_dims(mask, output.dims().to_vec(), TensorSource::Constant);
+
+        gradients.add(&self.input, mul(gradient, mask));
+    }
 }
 
-impl<S: Shape> DispatchTensorOp<TensorRelu<S>> for Device {
-    fn dispatch(&self, op: TensorRelu<S>) -> Tensor<S> {
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorRelu<S, E>> for Device {
+    fn dispatch(&self, op: TensorRelu<S, E>) -> Tensor<S, E> {
         let input = op.input.device.get_tensor_buffer(&op.input);
       
-        let output = input.iter().map(|i| i.max(0.0) ).collect();
+        let mut output = self.filled_buffer(input.len(), E::zero());
+        self.kernels().map(&input, &mut output, &|i| i.max(E::zero()));
 
-        return op.input.device.clone().allocate_tensor(output, TensorSource::Operation(Arc::new(op)))
+        let dims = op.input.dims().to_vec();
+
+        return op.input.device.clone().allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
     }
 
-    fn back_dispatch(&self, op: &TensorRelu<S>, output: &Tensor<S>) {
+    fn back_dispatch(&self, op: &TensorRelu<S, E>, output: &Tensor<S, E>) {
         let output_gradient = self.get_gradient_buffer(&output);
         let output_buffer = self.get_tensor_buffer(&output);
 
-        let nudge = output_buffer.iter()
-                                 .zip(output_gradient)
-                                 .map(|(v, d)| if *v > 0.0f32 { *d } else { 0.0 })
-                                 .collect::<Vec<f32>>();
+        let mut nudge = self.filled_buffer(output_buffer.len(), E::zero());
+        self.kernels().zip(&output_buffer, &output_gradient, &mut nudge, &|v, d| if v > E::zero() { d } else { E::zero() });
         
         op.input.device.add_to_gradient(&op.input, &nudge);
-
-        op.input.move_backward();
+        self.recycle_buffer(nudge);
     }
 }
\ No newline at end of file
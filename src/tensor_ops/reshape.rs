@@ -1,50 +1,79 @@
-use std::{marker::PhantomData, ops::Add, sync::Arc};
+use std::{marker::PhantomData, sync::Arc};
 
-use crate::{device::Device, tensor::{source::TensorSource, Rank1, Shape, Tensor}};
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, DType, Gradients, Shape, Tensor}};
 
 use super::{DispatchTensorOp, TensorOp};
 
-pub fn reshape<From: Shape, To: Shape>(t: Tensor<From>) -> Tensor<To> {
+pub fn reshape<From: Shape, To: Shape, E: DType>(t: Tensor<From, E>) -> Tensor<To, E> {
     assert_eq!(From::SIZE, To::SIZE, "Cannot reshape tensor to a different size");
 
+    reshape_to(t, To::dims())
+}
+
+///
+/// Reshapes a tensor into the given dimensions, which is how tensors move between static and dynamic shapes
+/// 
+pub(crate) fn reshape_to<From: AnyShape, To: AnyShape, E: DType>(t: Tensor<From, E>, dims: Vec<usize>) -> Tensor<To, E> {
+    assert_eq!(t.size(), dims.iter().product::<usize>(), "Cannot reshape tensor to a different size");
+
     let device = t.device.clone();
 
     device.dispatch(TensorReshape {
         from: t,
+        dims,
         _phantom: PhantomData
     })
 }
 
 ///
-/// Adds two tensors of the same shape
+/// Changes the shape of a tensor without changing its data
 /// 
-/// Each element in the output tensor is the sum of the corresponding elements in the input tensors
+/// Contiguous tensors are reshaped into a view of the same storage, other tensors are copied
 /// 
 #[derive(Clone)]
-pub struct TensorReshape<From: Shape, To: Shape> {
-    pub from: Tensor<From>,
+pub struct TensorReshape<From: AnyShape, To: AnyShape, E: DType> {
+    pub from: Tensor<From, E>,
+    pub dims: Vec<usize>,
     _phantom: PhantomData<To>
 }
 
-impl<From: Shape, To: Shape> TensorOp for TensorReshape<From, To> {
+impl<From: AnyShape, To: AnyShape, E: DType> TensorOp for TensorReshape<From, To, E> {
     type OutputShape = To;
+    type Elem = E;
 
-    fn backprop(&self, device: &Device, output: &Tensor<To>) {
+    fn backprop(&self, device: &Device, output: &Tensor<To, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.from]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<To, E>, gradient: Tensor<To, E>, gradients: &mut Gradients) {
+        gradients.add(&self.from, reshape_to(gradient, self.from.dims().to_vec()));
+    }
 }
 
-impl<From: Shape, To: Shape> DispatchTensorOp<TensorReshape<From, To>> for Device {
-    fn dispatch(&self, op: TensorReshape<From, To>) -> Tensor<To> {
-        let from_buffer = self.get_tensor_buffer(&op.from);
+impl<From: AnyShape, To: AnyShape, E: DType> DispatchTensorOp<TensorReshape<From, To, E>> for Device {
+    fn dispatch(&self, op: TensorReshape<From, To, E>) -> Tensor<To, E> {
+        if let Some(layout) = op.from.inner.layout().reshape(op.dims.clone()) {
+            let storage = op.from.inner.storage();
+
+            return self.allocate_view(storage, layout, TensorSource::Operation(Arc::new(op)));
+        }
+
+        let layout = op.from.inner.layout();
+
+        let mut from_buffer = self.filled_buffer(layout.size(), E::zero());
+        self.data_kernels().gather(&op.from.inner.storage().data, layout, &mut from_buffer);
 
-        return self.allocate_tensor(from_buffer.to_owned(), TensorSource::Operation(Arc::new(op)));
+        let dims = op.dims.clone();
+
+        self.allocate_with_dims(from_buffer, dims, TensorSource::Operation(Arc::new(op)))
     }
 
-    fn back_dispatch(&self, op: &TensorReshape<From, To>, output: &Tensor<To>) {
+    fn back_dispatch(&self, op: &TensorReshape<From, To, E>, output: &Tensor<To, E>) {
         let output_gradient = self.get_gradient_buffer(output);
-        self.add_to_gradient(&op.from, output_gradient);
-
-        op.from.move_backward();
+        self.add_to_gradient(&op.from, &output_gradient);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
+
+use super::{DispatchTensorOp, TensorOp};
+
+///
+/// Multiplies every element of a tensor by a constant
+///
+/// Scaling by `-1` negates a tensor, which together with `add` gives subtraction
+///
+pub fn scale<S: AnyShape, E: Float>(t: Tensor<S, E>, factor: E) -> Tensor<S, E> {
+    let device = t.device.clone();
+
+    device.dispatch(TensorScale {
+        input: t,
+        factor
+    })
+}
+
+pub struct TensorScale<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
+    pub factor: E,
+}
+
+impl<S: AnyShape, E: Float> TensorOp for TensorScale<S, E> {
+    type OutputShape = S;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<S, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        gradients.add(&self.input, scale(gradient, self.factor));
+    }
+}
+
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorScale<S, E>> for Device {
+    fn dispatch(&self, op: TensorScale<S, E>) -> Tensor<S, E> {
+        let input = self.get_tensor_buffer(&op.input);
+
+        let mut output = self.filled_buffer(input.len(), E::zero());
+        self.kernels().map(&input, &mut output, &|i| i * op.factor);
+
+        let dims = op.input.dims().to_vec();
+
+        self.allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorScale<S, E>, output: &Tensor<S, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let mut gradient = self.filled_buffer(output_gradient.len(), E::zero());
+        self.kernels().map(&output_gradient, &mut gradient, &|g| g * op.factor);
+
+        self.add_to_gradient(&op.input, &gradient);
+        self.recycle_buffer(gradient);
+    }
+}
@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
-use crate::{device::Device, tensor::{source::TensorSource, Shape, Tensor}};
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
 
-use super::{DispatchTensorOp, TensorOp};
+use super::{add, mul, scale, DispatchTensorOp, TensorOp};
 
-pub fn sigmoid<S: Shape>(t: Tensor<S>) -> Tensor<S> {
+pub fn sigmoid<S: AnyShape, E: Float>(t: Tensor<S, E>) -> Tensor<S, E> {
     let device = t.device.clone();
 
     device.dispatch(TensorSigmoid {
@@ -12,38 +12,51 @@ pub fn sigmoid<S: Shape>(t: Tensor<S>) -> Tensor<S> {
     })
 }
 
-pub struct TensorSigmoid<S: Shape> {
-    pub input: Tensor<S>,
+pub struct TensorSigmoid<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
 }
 
-impl<S: Shape> TensorOp for TensorSigmoid<S> {
+impl<S: AnyShape, E: Float> TensorOp for TensorSigmoid<S, E> {
     type OutputShape = S;
+    type Elem = E;
 
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>) {
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        // grad A = grad X * X * (1 - X) = grad X * (X - X^2)
+        let squared = mul(output.clone(), output.clone());
+        let derivative = add(output.clone(), scale(squared, -E::one()));
+
+        gradients.add(&self.input, mul(gradient, derivative));
+    }
 }
 
-impl<S: Shape> DispatchTensorOp<TensorSigmoid<S>> for Device {
-    fn dispatch(&self, op: TensorSigmoid<S>) -> Tensor<S> {
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorSigmoid<S, E>> for Device {
+    fn dispatch(&self, op: TensorSigmoid<S, E>) -> Tensor<S, E> {
         let input = op.input.device.get_tensor_buffer(&op.input);
       
-        let output = input.iter().map(|i| 1.0 / (1.0 + (-i).exp()) ).collect();
+        let mut output = self.filled_buffer(input.len(), E::zero());
+        self.kernels().map(&input, &mut output, &|i| E::one() / (E::one() + (-i).exp()));
+
+        let dims = op.input.dims().to_vec();
 
-        return op.input.device.clone().allocate_tensor(output, TensorSource::Operation(Arc::new(op)))
+        return op.input.device.clone().allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
     }
 
-    fn back_dispatch(&self, op: &TensorSigmoid<S>, output: &Tensor<S>) {
+    fn back_dispatch(&self, op: &TensorSigmoid<S, E>, output: &Tensor<S, E>) {
         let output_gradient = self.get_gradient_buffer(&output);
         let output_buffer = self.get_tensor_buffer(&output);
 
-        let nudge = output_buffer.iter()
-                                 .zip(output_gradient)
-                                 .map(|(v, d)| *d * v * (1.0 - v))
-                                 .collect::<Vec<f32>>();
+        let mut nudge = self.filled_buffer(output_buffer.len(), E::zero());
+        self.kernels().zip(&output_buffer, &output_gradient, &mut nudge, &|v, d| d * v * (E::one() - v));
         
         op.input.device.add_to_gradient(&op.input, &nudge);
-
-        op.input.move_backward();
+        self.recycle_buffer(nudge);
     }
 }
\ No newline at end of file
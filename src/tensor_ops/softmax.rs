@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
-use crate::{device::Device, tensor::{source::TensorSource, Shape, Tensor}};
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
 
-use super::{DispatchTensorOp, TensorOp};
+use super::{add, broadcast_to, mul, scale, sum, DispatchTensorOp, TensorOp};
 
-pub fn softmax<S: Shape>(t: Tensor<S>) -> Tensor<S> {
+pub fn softmax<S: AnyShape, E: Float>(t: Tensor<S, E>) -> Tensor<S, E> {
     let device = t.device.clone();
 
     device.dispatch(TensorSoftmax {
@@ -12,70 +12,59 @@ pub fn softmax<S: Shape>(t: Tensor<S>) -> Tensor<S> {
     })
 }
 
-pub struct TensorSoftmax<S: Shape> {
-    pub input: Tensor<S>,
+pub struct TensorSoftmax<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
 }
 
-impl<S: Shape> TensorOp for TensorSoftmax<S> {
+impl<S: AnyShape, E: Float> TensorOp for TensorSoftmax<S, E> {
     type OutputShape = S;
+    type Elem = E;
 
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>) {
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        // grad Ak = Xk * (grad Xk - sum[i] (grad Xi * Xi)), as in `back_dispatch`
+        let dot = sum(mul(gradient.clone(), output.clone()));
+        let dot = broadcast_to(dot, output.dims().to_vec());
+
+        gradients.add(&self.input, mul(output.clone(), add(gradient, scale(dot, -E::one()))));
+    }
 }
 
-impl<S: Shape> DispatchTensorOp<TensorSoftmax<S>> for Device {
-    fn dispatch(&self, op: TensorSoftmax<S>) -> Tensor<S> {
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorSoftmax<S, E>> for Device {
+    fn dispatch(&self, op: TensorSoftmax<S, E>) -> Tensor<S, E> {
         let input = op.input.device.get_tensor_buffer(&op.input);
 
-        let factor = input.iter().cloned().reduce(f32::max).unwrap();
-        let mut output = input.iter().map(|i| (i - factor).exp() ).collect::<Vec<_>>();
-        let sum = output.iter().sum::<f32>();
+        let mut output = self.filled_buffer(input.len(), E::zero());
+        self.kernels().softmax(&input, &mut output);
 
-        for i in output.iter_mut() {
-            *i /= sum;
-        }
+        let dims = op.input.dims().to_vec();
 
-        return op.input.device.clone().allocate_tensor(output, TensorSource::Operation(Arc::new(op)))
+        return op.input.device.clone().allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
     }
 
-    fn back_dispatch(&self, op: &TensorSoftmax<S>, output: &Tensor<S>) {
-        /* How to do the gradient of a softmax? */
-        /* Remember, Xi = (e^Ai) / (sum e^Aj) = u / v  */
-        /* del Xi / del Ak = (u'v - uv')/(v^2)   */
-        // v' = 1
-        // i == k: u' = e^Ak
+    fn back_dispatch(&self, op: &TensorSoftmax<S, E>, output: &Tensor<S, E>) {
+        // With Xi = e^Ai / sum[j] e^Aj:
         //
-        // Xi' = (e^Ak * sum e^Aj - e^Ak * e^Ak) / (sum e^Aj)^2
-        //     = (e^Ak) / (sum e^Aj) * (sum e^Aj - e^Ak) / (sum e^Aj)
-        //     = softmax(e^Ak) * (1 - softmax(e^Ak))
+        // del Xi / del Ak = Xi * (1 - Xk)  when i == k
+        //                 = -Xi * Xk       when i != k
         //
-        // i != k: u' = 0
-        //
-        // Xi' = (0 - e^Ai * e^Ak) / (sum e^Aj)^2
-        //     = -(e^Ai / sum e^Aj) * (e^Ak / sum e^Aj)
-        //     = -softmax(Ai) * softmax(Ak)
-
-        let output_gradient = self.get_gradient_buffer(&output);
-        let output_buffer = self.get_tensor_buffer(&output);
-
-        // grad Ai = sum[j] (grad Xj * del Xj / del Ai)
-
-        let mut gradient = vec![0.0f32; op.input.size()];
-
-        for j in 0..output_buffer.len() {
-            let softmax_j = output_buffer[j];
+        // grad Ak = sum[i] (grad Xi * del Xi / del Ak)
+        //         = Xk * (grad Xk - sum[i] (grad Xi * Xi))
 
-            for i in 0..gradient.len() {
-                let softmax_i = output_buffer[i];
-                let factor = if i == j { 1.0 } else { 0.0 };
+        let output_gradient = self.get_gradient_buffer(output);
+        let output_buffer = self.get_tensor_buffer(output);
 
-                gradient[i] = output_gradient[j] * ( (factor - softmax_i) * softmax_j );
-            }
-            
-            op.input.device.add_to_gradient(&op.input, &gradient);
-        }
+        let mut gradient = self.filled_buffer(output_buffer.len(), E::zero());
+        self.kernels().softmax_gradient(&output_buffer, &output_gradient, &mut gradient);
 
-        op.input.move_backward();
+        self.add_to_gradient(&op.input, &gradient);
+        self.recycle_buffer(gradient);
     }
 }
\ No newline at end of file
@@ -0,0 +1,73 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyTensor, DType, Dyn, Gradients, Shape, Tensor}};
+
+use super::{narrow_to, reshape_to, DispatchTensorOp, TensorOp};
+
+///
+/// Joins tensors of the same shape along a new leading axis
+///
+/// Entry `i` of the output along the first axis is `tensors[i]`
+///
+pub fn stack<S: Shape, To: Shape, E: DType>(tensors: Vec<Tensor<S, E>>) -> Tensor<To, E> {
+    assert!(!tensors.is_empty(), "Cannot stack an empty list of tensors");
+
+    let mut dims = vec![tensors.len()];
+    dims.extend(S::dims());
+
+    assert_eq!(dims, To::dims(), "Stacked shape does not match the output shape");
+
+    let device = tensors[0].device.clone();
+
+    device.dispatch(TensorStack {
+        inputs: tensors,
+        _phantom: PhantomData
+    })
+}
+
+pub struct TensorStack<S: Shape, To: Shape, E: DType> {
+    pub inputs: Vec<Tensor<S, E>>,
+    _phantom: PhantomData<To>
+}
+
+impl<S: Shape, To: Shape, E: DType> TensorOp for TensorStack<S, To, E> {
+    type OutputShape = To;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<To, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        self.inputs.iter().map(|t| t as &dyn AnyTensor).collect()
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<To, E>, gradient: Tensor<To, E>, gradients: &mut Gradients) {
+        // Each input gets its entry of the gradient along the stacked axis, without the axis
+        for (i, input) in self.inputs.iter().enumerate() {
+            let entry = narrow_to::<To, Dyn, E>(gradient.clone(), 0, i, 1);
+
+            gradients.add(input, reshape_to(entry, S::dims()));
+        }
+    }
+}
+
+impl<S: Shape, To: Shape, E: DType> DispatchTensorOp<TensorStack<S, To, E>> for Device {
+    fn dispatch(&self, op: TensorStack<S, To, E>) -> Tensor<To, E> {
+        let inputs = op.inputs.iter().map(|input| self.get_tensor_buffer(input)).collect::<Vec<_>>();
+        let inputs = inputs.iter().map(|input| &**input).collect::<Vec<_>>();
+
+        let mut output = self.filled_buffer(To::SIZE, E::zero());
+        self.data_kernels().stack(&inputs, &mut output);
+
+        self.allocate_tensor(output, TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorStack<S, To, E>, output: &Tensor<To, E>) {
+        let output_gradient = self.get_gradient_buffer(output);
+
+        for (input, gradient) in op.inputs.iter().zip(output_gradient.chunks(S::SIZE)) {
+            self.add_to_gradient(input, gradient);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Rank1, Tensor}};
+
+use super::{broadcast_to, DispatchTensorOp, TensorOp};
+
+///
+/// Adds up every element of a tensor
+///
+pub fn sum<S: AnyShape, E: Float>(t: Tensor<S, E>) -> Tensor<Rank1<1>, E> {
+    let device = t.device.clone();
+
+    device.dispatch(TensorSum {
+        input: t
+    })
+}
+
+pub struct TensorSum<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
+}
+
+impl<S: AnyShape, E: Float> TensorOp for TensorSum<S, E> {
+    type OutputShape = Rank1<1>;
+    type Elem = E;
+
+    fn backprop(&self, device: &Device, output: &Tensor<Rank1<1>, E>) {
+        device.back_dispatch(self, output);
+    }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, _output: &Tensor<Rank1<1>, E>, gradient: Tensor<Rank1<1>, E>, gradients: &mut Gradients) {
+        gradients.add(&self.input, broadcast_to(gradient, self.input.dims().to_vec()));
+    }
+}
+
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorSum<S, E>> for Device {
+    fn dispatch(&self, op: TensorSum<S, E>) -> Tensor<Rank1<1>, E> {
+        let input = self.get_tensor_buffer(&op.input);
+
+        let total = self.kernels().sum(&input);
+
+        self.allocate_tensor(self.filled_buffer(1, total), TensorSource::Operation(Arc::new(op)))
+    }
+
+    fn back_dispatch(&self, op: &TensorSum<S, E>, output: &Tensor<Rank1<1>, E>) {
+        // Every element contributes to the sum with a weight of one
+        let output_gradient = self.get_gradient_buffer(output);
+
+        let gradient = self.filled_buffer(op.input.size(), output_gradient[0]);
+
+        self.add_to_gradient(&op.input, &gradient);
+        self.recycle_buffer(gradient);
+    }
+}
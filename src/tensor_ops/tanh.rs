@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
-use crate::{device::Device, tensor::{source::TensorSource, Shape, Tensor}};
+use crate::{device::Device, tensor::{source::TensorSource, AnyShape, AnyTensor, Float, Gradients, Tensor}};
 
-use super::{DispatchTensorOp, TensorOp};
+use super::{add, mul, scale, DispatchTensorOp, TensorOp};
 
-pub fn tanh<S: Shape>(t: Tensor<S>) -> Tensor<S> {
+pub fn tanh<S: AnyShape, E: Float>(t: Tensor<S, E>) -> Tensor<S, E> {
     let device = t.device.clone();
 
     device.dispatch(TensorTanh {
@@ -12,38 +12,50 @@ pub fn tanh<S: Shape>(t: Tensor<S>) -> Tensor<S> {
     })
 }
 
-pub struct TensorTanh<S: Shape> {
-    pub input: Tensor<S>,
+pub struct TensorTanh<S: AnyShape, E: Float> {
+    pub input: Tensor<S, E>,
 }
 
-impl<S: Shape> TensorOp for TensorTanh<S> {
+impl<S: AnyShape, E: Float> TensorOp for TensorTanh<S, E> {
     type OutputShape = S;
+    type Elem = E;
 
-    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape>) {
+    fn backprop(&self, device: &Device, output: &Tensor<Self::OutputShape, E>) {
         device.back_dispatch(self, output);
     }
+
+    fn inputs(&self) -> Vec<&dyn AnyTensor> {
+        vec![&self.input]
+    }
+
+    fn backprop_graph(&self, output: &Tensor<S, E>, gradient: Tensor<S, E>, gradients: &mut Gradients) {
+        // grad A = grad X * (1 - X^2) = grad X - grad X * X^2
+        let squared = mul(output.clone(), output.clone());
+
+        gradients.add(&self.input, add(gradient.clone(), scale(mul(gradient, squared), -E::one())));
+    }
 }
 
-impl<S: Shape> DispatchTensorOp<TensorTanh<S>> for Device {
-    fn dispatch(&self, op: TensorTanh<S>) -> Tensor<S> {
+impl<S: AnyShape, E: Float> DispatchTensorOp<TensorTanh<S, E>> for Device {
+    fn dispatch(&self, op: TensorTanh<S, E>) -> Tensor<S, E> {
         let input = op.input.device.get_tensor_buffer(&op.input);
       
-        let output = input.iter().map(|i| (i.exp() - (-i).exp()) / (i.exp() + (-i).exp()) ).collect();
+        let mut output = self.filled_buffer(input.len(), E::zero());
+        self.kernels().map(&input, &mut output, &|i| (i.exp() - (-i).exp()) / (i.exp() + (-i).exp()));
+
+        let dims = op.input.dims().to_vec();
 
-        return op.input.device.clone().allocate_tensor(output, TensorSource::Operation(Arc::new(op)))
+        return op.input.device.clone().allocate_with_dims(output, dims, TensorSource::Operation(Arc::new(op)))
     }
 
-    fn back_dispatch(&self, op: &TensorTanh<S>, output: &Tensor<S>) {
+    fn back_dispatch(&self, op: &TensorTanh<S, E>, output: &Tensor<S, E>) {
         let output_gradient = self.get_gradient_buffer(&output);
         let output_buffer = self.get_tensor_buffer(&output);
 
-        let nudge = output_buffer.iter()
-                                 .zip(output_gradient)
-                                 .map(|(v, d)| *d * (1.0 - v*v))
-                                 .collect::<Vec<f32>>();
+        let mut nudge = self.filled_buffer(output_buffer.len(), E::zero());
+        self.kernels().zip(&output_buffer, &output_gradient, &mut nudge, &|v, d| d * (E::one() - v * v));
         
         op.input.device.add_to_gradient(&op.input, &nudge);
-
-        op.input.move_backward();
+        self.recycle_buffer(nudge);
     }
 }
\ No newline at end of file
@@ -0,0 +1,997 @@
+use crate::{device::Device, tensor::{AnyShape, Backward, Dyn, DynShape, Rank1, Rank2, Rank3, Shape, ShapeError, Tensor}};
+
+use super::*;
+
+fn device() -> Device {
+    let device = Device::new();
+    device.seed(0);
+
+    device
+}
+
+///
+/// Random inputs in `[-1, 1)`, well away from the kink in ReLU for any realistic draw
+///
+fn input<S: Shape>(device: &Device) -> Tensor<S, f64> {
+    device.uniform(-1.0, 1.0)
+}
+
+///
+/// Random inputs that can be treated as probabilities
+///
+fn positive<S: Shape>(device: &Device) -> Tensor<S, f64> {
+    device.uniform(0.1, 1.0)
+}
+
+fn check<S: AnyShape, O: AnyShape>(inputs: &[&Tensor<S, f64>], f: impl Fn() -> Tensor<O, f64>) {
+    let inputs = inputs.iter().map(|t| t.as_ref()).collect::<Vec<_>>();
+
+    if let Err(error) = gradcheck(&inputs, f) {
+        panic!("{error}");
+    }
+}
+
+#[test]
+fn add() {
+    let device = device();
+    let a = input::<Rank1<5>>(&device);
+    let b = input::<Rank1<5>>(&device);
+
+    check(&[&a, &b], || a.clone() + b.clone());
+}
+
+#[test]
+fn add_dyn() {
+    let device = device();
+    let a = device.sample_dyn::<f64>(&DynShape::new([2, 3]));
+    let b = device.sample_dyn::<f64>(&DynShape::new([2, 3]));
+
+    check(&[&a, &b], || try_add(a.clone(), b.clone()).unwrap());
+}
+
+#[test]
+fn mean_squared_error() {
+    let device = device();
+    let a = input::<Rank1<4>>(&device);
+    let targets = input::<Rank1<4>>(&device);
+
+    check(&[&a, &targets], || mse(a.clone(), targets.clone()));
+}
+
+#[test]
+fn cross_entropy() {
+    let device = device();
+    let a = positive::<Rank1<4>>(&device);
+    let targets = positive::<Rank1<4>>(&device);
+
+    check(&[&a, &targets], || cross_entropy_loss(a.clone(), targets.clone()));
+}
+
+#[test]
+fn sparse_cross_entropy() {
+    let device = device();
+    let a = positive::<Rank1<4>>(&device);
+    let label = device.constant::<Rank1<1>, i64>(&[2]);
+
+    check(&[&a], || sparse_cross_entropy_loss(a.clone(), label.clone()));
+}
+
+#[test]
+fn softmax_cross_entropy() {
+    let device = device();
+    let a = input::<Rank1<5>>(&device);
+    let targets = device.one_hot::<5, f64>(3);
+
+    check(&[&a], || cross_entropy_loss(softmax(a.clone()), targets.clone()));
+}
+
+#[test]
+fn vector_matmul() {
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    let b = input::<Rank2<3, 4>>(&device);
+
+    check(&[&a], || matmul(a.clone(), b.clone()));
+    check(&[&b], || matmul(a.clone(), b.clone()));
+}
+
+#[test]
+fn matrix_matmul() {
+    let device = device();
+    let a = input::<Rank2<2, 3>>(&device);
+    let b = input::<Rank2<3, 4>>(&device);
+
+    check(&[&a], || matmul(a.clone(), b.clone()));
+    check(&[&b], || matmul(a.clone(), b.clone()));
+}
+
+#[test]
+fn matmul_dyn() {
+    let device = device();
+    let vector = device.sample_dyn::<f64>(&DynShape::new([3]));
+    let matrix = device.sample_dyn::<f64>(&DynShape::new([2, 3]));
+    let rhs = device.sample_dyn::<f64>(&DynShape::new([3, 4]));
+
+    check(&[&vector, &rhs], || try_matmul(vector.clone(), rhs.clone()).unwrap());
+    check(&[&matrix, &rhs], || try_matmul(matrix.clone(), rhs.clone()).unwrap());
+}
+
+#[test]
+fn losses_dyn() {
+    let device = device();
+    let a = device.uniform::<Rank2<2, 3>, f64>(0.1, 1.0).to_dyn();
+    let targets = device.uniform::<Rank2<2, 3>, f64>(0.1, 1.0).to_dyn();
+
+    check(&[&a, &targets], || try_mse(a.clone(), targets.clone()).unwrap());
+    check(&[&a, &targets], || try_cross_entropy_loss(a.clone(), targets.clone()).unwrap());
+}
+
+#[test]
+fn mismatched_dyn_shapes() {
+    let device = device();
+    let vector = device.sample_dyn::<f64>(&DynShape::new([3]));
+    let matrix = device.sample_dyn::<f64>(&DynShape::new([2, 3]));
+    let tensor = device.sample_dyn::<f64>(&DynShape::new([2, 3, 4]));
+
+    let incompatible = |op, lhs: &Tensor<Dyn, f64>, rhs: &Tensor<Dyn, f64>| ShapeError::Incompatible { op, lhs: lhs.shape(), rhs: rhs.shape() };
+
+    assert_eq!(try_add(vector.clone(), matrix.clone()).unwrap_err(), incompatible("add", &vector, &matrix));
+    assert_eq!(try_matmul(matrix.clone(), matrix.clone()).unwrap_err(), incompatible("multiply", &matrix, &matrix));
+    assert_eq!(try_matmul(tensor.clone(), matrix.clone()).unwrap_err(), incompatible("multiply", &tensor, &matrix));
+    assert_eq!(try_matmul(vector.clone(), vector.clone()).unwrap_err(), incompatible("multiply", &vector, &vector));
+    assert_eq!(try_mse(matrix.clone(), tensor.clone()).unwrap_err(), incompatible("take the mean squared error of", &matrix, &tensor));
+    assert_eq!(try_cross_entropy_loss(vector.clone(), matrix.clone()).unwrap_err(), incompatible("take the cross entropy of", &vector, &matrix));
+
+    assert_eq!(matrix.try_reshape(&DynShape::new([4])).unwrap_err(), ShapeError::Size { expected: 6, found: 4 });
+    assert_eq!(matrix.try_to_shape::<Rank2<3, 2>>().unwrap_err(), ShapeError::Mismatch { expected: DynShape::new([3, 2]), found: matrix.shape() });
+}
+
+#[test]
+#[should_panic(expected = "Cannot take the mean squared error of tensors of different shapes")]
+fn mse_checks_dyn_shapes() {
+    let device = device();
+
+    mse(device.sample_dyn::<f64>(&DynShape::new([4])), device.sample_dyn::<f64>(&DynShape::new([3])));
+}
+
+#[test]
+fn activations() {
+    let device = device();
+    let a = input::<Rank2<2, 3>>(&device);
+
+    check(&[&a], || relu(a.clone()));
+    check(&[&a], || softmax(a.clone()));
+    check(&[&a], || sigmoid(a.clone()));
+    check(&[&a], || tanh(a.clone()));
+}
+
+#[test]
+fn convolution() {
+    let device = device();
+    let a = input::<Rank2<6, 5>>(&device);
+    let kernel = input::<Rank2<3, 2>>(&device);
+
+    check(&[&a], || conv2d(a.clone(), kernel.clone()));
+    check(&[&kernel], || conv2d(a.clone(), kernel.clone()));
+}
+
+#[test]
+fn reshapes() {
+    let device = device();
+    let a = input::<Rank2<2, 3>>(&device);
+
+    check(&[&a], || reshape::<_, Rank1<6>, _>(a.clone()));
+    check(&[&a], || a.to_dyn().try_reshape(&DynShape::new([3, 2])).unwrap());
+    check(&[&a], || a.to_dyn().try_to_shape::<Rank2<2, 3>>().unwrap());
+}
+
+#[test]
+fn permutes() {
+    let device = device();
+    let a = input::<Rank3<2, 3, 4>>(&device);
+    let b = input::<Rank2<2, 3>>(&device);
+
+    check(&[&a], || permute::<_, Rank3<4, 2, 3>, _>(a.clone(), &[2, 0, 1]));
+    check(&[&b], || transpose(b.clone()));
+    check(&[&b], || contiguous(transpose(b.clone())));
+}
+
+#[test]
+fn narrow_and_select() {
+    let device = device();
+    let a = input::<Rank2<4, 3>>(&device);
+
+    check(&[&a], || narrow::<_, Rank2<4, 2>, _>(a.clone(), 1, 1));
+    check(&[&a], || index_select::<_, Rank2<5, 3>, _>(a.clone(), 0, &[3, 0, 3, 1, 3]));
+}
+
+#[test]
+fn concat_and_stack() {
+    let device = device();
+    let a = input::<Rank2<2, 3>>(&device);
+    let b = input::<Rank2<2, 3>>(&device);
+
+    check(&[&a, &b], || concat::<_, _, Rank2<2, 6>, _>(a.clone(), b.clone(), 1));
+    check(&[&a, &b], || stack::<_, Rank3<2, 2, 3>, _>(vec![a.clone(), b.clone()]));
+}
+
+#[test]
+fn cast_to_f32() {
+    let device = device();
+    let a = input::<Rank1<4>>(&device);
+
+    let cfg = GradcheckConfig { eps: 1e-2, atol: 1e-3, rtol: 1e-3 };
+
+    if let Err(error) = gradcheck_with(&cfg, &[a.as_ref()], || cast::<_, f64, f32>(a.clone())) {
+        panic!("{error}");
+    }
+}
+
+#[test]
+fn cast_to_integer_saturates() {
+    let device = device();
+    let a = device.constant::<Rank1<6>, f64>(&[f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 300.0, -5.0, 2.7]);
+
+    assert_eq!(device.get_tensor_buffer(&cast::<_, _, u8>(a.clone())), &[0, 255, 0, 255, 0, 2]);
+    assert_eq!(device.get_tensor_buffer(&cast::<_, _, i32>(a)), &[0, i32::MAX, i32::MIN, 300, -5, 2]);
+
+    // Gradients are converted back to the integer input the same way
+    let labels = device.constant::<Rank1<3>, u8>(&[1, 2, 3]);
+    labels.set_requires_grad(true);
+
+    cast::<_, _, f64>(labels.clone()).backward_with(&[f64::NAN, 1e9, -1.5]);
+
+    assert_eq!(device.get_gradient_buffer(&labels), &[0, 255, 0]);
+}
+
+#[test]
+fn cast_to_integer_cuts_graph() {
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    a.set_requires_grad(true);
+
+    let labels = cast::<_, _, i64>(a.clone());
+
+    assert_eq!(labels.op_name(), None);
+    assert!(!labels.requires_grad());
+
+    // So no truncated gradient can make its way back to the float input
+    sum(mul(a.clone(), cast(labels))).back();
+    assert_eq!(device.get_gradient_buffer(&a), device.get_tensor_buffer(&a).iter().map(|x| x.trunc()).collect::<Vec<_>>());
+}
+
+#[test]
+fn embedding_lookup() {
+    let device = device();
+    let weights = input::<Rank2<5, 3>>(&device);
+    let indices = device.constant::<Rank1<4>, u8>(&[4, 0, 4, 2]);
+
+    check(&[&weights], || embedding(weights.clone(), indices.clone()));
+}
+
+#[test]
+fn dropout_mask() {
+    let device = device();
+    let a = input::<Rank2<3, 4>>(&device);
+
+    check(&[&a], || {
+        // Reseed so every evaluation drops the same elements
+        device.seed(1);
+
+        dropout(a.clone(), 0.5)
+    });
+}
+
+#[test]
+fn shared_subgraph() {
+    let device = device();
+    let a = input::<Rank1<4>>(&device);
+
+    check(&[&a], || {
+        let b = tanh(a.clone());
+
+        b.clone() + relu(b)
+    });
+}
+
+#[test]
+fn dyn_and_static_graphs_agree() {
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    let b = input::<Rank2<3, 2>>(&device);
+
+    let graph = || -> Tensor<Dyn, f64> {
+        let static_product = matmul(a.clone(), b.clone()).to_dyn();
+        let dyn_product = try_matmul(a.to_dyn(), b.to_dyn()).unwrap();
+
+        try_add(static_product, dyn_product).unwrap()
+    };
+
+    check(&[&a], graph);
+}
+
+///
+/// Elementwise product, standing in for an op written outside the crate
+///
+struct Multiply;
+
+impl CustomOp<f64> for Multiply {
+    fn forward(&self, inputs: &[&[f64]]) -> Vec<f64> {
+        inputs[0].iter().zip(inputs[1]).map(|(a, b)| a * b).collect()
+    }
+
+    fn backward(&self, grad_output: &[f64], inputs: &[&[f64]]) -> Vec<Vec<f64>> {
+        vec![
+            grad_output.iter().zip(inputs[1]).map(|(g, b)| g * b).collect(),
+            grad_output.iter().zip(inputs[0]).map(|(g, a)| g * a).collect(),
+        ]
+    }
+}
+
+#[test]
+fn custom() {
+    let device = device();
+    let a = input::<Rank2<2, 3>>(&device);
+    let b = input::<Rank2<2, 3>>(&device);
+
+    check(&[&a, &b], || custom_op::<Rank2<2, 3>, _, _>(Multiply, &[a.to_dyn(), b.to_dyn()]));
+    check(&[&a], || tanh(custom_op::<Dyn, _, _>(Multiply, &[a.to_dyn(), a.to_dyn()])));
+
+    a.set_requires_grad(true);
+    assert_eq!(custom_op::<Dyn, _, _>(Multiply, &[a.to_dyn(), b.to_dyn()]).op_name(), Some("Multiply"));
+
+    // Gradients can be calculated as tensors, but not differentiated again
+    let a = a.to_dyn();
+    let gradient = sum(custom_op::<Dyn, _, _>(Multiply, &[a.clone(), b.to_dyn()])).gradients().get(&a).unwrap();
+
+    assert_eq!(device.get_tensor_buffer(&gradient), device.get_tensor_buffer(&b));
+    assert!(gradient.requires_grad());
+}
+
+#[test]
+#[should_panic(expected = "Gradients of Multiply cannot be differentiated")]
+fn custom_gradients_are_not_differentiable() {
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+
+    hessian(|a| sum(custom_op::<Dyn, _, _>(Multiply, &[a.to_dyn(), a.to_dyn()])), &a);
+}
+
+#[test]
+fn hooks_see_final_gradient() {
+    use std::sync::{Arc, Mutex};
+
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    a.set_requires_grad(true);
+
+    let b = relu(a.clone());
+    let calls = Arc::new(Mutex::new(vec![]));
+
+    let seen = calls.clone();
+    b.register_hook(move |gradient| {
+        seen.lock().unwrap().push(gradient.to_vec());
+        gradient.fill(0.5);
+    });
+
+    (b.clone() + b).back();
+
+    // Both uses of `b` are summed before the hook runs, and its change reaches `a`
+    assert_eq!(*calls.lock().unwrap(), vec![vec![2.0; 3]]);
+
+    let expected = device.get_tensor_buffer(&a).iter().map(|x| if *x > 0.0 { 0.5 } else { 0.0 }).collect::<Vec<_>>();
+    assert_eq!(device.get_gradient_buffer(&a), expected);
+}
+
+#[test]
+fn hooks_can_use_their_tensor() {
+    use std::sync::{Arc, Mutex};
+
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    a.set_requires_grad(true);
+
+    let b = scale(a.clone(), 2.0);
+    let tensor = b.as_ref();
+    let seen = Arc::new(Mutex::new(vec![]));
+
+    let record = seen.clone();
+    b.register_hook(move |gradient| {
+        // The gradient can be read through the tensor while the hook runs, and hooks added now run next time
+        record.lock().unwrap().push(tensor.gradient().to_vec());
+        gradient.fill(3.0);
+
+        let record = record.clone();
+        tensor.register_hook(move |gradient| record.lock().unwrap().push(gradient.to_vec()));
+    });
+
+    let loss = sum(b);
+    Backward::new().root(&loss).retain_graph(true).run();
+
+    assert_eq!(*seen.lock().unwrap(), vec![vec![1.0; 3]]);
+    assert_eq!(device.get_gradient_buffer(&a), &[6.0; 3]);
+
+    loss.back();
+
+    assert_eq!(*seen.lock().unwrap(), vec![vec![1.0; 3], vec![1.0; 3], vec![3.0; 3]]);
+}
+
+///
+/// Passes values through unchanged, but claims a NaN gradient
+///
+struct BrokenBackward;
+
+impl CustomOp<f64> for BrokenBackward {
+    fn forward(&self, inputs: &[&[f64]]) -> Vec<f64> {
+        inputs[0].to_vec()
+    }
+
+    fn backward(&self, grad_output: &[f64], _inputs: &[&[f64]]) -> Vec<Vec<f64>> {
+        vec![vec![f64::NAN; grad_output.len()]]
+    }
+}
+
+#[test]
+#[should_panic(expected = "backward of BrokenBackward produced a NaN or infinite gradient")]
+fn anomaly_in_backward() {
+    let device = device();
+    device.set_detect_anomaly(true);
+
+    let a = input::<Rank1<3>>(&device);
+    a.set_requires_grad(true);
+
+    tanh(custom_op::<Rank1<3>, _, _>(BrokenBackward, &[a.to_dyn()])).back();
+}
+
+///
+/// Checks a Hessian-vector product, calculated by backpropagating through `Tensor::gradients`, against central
+/// finite differences of the first-order gradients
+///
+fn check_second_order<S: Shape, O: AnyShape>(x: &Tensor<S, f64>, f: impl Fn() -> Tensor<O, f64>) {
+    let device = x.device.clone();
+    let eps = 1e-6;
+
+    x.set_requires_grad(true);
+    x.as_ref().gradient_mut().fill(0.0);
+
+    let v = input::<S>(&device);
+
+    let gradient = || f().gradients().get(x).expect("x should have a gradient");
+
+    // d/dx (v . grad f) = H v
+    sum(mul(gradient(), v.clone())).back();
+    let analytic = device.get_gradient_buffer(x).to_vec();
+
+    let weighted = || {
+        let gradient = gradient();
+        let buffer = device.get_tensor_buffer(&gradient);
+
+        buffer.iter().zip(device.get_tensor_buffer(&v).iter()).map(|(g, v)| g * v).sum::<f64>()
+    };
+
+    for (index, analytic) in analytic.into_iter().enumerate() {
+        let original = x.as_ref().buffer_mut()[index];
+
+        x.as_ref().buffer_mut()[index] = original + eps;
+        let plus = weighted();
+
+        x.as_ref().buffer_mut()[index] = original - eps;
+        let minus = weighted();
+
+        x.as_ref().buffer_mut()[index] = original;
+
+        let numeric = (plus - minus) / (2.0 * eps);
+
+        assert!((analytic - numeric).abs() < 1e-5 + 1e-3 * numeric.abs(), "Hessian-vector product at {index} is {analytic}, but finite differences give {numeric}");
+    }
+}
+
+#[test]
+fn elementwise_ops() {
+    let device = device();
+    let a = input::<Rank2<2, 3>>(&device);
+    let b = input::<Rank2<2, 3>>(&device);
+
+    check(&[&a, &b], || mul(a.clone(), b.clone()));
+    check(&[&a, &b], || super::add(a.clone(), scale(b.clone(), -2.0)));
+    check(&[&a], || broadcast::<Rank2<3, 2>, _>(sum(a.clone())));
+}
+
+#[test]
+fn second_order_activations() {
+    let device = device();
+    let x = input::<Rank1<4>>(&device);
+
+    check_second_order(&x, || sum(tanh(x.clone())));
+    check_second_order(&x, || sum(mul(sigmoid(x.clone()), x.clone())));
+    check_second_order(&x, || sum(mul(softmax(x.clone()), x.clone())));
+    check_second_order(&x, || sum(mul(relu(x.clone()), x.clone())));
+}
+
+#[test]
+fn logarithm_and_reciprocal() {
+    let device = device();
+    let p = positive::<Rank1<4>>(&device);
+
+    check(&[&p], || ln(p.clone()));
+    check(&[&p], || recip(p.clone()));
+    check_second_order(&p, || sum(mul(ln(p.clone()), recip(p.clone()))));
+}
+
+#[test]
+fn second_order_losses() {
+    let device = device();
+    let x = input::<Rank1<4>>(&device);
+    let targets = positive::<Rank1<4>>(&device);
+    let label = device.constant::<Rank1<1>, i64>(&[2]);
+
+    check_second_order(&x, || cross_entropy_loss(softmax(x.clone()), targets.clone()));
+    check_second_order(&x, || sparse_cross_entropy_loss(softmax(x.clone()), label.clone()));
+
+    // The probabilities and targets both depend on x, so both gradients of the loss are used
+    check_second_order(&x, || cross_entropy_loss(sigmoid(x.clone()), sigmoid(scale(x.clone(), 2.0))));
+}
+
+#[test]
+fn second_order_conv2d() {
+    let device = device();
+    let x = input::<Rank2<4, 5>>(&device);
+    let k = input::<Rank2<2, 3>>(&device);
+
+    x.set_requires_grad(true);
+    k.set_requires_grad(true);
+
+    let network = || sum(tanh(conv2d(x.clone(), k.clone())));
+
+    check_second_order(&x, network);
+    check_second_order(&k, network);
+
+    // Penalising either gradient backpropagates through the ops that calculate it
+    let inputs = [x.as_ref(), k.as_ref()];
+
+    let input_penalty = gradcheck(&inputs, || {
+        let gradient = network().gradients().get(&x).unwrap();
+
+        sum(mul(gradient.clone(), gradient))
+    });
+
+    let kernel_penalty = gradcheck(&inputs, || {
+        let gradient = network().gradients().get(&k).unwrap();
+
+        sum(mul(gradient.clone(), gradient))
+    });
+
+    for result in [input_penalty, kernel_penalty] {
+        if let Err(error) = result {
+            panic!("{error}");
+        }
+    }
+}
+
+#[test]
+fn second_order_network() {
+    let device = device();
+    let x = input::<Rank1<3>>(&device);
+    let w1 = input::<Rank2<3, 4>>(&device);
+    let w2 = input::<Rank2<4, 2>>(&device);
+    let targets = input::<Rank1<2>>(&device);
+
+    w1.set_requires_grad(true);
+    w2.set_requires_grad(true);
+
+    let network = || mse(matmul(tanh(matmul(x.clone(), w1.clone())), w2.clone()), targets.clone());
+
+    check_second_order(&x, network);
+    check_second_order(&w1, network);
+
+    let batch = input::<Rank2<2, 3>>(&device);
+
+    check_second_order(&w1, || {
+        let hidden = sigmoid(matmul(batch.clone(), w1.clone()));
+
+        sum(mul(hidden.reshape::<Rank1<8>>(), hidden.transpose().contiguous().reshape::<Rank1<8>>()))
+    });
+}
+
+///
+/// Checks the Hessian of a function, calculated by differentiating `Tensor::gradients` again, against central finite
+/// differences of its gradient
+///
+fn check_hessian<S: Shape, O: AnyShape>(x: &Tensor<S, f64>, f: impl Fn(Tensor<S, f64>) -> Tensor<O, f64>) {
+    let device = x.device.clone();
+    let eps = 1e-6;
+
+    let analytic = device.get_tensor_buffer(&hessian(&f, x)).to_vec();
+    let values = device.get_tensor_buffer(x).to_vec();
+
+    let gradient_at = |index: usize, delta: f64| {
+        let mut shifted = values.clone();
+        shifted[index] += delta;
+
+        device.get_tensor_buffer(&grad(&f, &device.constant::<S, f64>(&shifted))).to_vec()
+    };
+
+    for k in 0..S::SIZE {
+        let (plus, minus) = (gradient_at(k, eps), gradient_at(k, -eps));
+
+        for i in 0..S::SIZE {
+            let (analytic, numeric) = (analytic[i * S::SIZE + k], (plus[i] - minus[i]) / (2.0 * eps));
+
+            assert!((analytic - numeric).abs() < 1e-5 + 1e-3 * numeric.abs(), "Hessian at [{i}, {k}] is {analytic}, but finite differences give {numeric}");
+        }
+    }
+}
+
+#[test]
+fn second_order_layout_ops() {
+    let device = device();
+    let x = input::<Rank2<4, 3>>(&device);
+    let indices = device.constant::<Rank1<4>, u8>(&[3, 0, 3, 1]);
+
+    // Each op is followed by one that isn't linear, so the Hessian isn't zero
+    check_hessian(&x, |x| sum(tanh(narrow::<_, Rank2<4, 2>, _>(x, 1, 1))));
+    check_hessian(&x, |x| sum(tanh(index_select::<_, Rank2<5, 3>, _>(x, 0, &[3, 0, 3, 1, 3]))));
+    check_hessian(&x, |x| sum(tanh(concat::<_, _, Rank2<4, 6>, _>(x.clone(), scale(x, 2.0), 1))));
+    check_hessian(&x, |x| sum(tanh(stack::<_, Rank3<2, 4, 3>, _>(vec![x.clone(), mul(x.clone(), x)]))));
+    check_hessian(&x, |x| sum(tanh(embedding(x, indices.clone()))));
+
+    check_second_order(&x, || sum(mul(narrow::<_, Rank2<2, 3>, _>(x.clone(), 0, 2), index_select::<_, Rank2<2, 3>, _>(x.clone(), 0, &[1, 1]))));
+}
+
+#[test]
+fn second_order_checkpoint() {
+    let device = device();
+    let x = input::<Rank1<3>>(&device);
+    let w = input::<Rank2<3, 2>>(&device);
+    w.set_requires_grad(true);
+
+    let segment = {
+        let w = w.clone();
+
+        move |x: Tensor<Rank1<3>, f64>| tanh(matmul(x, w.clone()))
+    };
+
+    check_hessian(&x, |x| sum(mul(checkpoint(segment.clone(), x, vec![w.as_ref()]), device.ones())));
+
+    // The parameter's gradient comes out of the recomputed graph
+    let loss = sum(checkpoint(segment.clone(), x.clone(), vec![w.as_ref()]));
+    let gradient = loss.gradients().get(&w).expect("the parameter should have a gradient");
+
+    loss.back();
+
+    for (e, f) in device.get_gradient_buffer(&w).iter().zip(device.get_tensor_buffer(&gradient).iter()) {
+        assert!((e - f).abs() < 1e-12, "{e} != {f}");
+    }
+}
+
+#[test]
+fn gradient_penalty() {
+    let device = device();
+    let x = input::<Rank1<3>>(&device);
+    let w = input::<Rank2<3, 2>>(&device);
+    x.set_requires_grad(true);
+
+    // The squared norm of the gradient with respect to the input, as in WGAN-GP
+    check(&[&w], || {
+        let gradient = sum(tanh(matmul(x.clone(), w.clone()))).gradients().get(&x).unwrap();
+
+        sum(mul(gradient.clone(), gradient))
+    });
+}
+
+#[test]
+fn gradients_match_back() {
+    let device = device();
+    let x = input::<Rank2<2, 3>>(&device);
+    let w = input::<Rank2<3, 4>>(&device);
+    let targets = input::<Rank2<2, 4>>(&device);
+    w.set_requires_grad(true);
+
+    let loss = mse(softmax(matmul(relu(x.clone()), w.clone())), targets.clone());
+
+    let gradient = loss.gradients().get(&w).unwrap();
+    assert!(device.get_gradient_buffer(&w).iter().all(|g| *g == 0.0), "gradients() should leave gradient buffers alone");
+
+    loss.back();
+
+    let expected = device.get_gradient_buffer(&w);
+    let found = device.get_tensor_buffer(&gradient);
+
+    for (e, f) in expected.iter().zip(found.iter()) {
+        assert!((e - f).abs() < 1e-12, "{e} != {f}");
+    }
+}
+
+#[test]
+fn functional_derivatives() {
+    let device = device();
+    let x = input::<Rank1<3>>(&device);
+    let a = input::<Rank2<3, 3>>(&device);
+    a.set_requires_grad(true);
+
+    let gradient = grad(|x| sum(tanh(x)), &x);
+    let expected = device.get_tensor_buffer(&x).iter().map(|x| 1.0 - x.tanh().powi(2)).collect::<Vec<_>>();
+
+    for (g, e) in device.get_tensor_buffer(&gradient).iter().zip(&expected) {
+        assert!((g - e).abs() < 1e-12);
+    }
+
+    // The Jacobian of x A is A^T
+    let jacobian = jacobian(|x| matmul(x, a.clone()), &x);
+    assert_eq!(jacobian.dims(), &[3, 3]);
+    assert_eq!(device.get_tensor_buffer(&jacobian), device.get_tensor_buffer(&a.transpose().contiguous()));
+
+    // The Hessian of x A x is A + A^T
+    let hessian = hessian(|x| sum(mul(x.clone(), matmul(x, a.clone()))), &x);
+    let a_buffer = device.get_tensor_buffer(&a);
+
+    for (index, h) in device.get_tensor_buffer(&hessian).iter().enumerate() {
+        let (i, k) = (index / 3, index % 3);
+
+        assert!((h - (a_buffer[i * 3 + k] + a_buffer[k * 3 + i])).abs() < 1e-12);
+    }
+
+    // Neither x nor the parameter the functions used were touched
+    assert!(!x.requires_grad());
+    assert!(device.get_gradient_buffer(&a).iter().all(|g| *g == 0.0));
+}
+
+#[test]
+fn functional_derivatives_of_first_order_ops() {
+    let device = device();
+    let x = input::<Rank1<3>>(&device);
+    let w = input::<Rank1<6>>(&device);
+    w.set_requires_grad(true);
+
+    // Computed before the calls, so its graph belongs to the caller
+    let scaled = scale(w.clone(), 2.0);
+
+    // Differentiated with ordinary backward passes, which custom ops need for their Jacobian
+    let gradient = grad(|x| {
+        let repeated: Tensor<Rank1<6>, f64> = concat(x.clone(), index_select::<_, Rank1<3>, _>(x, 0, &[2, 0, 0]), 0);
+
+        sum(mul(tanh(repeated), scaled.clone()))
+    }, &x);
+
+    let (x_buffer, s) = (device.get_tensor_buffer(&x), device.get_tensor_buffer(&scaled));
+    let d = x_buffer.iter().map(|x| 1.0 - x.tanh().powi(2)).collect::<Vec<_>>();
+    let expected = [d[0] * (s[0] + s[4] + s[5]), d[1] * s[1], d[2] * (s[2] + s[3])];
+
+    for (g, e) in device.get_tensor_buffer(&gradient).iter().zip(&expected) {
+        assert!((g - e).abs() < 1e-12);
+    }
+
+    // The Jacobian of x * x is diag(2x)
+    let jacobian = jacobian(|x| custom_op::<Rank1<3>, _, _>(Multiply, &[x.to_dyn(), x.to_dyn()]), &x);
+    let expected = (0..9).map(|i| if i % 4 == 0 { 2.0 * x_buffer[i / 4] } else { 0.0 }).collect::<Vec<_>>();
+
+    assert_eq!(device.get_tensor_buffer(&jacobian), expected);
+
+    // The parameter's gradient is untouched, and its graph can still be backpropagated through
+    assert!(device.get_gradient_buffer(&w).iter().all(|g| *g == 0.0));
+
+    sum(scaled).back();
+    assert_eq!(device.get_gradient_buffer(&w), &[2.0; 6]);
+    assert!(w.requires_grad());
+}
+
+#[test]
+fn checkpointed_segment() {
+    let device = device();
+    let x = input::<Rank1<3>>(&device);
+    let w1 = input::<Rank2<3, 4>>(&device);
+    let w2 = input::<Rank2<4, 2>>(&device);
+
+    let segment = {
+        let (w1, w2) = (w1.clone(), w2.clone());
+
+        move |x: Tensor<Rank1<3>, f64>| {
+            // Dropout checks that the same elements are dropped when the segment is recomputed
+            let hidden = dropout(tanh(matmul(x, w1.clone())), 0.5);
+
+            sigmoid(matmul(hidden, w2.clone()))
+        }
+    };
+
+    let inputs = [x.as_ref(), w1.as_ref(), w2.as_ref()];
+
+    let result = gradcheck(&inputs, || {
+        device.seed(1);
+
+        let output = checkpoint(segment.clone(), x.clone(), vec![w1.as_ref(), w2.as_ref()]);
+
+        // Nothing inside the segment is kept alive by the graph
+        assert_eq!(output.to_dot().matches("->").count(), 3);
+
+        output
+    });
+
+    if let Err(error) = result {
+        panic!("{error}");
+    }
+}
+
+#[test]
+fn losses_backpropagated_together() {
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    let targets = input::<Rank1<3>>(&device);
+    a.set_requires_grad(true);
+
+    // Two losses sharing a hidden tensor, summed in one pass and checked against a single summed loss
+    let hidden = tanh(a.clone());
+    let first = mse(hidden.clone(), targets.clone());
+    let second = sum(relu(hidden.clone()));
+
+    Backward::new().root(&first).root(&second).run();
+    let together = device.get_gradient_buffer(&a).to_vec();
+
+    a.as_ref().gradient_mut().fill(0.0);
+
+    let hidden = tanh(a.clone());
+    (mse(hidden.clone(), targets.clone()) + sum(relu(hidden))).back();
+
+    assert_eq!(device.get_gradient_buffer(&a), together);
+}
+
+#[test]
+fn retained_graph_accumulates() {
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    a.set_requires_grad(true);
+
+    let loss = sum(tanh(a.clone()));
+
+    Backward::new().root(&loss).retain_graph(true).run();
+    let once = device.get_gradient_buffer(&a).to_vec();
+
+    loss.back();
+    let twice = once.iter().map(|g| g * 2.0).collect::<Vec<_>>();
+
+    assert_eq!(device.get_gradient_buffer(&a), twice);
+}
+
+#[test]
+#[should_panic(expected = "a second time")]
+fn second_backward_needs_retained_graph() {
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    a.set_requires_grad(true);
+
+    let hidden = tanh(a.clone());
+
+    sum(hidden.clone()).back();
+    mse(hidden, a.clone()).back();
+}
+
+#[test]
+fn backward_from_non_scalar() {
+    let device = device();
+    let a = input::<Rank1<3>>(&device);
+    a.set_requires_grad(true);
+
+    scale(a.clone(), 3.0).backward_with(&[1.0, 0.0, -2.0]);
+
+    assert_eq!(device.get_gradient_buffer(&a), &[3.0, 0.0, -6.0]);
+}
+
+#[test]
+fn device_shared_between_threads() {
+    let device = device();
+    let data = [0.5, -0.25, 0.75];
+
+    let gradient_of = |device: &Device| {
+        let a = device.constant::<Rank1<3>, f64>(&data);
+        a.set_requires_grad(true);
+
+        sum(mul(tanh(a.clone()), a.clone())).back();
+
+        device.get_gradient_buffer(&a).into_owned()
+    };
+
+    let expected = gradient_of(&device);
+
+    let gradients = std::thread::scope(|scope| {
+        let threads = (0..4)
+            .map(|_| scope.spawn(|| (0..50).map(|_| gradient_of(&device)).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect::<Vec<_>>()
+    });
+
+    assert!(gradients.iter().all(|gradient| *gradient == expected));
+}
+
+#[test]
+fn memory_released_between_steps() {
+    let device = device();
+    let w = input::<Rank2<3, 2>>(&device);
+    w.set_requires_grad(true);
+
+    // Held the way an optimizer holds the parameters of a model
+    let parameters = vec![w.as_ref()];
+
+    let step = || {
+        let x = input::<Rank1<3>>(&device);
+
+        sum(tanh(matmul(x, w.clone()))).back();
+
+        let parameter = &parameters[0];
+        let gradient = parameter.gradient();
+
+        for (p, g) in parameter.buffer_mut().iter_mut().zip(gradient.iter()) {
+            *p -= 0.1 * g;
+        }
+    };
+
+    step();
+    let after_first = device.memory_stats();
+
+    step();
+
+    // Only the parameter and its gradient survive a step
+    assert_eq!(after_first, device.memory_stats());
+    assert_eq!(after_first.live_tensors, 1);
+    assert_eq!(after_first.bytes_allocated, 12 * std::mem::size_of::<f64>());
+    assert!(after_first.peak_bytes > after_first.bytes_allocated);
+
+    // The optimizer's reference keeps the parameter alive, and it is freed along with the reference
+    drop(w);
+    assert_eq!(device.memory_stats().live_tensors, 1);
+
+    drop(parameters);
+    assert_eq!(device.memory_stats().live_tensors, 0);
+    assert_eq!(device.memory_stats().bytes_allocated, 0);
+}
+
+#[test]
+fn buffers_reused_between_steps() {
+    let device = device();
+    let w = input::<Rank2<3, 4>>(&device);
+    w.set_requires_grad(true);
+
+    let step = || {
+        let x = input::<Rank1<3>>(&device);
+        let targets = positive::<Rank1<4>>(&device);
+
+        mse(softmax(matmul(x, w.clone())), targets).back();
+        device.zero_grad();
+
+        device.memory_stats()
+    };
+
+    // The first step fills the pool, and later ones take their buffers from it and give them back
+    let first = step();
+    let (hits, misses) = (device.buffer_pool_hits(), device.buffer_pool_misses());
+
+    assert!(first.pooled_bytes > 0);
+    assert_eq!(step(), first);
+
+    let reused = device.buffer_pool_hits() - hits;
+    assert_eq!(step(), first);
+
+    // Every buffer of a later step, forward or backward, comes from the pool, so none are allocated
+    assert!(reused >= misses, "{reused} buffers reused after the first step allocated {misses}");
+    assert_eq!(device.buffer_pool_hits(), hits + 2 * reused);
+    assert_eq!(device.buffer_pool_misses(), misses);
+
+    device.clear_buffer_pool();
+    assert_eq!(device.memory_stats().pooled_bytes, 0);
+}
+
+#[test]
+fn gradcheck_restores_requires_grad() {
+    let device = device();
+    let constant = input::<Rank1<3>>(&device);
+    let parameter = input::<Rank1<3>>(&device);
+    parameter.set_requires_grad(true);
+
+    check(&[&constant, &parameter], || mul(constant.clone(), parameter.clone()));
+
+    assert!(!constant.requires_grad());
+    assert!(parameter.requires_grad());
+
+    // Including when the check fails, here because detaching one factor of x * x hides half the gradient
+    assert!(gradcheck(&[constant.as_ref()], || mul(constant.detach(), constant.clone())).is_err());
+    assert!(!constant.requires_grad());
+}